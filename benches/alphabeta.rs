@@ -0,0 +1,40 @@
+//! Compares the two ways to compute the $e(\alpha,\beta)^{\sum r}$ term of the
+//! aggregated Groth16 equation, see `AlphaBeta::fastest`: a Miller loop on
+//! $\alpha^{\sum r}$ and $\beta$, whose final exponentiation is shared with the
+//! other pairings, or an exponentiation in $G_T$ of the precomputed
+//! $e(\alpha,\beta)$.
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, UniformRand};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand_core::SeedableRng;
+
+fn bench_alphabeta<E: PairingEngine>(c: &mut Criterion, curve: &str) {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+    let alpha = E::G1Projective::rand(&mut rng).into_affine();
+    let beta = E::G2Projective::rand(&mut rng).into_affine();
+    let alphabeta = E::pairing(alpha, beta);
+    let r_sum = E::Fr::rand(&mut rng);
+    let left = E::Fqk::rand(&mut rng);
+    let right = E::Fqk::rand(&mut rng);
+
+    let mut group = c.benchmark_group("alphabeta");
+    group.bench_function(BenchmarkId::new("miller_loop", curve), |b| {
+        b.iter(|| {
+            let alpha_r_sum = alpha.mul(r_sum).into_affine();
+            let ml = E::miller_loop([&(alpha_r_sum.into(), beta.into())]);
+            left * ml
+        })
+    });
+    group.bench_function(BenchmarkId::new("precomputed", curve), |b| {
+        b.iter(|| right * alphabeta.pow((-r_sum).into_repr()))
+    });
+    group.finish();
+}
+
+fn alphabeta(c: &mut Criterion) {
+    bench_alphabeta::<ark_bn254::Bn254>(c, "bn254");
+    bench_alphabeta::<ark_bls12_381::Bls12_381>(c, "bls12_381");
+}
+
+criterion_group!(benches, alphabeta);
+criterion_main!(benches);
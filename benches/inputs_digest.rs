@@ -0,0 +1,43 @@
+//! Compares the two ways to include the public inputs of the aggregated proofs
+//! in the transcript: appending them, which serializes and hashes them all in
+//! sequence, or appending their digest with `Transcript::append_inputs_digest`,
+//! which hashes them in parallel. Both many proofs with a few inputs and a few
+//! proofs with many inputs are measured.
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand_core::SeedableRng;
+use snarkpack::transcript::{new_merlin_transcript, Transcript};
+
+fn bench_inputs(c: &mut Criterion, name: &str, nproofs: usize, ninputs: usize) {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+    let inputs = (0..nproofs)
+        .map(|_| (0..ninputs).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("inputs_digest");
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("append", name), |b| {
+        b.iter(|| {
+            let mut transcript = new_merlin_transcript(b"bench");
+            transcript.append(b"public-inputs", &inputs);
+            transcript.challenge_scalar::<Fr>(b"challenge")
+        })
+    });
+    group.bench_function(BenchmarkId::new("digest", name), |b| {
+        b.iter(|| {
+            let mut transcript = new_merlin_transcript(b"bench");
+            transcript.append_inputs_digest(b"public-inputs-digest", &inputs);
+            transcript.challenge_scalar::<Fr>(b"challenge")
+        })
+    });
+    group.finish();
+}
+
+fn inputs_digest(c: &mut Criterion) {
+    bench_inputs(c, "8192x8", 1 << 13, 8);
+    bench_inputs(c, "1x65536", 1, 1 << 16);
+}
+
+criterion_group!(benches, inputs_digest);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+//! Benchmarks the pairing evaluations of the prover, the inner pairing
+//! products of the commitments, and of the verifier, the batched
+//! `PairingCheck`s, for batches of $2^{10}$ to $2^{16}$ proofs. The largest
+//! batch is set with `SNARKPACK_BENCH_MAX_LOG`, e.g. to 12 on small machines.
+//! The `per_pair` and `single_loop` benchmarks are the baselines of the
+//! previous evaluation, with a miller loop per pair in the `PairingCheck`s and
+//! a single miller loop over all the pairs of an inner pairing product.
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, UniformRand};
+use ark_groth16::{create_random_proof, generate_random_parameters, prepare_verifying_key};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand_core::SeedableRng;
+use rayon::prelude::*;
+use snarkpack::{
+    aggregate_proofs, commitment, pairing_check::PairingCheck, srs::setup_fake_srs,
+    transcript::new_merlin_transcript, verify_aggregate_proof,
+};
+use std::sync::Mutex;
+
+type Fqk = <Bn254 as PairingEngine>::Fqk;
+
+// c = a * b with c public
+#[derive(Clone)]
+struct MulCircuit {
+    a: Fr,
+    b: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for MulCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let a = cs.new_witness_variable(|| Ok(self.a))?;
+        let b = cs.new_witness_variable(|| Ok(self.b))?;
+        let c = cs.new_input_variable(|| Ok(self.a * self.b))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+    }
+}
+
+fn max_log() -> usize {
+    std::env::var("SNARKPACK_BENCH_MAX_LOG")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(16)
+}
+
+fn pairing_check(c: &mut Criterion) {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+    let a = ark_bn254::G1Projective::rand(&mut rng).into_affine();
+    let b = ark_bn254::G2Projective::rand(&mut rng).into_affine();
+    let out = Bn254::pairing(a, b);
+    let prepared = <Bn254 as PairingEngine>::G2Prepared::from(b);
+    let rng = Mutex::new(rng);
+    // e(A,v1)e(w1,B) as in the TIPP verifier
+    c.bench_function("pairing_check/rand", |bench| {
+        bench.iter(|| PairingCheck::<Bn254>::rand(&rng, &[(&a, &b), (&a, &b)], &out))
+    });
+    c.bench_function("pairing_check/rand_prepared", |bench| {
+        bench.iter(|| {
+            PairingCheck::<Bn254>::rand_prepared(&rng, &[(&a, &prepared), (&a, &prepared)], &out)
+        })
+    });
+    c.bench_function("pairing_check/per_pair", |bench| {
+        bench.iter(|| {
+            let coeff = Fr::rand(&mut *rng.lock().unwrap());
+            let miller_out = [(&a, &b), (&a, &b)]
+                .par_iter()
+                .map(|(a, b)| {
+                    let na = a.mul(coeff).into_affine();
+                    Bn254::miller_loop([&(na.into(), (**b).into())])
+                })
+                .product::<Fqk>();
+            (miller_out, out.pow(coeff.into_repr()))
+        })
+    });
+}
+
+/// The inner pairing product as computed before the chunked miller loops:
+/// the pairs are prepared in parallel and evaluated in one miller loop.
+fn single_loop(left: &[G1Affine], right: &[G2Affine]) -> Fqk {
+    let pairs = left
+        .par_iter()
+        .map(|a| (*a).into())
+        .zip(right.par_iter().map(|b| (*b).into()))
+        .collect::<Vec<_>>();
+    Bn254::final_exponentiation(&Bn254::miller_loop(pairs.iter())).unwrap()
+}
+
+fn aggregation(c: &mut Criterion) {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+    let circuit = MulCircuit {
+        a: Fr::rand(&mut rng),
+        b: Fr::rand(&mut rng),
+    };
+    let params = generate_random_parameters::<Bn254, _, _>(circuit.clone(), &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    // the same proof aggregated many times costs as much as distinct proofs
+    let proof = create_random_proof(circuit.clone(), &params, &mut rng).unwrap();
+    let max = 1 << max_log();
+    let srs = setup_fake_srs::<Bn254, _>(&mut rng, max);
+
+    let mut group = c.benchmark_group("aggregation");
+    group.sample_size(10);
+    for log in (10..=max_log()).step_by(2) {
+        let n = 1 << log;
+        let (prover_srs, ver_srs) = srs.specialize(n);
+        let proofs = vec![proof.clone(); n];
+        let inputs = vec![vec![circuit.a * circuit.b]; n];
+        group.bench_with_input(BenchmarkId::new("commit", n), &n, |b, _| {
+            b.iter(|| {
+                let a = proofs.iter().map(|p| p.a).collect::<Vec<_>>();
+                let bs = proofs.iter().map(|p| p.b).collect::<Vec<_>>();
+                commitment::pair::<Bn254>(&prover_srs.vkey, &prover_srs.wkey, &a, &bs).unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("commit_single_loop", n), &n, |b, _| {
+            b.iter(|| {
+                let a = proofs.iter().map(|p| p.a).collect::<Vec<_>>();
+                let bs = proofs.iter().map(|p| p.b).collect::<Vec<_>>();
+                let (vkey, wkey) = (&prover_srs.vkey, &prover_srs.wkey);
+                let ((t1, t2), (u1, u2)) = rayon::join(
+                    || rayon::join(|| single_loop(&a, &vkey.a), || single_loop(&wkey.a, &bs)),
+                    || rayon::join(|| single_loop(&a, &vkey.b), || single_loop(&wkey.b, &bs)),
+                );
+                (t1 * t2, u1 * u2)
+            })
+        });
+        let aggregate =
+            aggregate_proofs(&prover_srs, &mut new_merlin_transcript(b"bench"), &proofs).unwrap();
+        group.bench_with_input(BenchmarkId::new("verify", n), &n, |b, _| {
+            b.iter(|| {
+                verify_aggregate_proof(
+                    &ver_srs,
+                    &pvk,
+                    &inputs,
+                    &aggregate,
+                    &mut rng,
+                    &mut new_merlin_transcript(b"bench"),
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pairing_check, aggregation);
+criterion_main!(benches);
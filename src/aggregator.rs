@@ -0,0 +1,438 @@
+//! Aggregation of proofs received one at a time, possibly by several workers.
+//!
+//! The commitments to A, B and C are updated as proofs are pushed to an
+//! `Aggregator`, which hands the points themselves to a `ProofStore` until
+//! `finalize`: `MemoryStore` keeps them in memory, `FileStore` writes them out
+//! so only the commitments and a bounded buffer stay in memory.
+//!
+//! When the proofs are split in shards, each worker exports its
+//! state as a `Shard`, which contains the A, B and C points of all its proofs
+//! besides the commitments. Only the commitments are merged homomorphically:
+//! the coordinator gathers the full vectors from the shards and runs GIPA over
+//! all of them in `finalize`, so the shards are as large as the proofs they
+//! hold and the GIPA work is not split. See the `distributed` module to spread
+//! GIPA itself over several workers.
+use ark_ec::PairingEngine;
+use ark_groth16::{Proof, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{convert::TryInto, fmt::Debug, ops::MulAssign, string::ToString, vec::Vec, One};
+#[cfg(feature = "std")]
+use std::io::{BufReader, BufWriter, Seek, SeekFrom};
+
+use crate::{
+    commitment::Output,
+    ip,
+    proof::AggregateProof,
+    prover::aggregate_committed,
+    srs::ProverSRS,
+    transcript::{append_statement, digest_from_leaves, inputs_leaf, InputsBinding, Transcript},
+    Error,
+};
+
+/// Aggregator creates an aggregate proof from proofs received one at a time.
+/// The commitments to A, B and C are updated as proofs are pushed, and the
+/// A, B and C points plus a hash of the public inputs of each proof are handed
+/// to its `ProofStore` until `finalize`. The resulting proof is bound to its
+/// statement the same way as in `aggregate_and_bind`, so it must be verified
+/// with `verify_bound`.
+///
+/// With a `FileStore`, memory stays bounded while proofs are pushed: only the
+/// commitments, the store's buffer and the SRS are kept. The points are read
+/// back in `finalize`, as the challenge $r$ and the first round of GIPA depend
+/// on all the proofs and can only be computed once they are all pushed. GIPA
+/// then folds that single copy in place.
+///
+/// The proofs can also be split in contiguous shards, each accumulated by a
+/// different worker with `Aggregator::shard`. The coordinator combines the
+/// resulting `Shard`s with `Aggregator::merge` into the same state as if it
+/// had received all proofs itself, and then calls `finalize`, which runs GIPA
+/// over all the proofs.
+#[derive(Debug)]
+pub struct Aggregator<'a, E: PairingEngine, S = MemoryStore<E>> {
+    srs: &'a ProverSRS<E>,
+    vk: &'a VerifyingKey<E>,
+    binding: InputsBinding,
+    /// position of the first proof pushed in the aggregated vectors
+    offset: usize,
+    /// the A, B and C points and the leaves of the public inputs digest
+    store: S,
+    /// miller loop outputs of the commitments to A and B, and to C. The final
+    /// exponentiation is only done once when finalizing.
+    ml_ab: (E::Fqk, E::Fqk),
+    ml_c: (E::Fqk, E::Fqk),
+}
+
+impl<'a, E: PairingEngine + Debug> Aggregator<'a, E> {
+    /// Returns an aggregator for as many proofs as the given SRS has been
+    /// specialized for, keeping the proofs in memory. Public inputs are bound
+    /// in the transcript unless specified otherwise with `binding`.
+    pub fn new(srs: &'a ProverSRS<E>, vk: &'a VerifyingKey<E>) -> Self {
+        Self::shard(srs, vk, 0)
+    }
+
+    /// Returns an aggregator for the shard of proofs starting at position
+    /// `offset` in the aggregate proof. Its state is exported with
+    /// `into_shard` once all the proofs of the shard have been pushed.
+    pub fn shard(srs: &'a ProverSRS<E>, vk: &'a VerifyingKey<E>, offset: usize) -> Self {
+        let mut agg = Self::with_store(srs, vk, MemoryStore::with_capacity(srs.n));
+        agg.offset = offset;
+        agg
+    }
+
+    /// Combines the shards accumulated by different workers into an aggregator
+    /// holding all the proofs. The shards can be given in any order but must
+    /// cover contiguous ranges of proofs starting at 0, computed with the same
+    /// SRS. Public inputs are bound in the transcript unless specified
+    /// otherwise with `binding`, in which case the shards must have been
+    /// accumulated with the same binding.
+    pub fn merge(
+        srs: &'a ProverSRS<E>,
+        vk: &'a VerifyingKey<E>,
+        mut shards: Vec<Shard<E>>,
+    ) -> Result<Self, Error> {
+        shards.sort_by_key(|shard| shard.offset);
+        let mut agg = Self::new(srs, vk);
+        for shard in shards {
+            if shard.srs_hash != srs.srs_hash {
+                return Err(Error::InvalidSRS(
+                    "shard computed with a different SRS".to_string(),
+                ));
+            }
+            let store = &mut agg.store;
+            if shard.offset != store.a.len() {
+                return Err(Error::InvalidProof(format!(
+                    "missing or overlapping shard at proof {}",
+                    store.a.len()
+                )));
+            }
+            let len = shard.a.len();
+            if shard.b.len() != len || shard.c.len() != len || store.a.len() + len > srs.n {
+                return Err(Error::InvalidProof("invalid shard length".to_string()));
+            }
+            // the leaves are empty if the inputs are not bound in the transcript
+            if !shard.leaves.is_empty() && shard.leaves.len() != len {
+                return Err(Error::InvalidProof(
+                    "invalid shard inputs length".to_string(),
+                ));
+            }
+            for leaf in shard.leaves {
+                let leaf = leaf
+                    .try_into()
+                    .map_err(|_| Error::InvalidProof("invalid shard inputs hash".to_string()))?;
+                store.leaves.push(leaf);
+            }
+            agg.ml_ab.0.mul_assign(&shard.ml_ab.0);
+            agg.ml_ab.1.mul_assign(&shard.ml_ab.1);
+            agg.ml_c.0.mul_assign(&shard.ml_c.0);
+            agg.ml_c.1.mul_assign(&shard.ml_c.1);
+            store.a.extend(shard.a);
+            store.b.extend(shard.b);
+            store.c.extend(shard.c);
+        }
+        Ok(agg)
+    }
+
+    /// Exports the state of the aggregator, to be sent to the coordinator
+    /// calling `merge`.
+    pub fn into_shard(self) -> Shard<E> {
+        Shard {
+            offset: self.offset,
+            srs_hash: self.srs.srs_hash.clone(),
+            a: self.store.a,
+            b: self.store.b,
+            c: self.store.c,
+            leaves: self.store.leaves.iter().map(|leaf| leaf.to_vec()).collect(),
+            ml_ab: self.ml_ab,
+            ml_c: self.ml_c,
+        }
+    }
+}
+
+impl<'a, E: PairingEngine + Debug, S: ProofStore<E>> Aggregator<'a, E, S> {
+    /// Returns an aggregator for as many proofs as the given SRS has been
+    /// specialized for, handing the proofs to `store` until `finalize`.
+    pub fn with_store(srs: &'a ProverSRS<E>, vk: &'a VerifyingKey<E>, store: S) -> Self {
+        Self {
+            srs,
+            vk,
+            binding: InputsBinding::default(),
+            offset: 0,
+            store,
+            ml_ab: (E::Fqk::one(), E::Fqk::one()),
+            ml_c: (E::Fqk::one(), E::Fqk::one()),
+        }
+    }
+
+    /// Sets how the public inputs are bound in the transcript.
+    pub fn binding(mut self, binding: InputsBinding) -> Self {
+        self.binding = binding;
+        self
+    }
+
+    /// Returns the number of proofs pushed so far.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns true if no proof has been pushed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds the next proof to aggregate, with its public inputs.
+    pub fn push(&mut self, proof: &Proof<E>, public_inputs: &[E::Fr]) -> Result<(), Error> {
+        let i = self.offset + self.len();
+        if i >= self.srs.n {
+            return Err(Error::InvalidSRS("SRS len != proofs len".to_string()));
+        }
+        if public_inputs.len() + 1 != self.vk.gamma_abc_g1.len() {
+            return Err(Error::MalformedVerifyingKey);
+        }
+        let (v1, v2) = (self.srs.vkey.a[i], self.srs.vkey.b[i]);
+        let (w1, w2) = (self.srs.wkey.a[i], self.srs.wkey.b[i]);
+        // same terms as in commitment::pair and commitment::single_g1
+        try_par! {
+            let t_ab = ip::pairing_miller_affine::<E>(&[proof.a, w1], &[v1, proof.b]),
+            let u_ab = ip::pairing_miller_affine::<E>(&[proof.a, w2], &[v2, proof.b]),
+            let t_c = ip::pairing_miller_affine::<E>(&[proof.c], &[v1]),
+            let u_c = ip::pairing_miller_affine::<E>(&[proof.c], &[v2])
+        };
+        self.ml_ab.0.mul_assign(&t_ab);
+        self.ml_ab.1.mul_assign(&u_ab);
+        self.ml_c.0.mul_assign(&t_c);
+        self.ml_c.1.mul_assign(&u_c);
+        let leaf = match self.binding {
+            InputsBinding::Absorb => Some(inputs_leaf(public_inputs)),
+            InputsBinding::Fixed => None,
+        };
+        self.store.push(proof, leaf)
+    }
+
+    /// Creates the aggregate proof once all proofs have been pushed.
+    pub fn finalize<T: Transcript>(self, transcript: &mut T) -> Result<AggregateProof<E>, Error> {
+        let n = self.len();
+        if self.offset != 0 || n != self.srs.n {
+            return Err(Error::InvalidSRS("SRS len != proofs len".to_string()));
+        }
+        if n < 2 || !n.is_power_of_two() {
+            return Err(Error::InvalidProof(
+                "invalid proof size: not power of two".to_string(),
+            ));
+        }
+        let store = self.store.load()?;
+        if store.a.len() != n || store.b.len() != n || store.c.len() != n {
+            return Err(Error::InvalidProof(
+                "store returned the wrong number of proofs".to_string(),
+            ));
+        }
+        let digest = match self.binding {
+            InputsBinding::Absorb if store.leaves.len() != n => {
+                return Err(Error::InvalidProof(
+                    "public inputs of some proofs are missing".to_string(),
+                ))
+            }
+            InputsBinding::Absorb => Some(digest_from_leaves(store.leaves)),
+            InputsBinding::Fixed => None,
+        };
+        append_statement(transcript, self.vk, &self.srs.srs_hash, n, digest)?;
+
+        let final_exp = |ml: &E::Fqk| E::final_exponentiation(ml).ok_or(Error::InvalidPairing);
+        let com_ab = Output(final_exp(&self.ml_ab.0)?, final_exp(&self.ml_ab.1)?);
+        let com_c = Output(final_exp(&self.ml_c.0)?, final_exp(&self.ml_c.1)?);
+        aggregate_committed(
+            self.srs, transcript, store.a, store.b, store.c, com_ab, com_c,
+        )
+        .map(|(proof, _)| proof)
+    }
+}
+
+/// ProofStore holds the A, B and C points of the proofs pushed to an
+/// `Aggregator`, and the leaves of their public inputs, until `finalize`
+/// needs them all to run GIPA.
+pub trait ProofStore<E: PairingEngine> {
+    /// Stores the points of the next proof, with the leaf of its public inputs
+    /// if they are bound in the transcript.
+    fn push(&mut self, proof: &Proof<E>, leaf: Option<[u8; 32]>) -> Result<(), Error>;
+
+    /// Returns the number of proofs stored.
+    fn len(&self) -> usize;
+
+    /// Returns true if no proof is stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns all the points and leaves in memory, in the order they were
+    /// pushed.
+    fn load(self) -> Result<MemoryStore<E>, Error>;
+}
+
+/// MemoryStore keeps the points of the proofs in memory. It is the store of
+/// the aggregators of shards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryStore<E: PairingEngine> {
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+    c: Vec<E::G1Affine>,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl<E: PairingEngine> MemoryStore<E> {
+    /// Returns an empty store with room for `n` proofs.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            a: Vec::with_capacity(n),
+            b: Vec::with_capacity(n),
+            c: Vec::with_capacity(n),
+            leaves: Vec::with_capacity(n),
+        }
+    }
+}
+
+impl<E: PairingEngine> Default for MemoryStore<E> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<E: PairingEngine> ProofStore<E> for MemoryStore<E> {
+    fn push(&mut self, proof: &Proof<E>, leaf: Option<[u8; 32]>) -> Result<(), Error> {
+        self.a.push(proof.a);
+        self.b.push(proof.b);
+        self.c.push(proof.c);
+        self.leaves.extend(leaf);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.a.len()
+    }
+
+    fn load(self) -> Result<Self, Error> {
+        Ok(self)
+    }
+}
+
+/// FileStore writes the points of the proofs to a file, or any seekable
+/// stream, through a buffer of bounded size, and reads them back when the
+/// aggregator is finalized. The points are written uncompressed and read back
+/// without checks, so the file must not be modified in between.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FileStore<E, F: Write> {
+    writer: BufWriter<F>,
+    len: usize,
+    _e: std::marker::PhantomData<E>,
+}
+
+#[cfg(feature = "std")]
+impl<E: PairingEngine, F: Read + Write + Seek> FileStore<E, F> {
+    /// Returns a store writing to `file` from its start, through a buffer of
+    /// `buffer` bytes.
+    pub fn new(file: F, buffer: usize) -> Self {
+        Self {
+            writer: BufWriter::with_capacity(buffer, file),
+            len: 0,
+            _e: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: PairingEngine, F: Read + Write + Seek> ProofStore<E> for FileStore<E, F> {
+    fn push(&mut self, proof: &Proof<E>, leaf: Option<[u8; 32]>) -> Result<(), Error> {
+        proof.a.serialize_uncompressed(&mut self.writer)?;
+        proof.b.serialize_uncompressed(&mut self.writer)?;
+        proof.c.serialize_uncompressed(&mut self.writer)?;
+        // the leaf is preceded by whether there is one
+        match leaf {
+            Some(leaf) => self
+                .writer
+                .write_all(&[1])
+                .and_then(|_| self.writer.write_all(&leaf)),
+            None => self.writer.write_all(&[0]),
+        }
+        .map_err(SerializationError::from)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn load(self) -> Result<MemoryStore<E>, Error> {
+        let mut file = self
+            .writer
+            .into_inner()
+            .map_err(|e| SerializationError::from(e.into_error()))?;
+        file.seek(SeekFrom::Start(0))
+            .map_err(SerializationError::from)?;
+        let mut reader = BufReader::new(file);
+        let mut store = MemoryStore::with_capacity(self.len);
+        for _ in 0..self.len {
+            let proof = Proof {
+                a: E::G1Affine::deserialize_unchecked(&mut reader)?,
+                b: E::G2Affine::deserialize_unchecked(&mut reader)?,
+                c: E::G1Affine::deserialize_unchecked(&mut reader)?,
+            };
+            let mut flag = [0u8];
+            reader
+                .read_exact(&mut flag)
+                .map_err(SerializationError::from)?;
+            let leaf = if flag[0] == 1 {
+                let mut leaf = [0u8; 32];
+                reader
+                    .read_exact(&mut leaf)
+                    .map_err(SerializationError::from)?;
+                Some(leaf)
+            } else {
+                None
+            };
+            store.push(&proof, leaf)?;
+        }
+        Ok(store)
+    }
+}
+
+/// Shard is the state of an aggregator which received a contiguous range of
+/// the proofs to aggregate. It is created with `Aggregator::into_shard` and
+/// combined with the other shards using `Aggregator::merge`.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct Shard<E: PairingEngine> {
+    offset: usize,
+    srs_hash: Vec<u8>,
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+    c: Vec<E::G1Affine>,
+    leaves: Vec<Vec<u8>>,
+    ml_ab: (E::Fqk, E::Fqk),
+    ml_c: (E::Fqk, E::Fqk),
+}
+
+impl<E: PairingEngine> Shard<E> {
+    /// Returns the position of the first proof of the shard.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of proofs in the shard.
+    pub fn len(&self) -> usize {
+        self.a.len()
+    }
+
+    /// Returns true if the shard does not contain any proof.
+    pub fn is_empty(&self) -> bool {
+        self.a.is_empty()
+    }
+
+    /// Writes the shard to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        self.serialize(&mut out).map_err(Error::Serialization)
+    }
+
+    /// Reads a shard from the given source.
+    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+        Self::deserialize(&mut source).map_err(Error::Serialization)
+    }
+}
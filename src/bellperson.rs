@@ -0,0 +1,689 @@
+//! Parsers for the binary serialization of BLS12-381 Groth16 proofs and
+//! verifying keys produced by bellman and bellperson, e.g. by the Filecoin
+//! proofs tooling, and aggregation compatible with the aggregate module of
+//! bellperson.
+//!
+//! Points use the zcash encoding: proofs are written compressed, $A$, $B$ and
+//! $C$ one after the other in 192 bytes, and verifying keys uncompressed. The
+//! parameters files written by `Parameters::write` start with the verifying
+//! key, which `read_bellperson_vk` reads from them as well. Their proving key
+//! is not converted since bellman filters the points at infinity out of its
+//! queries.
+//!
+//! `aggregate_proofs_bellperson` and `verify_aggregate_proof_bellperson` derive
+//! the challenges with the SHA-256 transcript of bellperson instead of a
+//! `Transcript`, so that aggregate proofs, serialized with
+//! `write_bellperson_aggregate` and `read_bellperson_aggregate`, can be
+//! exchanged with bellperson, e.g. the ones of Filecoin. The SRS must be the
+//! same on both sides, see `read_bellperson_srs`.
+use ark_bls12_381::{Bls12_381, Fq, Fq12, Fq2, Fq6, Fr, G1Affine, G2Affine};
+use ark_ec::{short_weierstrass_jacobian::GroupAffine, SWModelParameters};
+use ark_ff::{BigInteger, BigInteger256, Field, FromBytes, One, PrimeField, Zero};
+use ark_groth16::{PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::SerializationError;
+use ark_std::rand::Rng;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+
+use crate::{
+    commitment::Output,
+    gnark::{read_g1, read_g2},
+    prover::commit_and_aggregate,
+    srs::{GenericSRS, ProverSRS, VerifierSRS, MAX_SRS_SIZE},
+    transcript::{AggregateTranscript, GipaRound},
+    verifier::{verify_with_transcript, AlphaBeta},
+    AggregateProof, Error, GipaProof, KZGOpening, TippMippProof, TranscriptKind,
+};
+
+const G1_COMPRESSED_SIZE: usize = 48;
+const G2_COMPRESSED_SIZE: usize = 96;
+/// Size of a proof written by bellperson's `Proof::write`.
+pub const BELLPERSON_PROOF_SIZE: usize = 2 * G1_COMPRESSED_SIZE + G2_COMPRESSED_SIZE;
+
+/// Reads a proof serialized by bellperson's `Proof::write`.
+pub fn read_bellperson_proof<R: Read>(mut reader: R) -> Result<Proof<Bls12_381>, Error> {
+    let a = read_bellperson_g1(&mut reader, true)?;
+    let b = read_bellperson_g2(&mut reader, true)?;
+    let c = read_bellperson_g1(&mut reader, true)?;
+    Ok(Proof { a, b, c })
+}
+
+/// Reads the proofs concatenated in `bytes`, as stored for instance by the
+/// Filecoin proofs tooling for the proofs of a sector.
+pub fn read_bellperson_proofs(bytes: &[u8]) -> Result<Vec<Proof<Bls12_381>>, Error> {
+    if bytes.len() % BELLPERSON_PROOF_SIZE != 0 {
+        return Err(Error::InvalidProof(
+            "proofs length is not a multiple of the proof size".to_string(),
+        ));
+    }
+    bytes
+        .chunks(BELLPERSON_PROOF_SIZE)
+        .map(read_bellperson_proof)
+        .collect()
+}
+
+/// Reads a verifying key serialized by bellperson's `VerifyingKey::write`.
+/// $[\beta]_1$ and $[\delta]_1$ are not part of `VerifyingKey`.
+pub fn read_bellperson_vk<R: Read>(mut reader: R) -> Result<VerifyingKey<Bls12_381>, Error> {
+    let alpha_g1 = read_bellperson_g1(&mut reader, false)?;
+    let _beta_g1 = read_bellperson_g1(&mut reader, false)?;
+    let beta_g2 = read_bellperson_g2(&mut reader, false)?;
+    let gamma_g2 = read_bellperson_g2(&mut reader, false)?;
+    let _delta_g1 = read_bellperson_g1(&mut reader, false)?;
+    let delta_g2 = read_bellperson_g2(&mut reader, false)?;
+
+    let mut len = [0u8; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(SerializationError::from)?;
+    let gamma_abc_g1 = (0..u32::from_be_bytes(len))
+        .map(|_| read_bellperson_g1(&mut reader, false))
+        .collect::<Result<Vec<_>, _>>()?;
+    if gamma_abc_g1.is_empty() {
+        return Err(Error::MalformedVerifyingKey);
+    }
+    Ok(VerifyingKey {
+        alpha_g1,
+        beta_g2,
+        gamma_g2,
+        delta_g2,
+        gamma_abc_g1,
+    })
+}
+
+/// Version of the aggregation of bellperson. The versions only differ in the
+/// derivation of the challenges.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateVersion {
+    /// The original version, in which the challenge of the first GIPA round
+    /// does not depend on the values sent by the prover in this round.
+    V1,
+    /// The challenge of the first GIPA round is derived from its values, as
+    /// for the following rounds, and the KZG challenge from the last round and
+    /// the final values of GIPA.
+    V2,
+}
+
+impl From<AggregateVersion> for TranscriptKind {
+    fn from(version: AggregateVersion) -> Self {
+        match version {
+            AggregateVersion::V1 => TranscriptKind::BellpersonV1,
+            AggregateVersion::V2 => TranscriptKind::BellpersonV2,
+        }
+    }
+}
+
+/// Aggregates proofs as bellperson's `aggregate_proofs`. The caller must
+/// include in `transcript_include` all the public inputs which are not fixed
+/// before the aggregation, see `aggregate_proofs`.
+pub fn aggregate_proofs_bellperson(
+    srs: &ProverSRS<Bls12_381>,
+    transcript_include: &[u8],
+    proofs: &[Proof<Bls12_381>],
+    version: AggregateVersion,
+) -> Result<AggregateProof<Bls12_381>, Error> {
+    let mut transcript = BellpersonTranscript::new(transcript_include, version);
+    commit_and_aggregate(srs, &mut transcript, proofs).map(|(proof, _)| proof)
+}
+
+/// Verifies an aggregate proof created by bellperson's `aggregate_proofs` or
+/// by `aggregate_proofs_bellperson`, with the same `transcript_include`.
+pub fn verify_aggregate_proof_bellperson<R: Rng + Send>(
+    ip_verifier_srs: &VerifierSRS<Bls12_381>,
+    pvk: &PreparedVerifyingKey<Bls12_381>,
+    public_inputs: &[Vec<Fr>],
+    proof: &AggregateProof<Bls12_381>,
+    rng: R,
+    transcript_include: &[u8],
+    version: AggregateVersion,
+) -> Result<(), Error> {
+    let mut transcript = BellpersonTranscript::new(transcript_include, version);
+    verify_with_transcript(
+        ip_verifier_srs,
+        pvk,
+        public_inputs,
+        proof,
+        AlphaBeta::fastest(),
+        rng,
+        &mut transcript,
+    )
+}
+
+/// Writes an aggregate proof as bellperson's `AggregateProof::write`: points
+/// are compressed and elements of $G_T$ use the torus-based compression of
+/// blstrs.
+pub fn write_bellperson_aggregate<W: Write>(
+    proof: &AggregateProof<Bls12_381>,
+    mut writer: W,
+) -> Result<(), Error> {
+    let gipa = &proof.tmipp.gipa;
+    // com_ab, com_c and ip_ab
+    for gt in [
+        &proof.com_ab.0,
+        &proof.com_ab.1,
+        &proof.com_c.0,
+        &proof.com_c.1,
+    ]
+    .iter()
+    {
+        write_gt(gt, &mut writer)?;
+    }
+    write_gt(&proof.ip_ab, &mut writer)?;
+    write_bytes(&mut writer, &g1_bytes(&proof.agg_c))?;
+
+    write_bytes(&mut writer, &gipa.nproofs.to_le_bytes())?;
+    for (l, r) in gipa.comms_ab.iter().chain(gipa.comms_c.iter()) {
+        for gt in [&l.0, &l.1, &r.0, &r.1].iter() {
+            write_gt(gt, &mut writer)?;
+        }
+    }
+    for (l, r) in gipa.z_ab.iter() {
+        write_gt(l, &mut writer)?;
+        write_gt(r, &mut writer)?;
+    }
+    for (l, r) in gipa.z_c.iter() {
+        write_bytes(&mut writer, &g1_bytes(l))?;
+        write_bytes(&mut writer, &g1_bytes(r))?;
+    }
+    write_bytes(&mut writer, &g1_bytes(&gipa.final_a))?;
+    write_bytes(&mut writer, &g2_bytes(&gipa.final_b))?;
+    write_bytes(&mut writer, &g1_bytes(&gipa.final_c))?;
+    write_bytes(&mut writer, &g2_bytes(&gipa.final_vkey.0))?;
+    write_bytes(&mut writer, &g2_bytes(&gipa.final_vkey.1))?;
+    write_bytes(&mut writer, &g1_bytes(&gipa.final_wkey.0))?;
+    write_bytes(&mut writer, &g1_bytes(&gipa.final_wkey.1))?;
+    write_bytes(&mut writer, &g2_bytes(&proof.tmipp.vkey_opening.0))?;
+    write_bytes(&mut writer, &g2_bytes(&proof.tmipp.vkey_opening.1))?;
+    write_bytes(&mut writer, &g1_bytes(&proof.tmipp.wkey_opening.0))?;
+    write_bytes(&mut writer, &g1_bytes(&proof.tmipp.wkey_opening.1))
+}
+
+/// Reads an aggregate proof written by bellperson's `AggregateProof::write`.
+pub fn read_bellperson_aggregate<R: Read>(
+    mut reader: R,
+) -> Result<AggregateProof<Bls12_381>, Error> {
+    let com_ab = read_output(&mut reader)?;
+    let com_c = read_output(&mut reader)?;
+    let ip_ab = read_gt(&mut reader)?;
+    let agg_c = read_compressed_g1(&mut reader)?;
+
+    let mut nproofs = [0u8; 4];
+    reader
+        .read_exact(&mut nproofs)
+        .map_err(SerializationError::from)?;
+    let nproofs = u32::from_le_bytes(nproofs);
+    if nproofs < 2 {
+        return Err(Error::InvalidProof("invalid nproofs field".to_string()));
+    }
+    let log_proofs = nproofs.next_power_of_two().trailing_zeros() as usize;
+
+    let comms_ab = (0..log_proofs)
+        .map(|_| Ok((read_output(&mut reader)?, read_output(&mut reader)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let comms_c = (0..log_proofs)
+        .map(|_| Ok((read_output(&mut reader)?, read_output(&mut reader)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let z_ab = (0..log_proofs)
+        .map(|_| Ok((read_gt(&mut reader)?, read_gt(&mut reader)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let z_c = (0..log_proofs)
+        .map(|_| {
+            Ok((
+                read_compressed_g1(&mut reader)?,
+                read_compressed_g1(&mut reader)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let final_a = read_compressed_g1(&mut reader)?;
+    let final_b = read_compressed_g2(&mut reader)?;
+    let final_c = read_compressed_g1(&mut reader)?;
+    let final_vkey = (
+        read_compressed_g2(&mut reader)?,
+        read_compressed_g2(&mut reader)?,
+    );
+    let final_wkey = (
+        read_compressed_g1(&mut reader)?,
+        read_compressed_g1(&mut reader)?,
+    );
+    let vkey_opening = KZGOpening(
+        read_compressed_g2(&mut reader)?,
+        read_compressed_g2(&mut reader)?,
+    );
+    let wkey_opening = KZGOpening(
+        read_compressed_g1(&mut reader)?,
+        read_compressed_g1(&mut reader)?,
+    );
+
+    Ok(AggregateProof {
+        com_ab,
+        com_c,
+        ip_ab,
+        agg_c,
+        tmipp: TippMippProof {
+            gipa: GipaProof {
+                nproofs,
+                comms_ab,
+                comms_c,
+                z_ab,
+                z_c,
+                final_a,
+                final_b,
+                final_c,
+                final_vkey,
+                final_wkey,
+            },
+            vkey_opening,
+            wkey_opening,
+        },
+    })
+}
+
+/// Reads a SRS written by bellperson's `GenericSRS::write`, e.g. the one
+/// Filecoin derived from the Filecoin and Zcash powers of tau ceremonies.
+pub fn read_bellperson_srs<R: Read>(mut reader: R) -> Result<GenericSRS<Bls12_381>, Error> {
+    let g_alpha_powers = read_srs_vec(&mut reader, |r| read_compressed_g1(r))?;
+    let g_beta_powers = read_srs_vec(&mut reader, |r| read_compressed_g1(r))?;
+    let h_alpha_powers = read_srs_vec(&mut reader, |r| read_compressed_g2(r))?;
+    let h_beta_powers = read_srs_vec(&mut reader, |r| read_compressed_g2(r))?;
+    Ok(GenericSRS {
+        g_alpha_powers,
+        h_alpha_powers,
+        g_beta_powers,
+        h_beta_powers,
+    })
+}
+
+/// Reads a vector of points prefixed by its length as a big-endian u32.
+fn read_srs_vec<R: Read, G, F: Fn(&mut R) -> Result<G, Error>>(
+    reader: &mut R,
+    read: F,
+) -> Result<Vec<G>, Error> {
+    let mut len = [0u8; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(SerializationError::from)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_SRS_SIZE {
+        return Err(Error::InvalidSRS("SRS len > maximum".to_string()));
+    }
+    (0..len).map(|_| read(reader)).collect()
+}
+
+/// The transcript of bellperson's aggregation. Each challenge is derived from
+/// a new SHA-256 hash of a tag and of the bincode serialization of the values
+/// it depends on, which include the previous challenge.
+struct BellpersonTranscript<'a> {
+    include: &'a [u8],
+    version: AggregateVersion,
+    /// the hash of the commitments, derived along with $r$
+    hcom: Fr,
+}
+
+impl<'a> BellpersonTranscript<'a> {
+    fn new(include: &'a [u8], version: AggregateVersion) -> Self {
+        Self {
+            include,
+            version,
+            hcom: Fr::zero(),
+        }
+    }
+
+    fn challenge(tag: &str, elements: &[&[u8]]) -> Fr {
+        let mut hasher = Sha256::new();
+        hasher.update(b"snarkpack-v1");
+        hasher.update(tag.as_bytes());
+        for element in elements {
+            hasher.update(element);
+        }
+        let mut counter: u64 = 0;
+        loop {
+            // bellperson hashes a usize, i.e. 8 bytes on 64-bit platforms
+            counter += 1;
+            hasher.update(counter.to_be_bytes());
+            let mut digest: [u8; 32] = hasher.clone().finalize().into();
+            // the digest is read in little-endian with the bits above the
+            // size of the field cleared
+            digest[31] &= 0xff >> (256 - Fr::size_in_bits());
+            let c = BigInteger256::read(&digest[..])
+                .ok()
+                .and_then(Fr::from_repr);
+            match c {
+                Some(c) if !c.is_one() && !c.is_zero() => return c,
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl AggregateTranscript<Bls12_381> for BellpersonTranscript<'_> {
+    fn r_challenge(&mut self, com_ab: &Output<Fq12>, com_c: &Output<Fq12>) -> Fr {
+        self.hcom = Self::challenge(
+            "hcom",
+            &[
+                &fq12_bytes(&com_ab.0),
+                &fq12_bytes(&com_ab.1),
+                &fq12_bytes(&com_c.0),
+                &fq12_bytes(&com_c.1),
+            ],
+        );
+        // the included bytes are serialized as a slice, prefixed by their length
+        let len = (self.include.len() as u64).to_le_bytes();
+        Self::challenge("random-r", &[&fr_bytes(&self.hcom), &len, self.include])
+    }
+
+    fn first_challenge(
+        &mut self,
+        ip_ab: &Fq12,
+        agg_c: &G1Affine,
+        agg_d: Option<&G1Affine>,
+        r_shift: &Fr,
+    ) -> Fr {
+        // bellperson has no extra commitment, so its challenges are unchanged
+        // when there is none
+        let agg_d = agg_d.map(g1_bytes).unwrap_or_default();
+        Self::challenge(
+            "gipa-0",
+            &[
+                &fr_bytes(&self.hcom),
+                &fq12_bytes(ip_ab),
+                &g1_bytes(agg_c),
+                &fr_bytes(r_shift),
+                &agg_d,
+            ],
+        )
+    }
+
+    fn round_challenge(&mut self, i: usize, c_inv: &Fr, round: GipaRound<Bls12_381>) -> Fr {
+        if i == 0 && self.version == AggregateVersion::V1 {
+            return *c_inv;
+        }
+        let d = round
+            .d
+            .map(|(comms_d, z_d)| {
+                [
+                    g1_bytes(&z_d.0),
+                    g1_bytes(&z_d.1),
+                    fq12_bytes(&comms_d.0 .0),
+                    fq12_bytes(&comms_d.0 .1),
+                    fq12_bytes(&comms_d.1 .0),
+                    fq12_bytes(&comms_d.1 .1),
+                ]
+                .concat()
+            })
+            .unwrap_or_default();
+        Self::challenge(
+            &format!("gipa-{}", i),
+            &[
+                &fr_bytes(c_inv),
+                &fq12_bytes(&round.z_ab.0),
+                &fq12_bytes(&round.z_ab.1),
+                &g1_bytes(&round.z_c.0),
+                &g1_bytes(&round.z_c.1),
+                &fq12_bytes(&round.comms_ab.0 .0),
+                &fq12_bytes(&round.comms_ab.0 .1),
+                &fq12_bytes(&round.comms_ab.1 .0),
+                &fq12_bytes(&round.comms_ab.1 .1),
+                &fq12_bytes(&round.comms_c.0 .0),
+                &fq12_bytes(&round.comms_c.0 .1),
+                &fq12_bytes(&round.comms_c.1 .0),
+                &fq12_bytes(&round.comms_c.1 .1),
+                &d,
+            ],
+        )
+    }
+
+    fn kzg_challenge(&mut self, c: &Fr, gipa: &GipaProof<Bls12_381>) -> Fr {
+        let keys = [
+            g2_bytes(&gipa.final_vkey.0),
+            g2_bytes(&gipa.final_vkey.1),
+            g1_bytes(&gipa.final_wkey.0),
+            g1_bytes(&gipa.final_wkey.1),
+        ];
+        match self.version {
+            AggregateVersion::V1 => Self::challenge(
+                "random-z",
+                &[&fr_bytes(c), &keys[0], &keys[1], &keys[2], &keys[3]],
+            ),
+            AggregateVersion::V2 => {
+                // the last round and the final values are linked to the KZG
+                // challenge by an extra challenge
+                let finals = [
+                    g1_bytes(&gipa.final_a),
+                    g2_bytes(&gipa.final_b),
+                    g1_bytes(&gipa.final_c),
+                ];
+                let (comms_ab, comms_c) = (gipa.comms_ab.last(), gipa.comms_c.last());
+                let (z_ab, z_c) = (gipa.z_ab.last(), gipa.z_c.last());
+                let link = match (comms_ab, comms_c, z_ab, z_c) {
+                    (Some(comms_ab), Some(comms_c), Some(z_ab), Some(z_c)) => Self::challenge(
+                        "gipa-extra-link",
+                        &[
+                            &fr_bytes(c),
+                            &finals[0],
+                            &finals[1],
+                            &finals[2],
+                            &fq12_bytes(&z_ab.0),
+                            &fq12_bytes(&z_ab.1),
+                            &g1_bytes(&z_c.0),
+                            &g1_bytes(&z_c.1),
+                            &fq12_bytes(&comms_ab.0 .0),
+                            &fq12_bytes(&comms_ab.0 .1),
+                            &fq12_bytes(&comms_ab.1 .0),
+                            &fq12_bytes(&comms_ab.1 .1),
+                            &fq12_bytes(&comms_c.0 .0),
+                            &fq12_bytes(&comms_c.0 .1),
+                            &fq12_bytes(&comms_c.1 .0),
+                            &fq12_bytes(&comms_c.1 .1),
+                        ],
+                    ),
+                    // GIPA always runs at least one round
+                    _ => *c,
+                };
+                Self::challenge(
+                    "random-z",
+                    &[
+                        &fr_bytes(&link),
+                        &keys[0],
+                        &keys[1],
+                        &keys[2],
+                        &keys[3],
+                        &finals[0],
+                        &finals[1],
+                        &finals[2],
+                    ],
+                )
+            }
+        }
+    }
+}
+
+/// Returns the little-endian encoding of a scalar, as serialized by blstrs.
+fn fr_bytes(f: &Fr) -> Vec<u8> {
+    f.into_repr().to_bytes_le()
+}
+
+/// Returns the coordinates of an element of $F_{q^{12}}$ over $F_q$ in
+/// little-endian, the lowest degree first, as serialized by blstrs.
+fn fq12_bytes(f: &Fq12) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(576);
+    for c6 in [f.c0, f.c1].iter() {
+        for c2 in [c6.c0, c6.c1, c6.c2].iter() {
+            bytes.extend(c2.c0.into_repr().to_bytes_le());
+            bytes.extend(c2.c1.into_repr().to_bytes_le());
+        }
+    }
+    bytes
+}
+
+/// Returns the compressed zcash encoding of a point of $G_1$.
+fn g1_bytes(p: &G1Affine) -> Vec<u8> {
+    compressed_bytes(p, p.x.into_repr().to_bytes_be())
+}
+
+/// Returns the compressed zcash encoding of a point of $G_2$, whose $x$
+/// coordinate is written $c_1$ first.
+fn g2_bytes(p: &G2Affine) -> Vec<u8> {
+    let mut x = p.x.c1.into_repr().to_bytes_be();
+    x.extend(p.x.c0.into_repr().to_bytes_be());
+    compressed_bytes(p, x)
+}
+
+fn compressed_bytes<P: SWModelParameters>(p: &GroupAffine<P>, mut x: Vec<u8>) -> Vec<u8> {
+    if p.infinity {
+        x.iter_mut().for_each(|b| *b = 0);
+        x[0] = 0b110 << 5;
+    } else if p.y > -p.y {
+        x[0] |= 0b101 << 5;
+    } else {
+        x[0] |= 0b100 << 5;
+    }
+    x
+}
+
+/// Writes an element of $G_T$ compressed as in blstrs: $c_0 + c_1 w$ is
+/// written as $(c_0 + 1) / c_1$, which is not defined for $c_1 = 0$.
+fn write_gt<W: Write>(gt: &Fq12, writer: W) -> Result<(), Error> {
+    let c1_inv = gt.c1.inverse().ok_or(SerializationError::InvalidData)?;
+    let b = (gt.c0 + Fq6::one()) * c1_inv;
+    let mut bytes = Vec::with_capacity(288);
+    for c in [b.c0, b.c1, b.c2].iter() {
+        bytes.extend(c.c0.into_repr().to_bytes_le());
+        bytes.extend(c.c1.into_repr().to_bytes_le());
+    }
+    write_bytes(writer, &bytes)
+}
+
+fn read_gt<R: Read>(mut reader: R) -> Result<Fq12, Error> {
+    let mut coeffs = [Fq2::zero(); 3];
+    for c in coeffs.iter_mut() {
+        *c = Fq2::new(read_fq(&mut reader)?, read_fq(&mut reader)?);
+    }
+    let b = Fq6::new(coeffs[0], coeffs[1], coeffs[2]);
+    // (b + w) / (b - w)
+    let den = Fq12::new(b, -Fq6::one())
+        .inverse()
+        .ok_or(SerializationError::InvalidData)?;
+    let gt = Fq12::new(b, Fq6::one()) * den;
+    if !gt.pow(Fr::characteristic()).is_one() {
+        return Err(SerializationError::InvalidData.into());
+    }
+    Ok(gt)
+}
+
+fn read_output<R: Read>(mut reader: R) -> Result<Output<Fq12>, Error> {
+    Ok(Output(read_gt(&mut reader)?, read_gt(&mut reader)?))
+}
+
+/// Reads an element of $F_q$ in little-endian, rejecting non-canonical values.
+fn read_fq<R: Read>(mut reader: R) -> Result<Fq, Error> {
+    let mut bytes = [0u8; 48];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(SerializationError::from)?;
+    let repr = <Fq as PrimeField>::BigInt::read(&bytes[..]).map_err(SerializationError::from)?;
+    Ok(Fq::from_repr(repr).ok_or(SerializationError::InvalidData)?)
+}
+
+fn write_bytes<W: Write>(mut writer: W, bytes: &[u8]) -> Result<(), Error> {
+    writer
+        .write_all(bytes)
+        .map_err(|e| SerializationError::from(e).into())
+}
+
+fn read_compressed_g1<R: Read>(reader: R) -> Result<G1Affine, Error> {
+    let bytes = read_encoded(reader, G1_COMPRESSED_SIZE, true)?;
+    read_g1::<Bls12_381, _>(&bytes[..])
+}
+
+fn read_compressed_g2<R: Read>(reader: R) -> Result<G2Affine, Error> {
+    let bytes = read_encoded(reader, G2_COMPRESSED_SIZE, true)?;
+    read_g2::<Bls12_381, _>(&bytes[..])
+}
+
+/// Reads a point of $G_1$ in the expected encoding. Like bellperson, the point
+/// at infinity is rejected.
+fn read_bellperson_g1<R: Read>(reader: R, compressed: bool) -> Result<G1Affine, Error> {
+    let bytes = read_encoded(reader, G1_COMPRESSED_SIZE, compressed)?;
+    non_zero(read_g1::<Bls12_381, _>(&bytes[..])?)
+}
+
+fn read_bellperson_g2<R: Read>(reader: R, compressed: bool) -> Result<G2Affine, Error> {
+    let bytes = read_encoded(reader, G2_COMPRESSED_SIZE, compressed)?;
+    non_zero(read_g2::<Bls12_381, _>(&bytes[..])?)
+}
+
+/// Reads the bytes of a point and checks its compression flag.
+fn read_encoded<R: Read>(
+    mut reader: R,
+    compressed_size: usize,
+    compressed: bool,
+) -> Result<Vec<u8>, Error> {
+    let size = if compressed {
+        compressed_size
+    } else {
+        2 * compressed_size
+    };
+    let mut bytes = vec![0u8; size];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(SerializationError::from)?;
+    if (bytes[0] >> 7 == 1) != compressed {
+        return Err(SerializationError::UnexpectedFlags.into());
+    }
+    Ok(bytes)
+}
+
+fn non_zero<G: Zero>(point: G) -> Result<G, Error> {
+    if point.is_zero() {
+        return Err(SerializationError::InvalidData.into());
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::{AffineCurve, PairingEngine};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_read_encoding() {
+        let g1 = hex("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb");
+        assert_eq!(
+            read_bellperson_g1(&g1[..], true).unwrap(),
+            <Bls12_381 as PairingEngine>::G1Affine::prime_subgroup_generator()
+        );
+        // the points of the verifying key are uncompressed
+        let padded = [&g1[..], &[0u8; 48]].concat();
+        read_bellperson_g1(&padded[..], false).expect_err("compressed point");
+
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0b110 << 5;
+        read_bellperson_g1(&infinity[..], true).expect_err("point at infinity");
+    }
+
+    #[test]
+    fn test_gt_compression() {
+        let gt = Bls12_381::pairing(
+            G1Affine::prime_subgroup_generator(),
+            G2Affine::prime_subgroup_generator(),
+        );
+        let mut bytes = Vec::new();
+        write_gt(&gt, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 288);
+        assert_eq!(read_gt(&bytes[..]).unwrap(), gt);
+        // the identity has no compressed form
+        write_gt(&Fq12::one(), &mut bytes).expect_err("identity");
+        // not in the subgroup of order r
+        let mut b = [0u8; 288];
+        b[0] = 2;
+        read_gt(&b[..]).expect_err("not in the subgroup");
+    }
+}
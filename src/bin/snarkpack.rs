@@ -0,0 +1,448 @@
+//! `snarkpack` aggregates and verifies snarkjs Groth16 proofs on BN254 from the
+//! command line, using the transcript returned by `snarkjs_transcript`.
+//!
+//! Proofs are read from a directory containing one sub-directory per proof,
+//! each holding the snarkjs `proof.json` and `public.json`. Sub-directories are
+//! aggregated in lexicographic order.
+//!
+//! Large batches can be split across several machines: each one accumulates
+//! its shard of the proofs with `aggregate-shard` and a coordinator combines
+//! the shards with `merge`. Merged proofs are bound to the verifying key and
+//! SRS and are checked with `verify --bound`.
+//!
+//! The prover work of `aggregate` can also be spread over servers running
+//! `worker`, given with `--workers`.
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{prepare_verifying_key, verify_proof, PreparedVerifyingKey, Proof};
+use clap::{Parser, Subcommand, ValueEnum};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use snarkpack::{
+    aggregate_proofs,
+    distributed::{aggregate_distributed, serve, Connection},
+    snarkjs_transcript,
+    srs::{setup_fake_srs, GenericSRS, ProverSRS, VerifierSRS},
+    transcript::{new_merlin_transcript, InputsBinding},
+    verify_aggregate_proof, verify_bound, Aggregator, Shard, SnarkJSProof, SnarkJSVK,
+    TranscriptKind, VersionedProof, SNARKJS_TRANSCRIPT_LABEL,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    thread,
+};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[clap(
+    name = "snarkpack",
+    version,
+    about = "Aggregates snarkjs Groth16 proofs"
+)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates an INSECURE generic SRS, only to be used for testing.
+    SetupFake {
+        /// Maximum number of proofs the SRS can aggregate
+        #[clap(long)]
+        size: usize,
+        /// Seed of the rng, a random one is used if not given
+        #[clap(long)]
+        seed: Option<u64>,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Checks a generic SRS, e.g. assembled from two powers of tau
+    /// ceremonies, and rewrites it, optionally truncated.
+    ImportSrs {
+        #[clap(long)]
+        input: PathBuf,
+        /// Only keeps the powers needed to aggregate up to this number of
+        /// proofs
+        #[clap(long)]
+        max_proofs: Option<usize>,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Derives the prover and verifier SRS for a given number of proofs.
+    Specialize {
+        /// Generic SRS
+        #[clap(long)]
+        srs: PathBuf,
+        /// Number of proofs to aggregate, must be a power of two
+        #[clap(long)]
+        nproofs: usize,
+        #[clap(long)]
+        prover_out: PathBuf,
+        #[clap(long)]
+        verifier_out: PathBuf,
+    },
+    /// Aggregates all the proofs found in a directory.
+    Aggregate {
+        /// Directory containing one sub-directory per proof
+        #[clap(long)]
+        dir: PathBuf,
+        /// snarkjs verifying key, defaults to `<dir>/verification_key.json`
+        #[clap(long)]
+        vk: Option<PathBuf>,
+        /// Prover SRS
+        #[clap(long)]
+        srs: PathBuf,
+        #[clap(long)]
+        out: PathBuf,
+        /// Addresses of `worker` servers computing the aggregation, separated
+        /// by commas. Their number must be a power of two.
+        #[clap(long, value_delimiter = ',')]
+        workers: Vec<String>,
+    },
+    /// Accumulates the proofs found in a directory as one shard of a larger
+    /// aggregation, to be combined with the other shards by `merge`.
+    AggregateShard {
+        /// Directory containing one sub-directory per proof of the shard
+        #[clap(long)]
+        dir: PathBuf,
+        /// snarkjs verifying key, defaults to `<dir>/verification_key.json`
+        #[clap(long)]
+        vk: Option<PathBuf>,
+        /// Prover SRS
+        #[clap(long)]
+        srs: PathBuf,
+        /// Position of the first proof of the shard in the aggregate proof
+        #[clap(long, default_value = "0")]
+        offset: usize,
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Combines the shards written by `aggregate-shard` into an aggregate
+    /// proof.
+    Merge {
+        /// snarkjs verifying key
+        #[clap(long)]
+        vk: PathBuf,
+        /// Prover SRS
+        #[clap(long)]
+        srs: PathBuf,
+        #[clap(long)]
+        out: PathBuf,
+        #[clap(required = true)]
+        shards: Vec<PathBuf>,
+    },
+    /// Verifies an aggregate proof against the public inputs found in a
+    /// directory. Exits with a non zero status if the proof is invalid.
+    Verify {
+        /// Directory containing one sub-directory per proof
+        #[clap(long)]
+        dir: PathBuf,
+        /// snarkjs verifying key, defaults to `<dir>/verification_key.json`
+        #[clap(long)]
+        vk: Option<PathBuf>,
+        /// Verifier SRS
+        #[clap(long)]
+        srs: PathBuf,
+        #[clap(long)]
+        proof: PathBuf,
+        /// The proof was created by `merge`
+        #[clap(long)]
+        bound: bool,
+    },
+    /// Serves the requests of `aggregate --workers` until killed.
+    Worker {
+        /// Address to listen on, the address actually used is printed
+        #[clap(long, default_value = "127.0.0.1:0")]
+        listen: String,
+    },
+    /// Prints a summary of a file written by this tool.
+    Inspect {
+        file: PathBuf,
+        #[clap(long, value_enum, default_value = "proof")]
+        kind: Kind,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Proof,
+    Srs,
+    ProverSrs,
+    VerifierSrs,
+    Shard,
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse().command) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(command: Command) -> CliResult<()> {
+    match command {
+        Command::SetupFake { size, seed, out } => {
+            let srs = match seed {
+                Some(seed) => setup_fake_srs::<Bn254, _>(&mut ChaChaRng::seed_from_u64(seed), size),
+                None => setup_fake_srs::<Bn254, _>(&mut rand::thread_rng(), size),
+            };
+            srs.write(BufWriter::new(File::create(out)?))?;
+            println!("srs hash: {}", to_hex(&srs.hash()));
+        }
+        Command::ImportSrs {
+            input,
+            max_proofs,
+            out,
+        } => {
+            let mut srs = GenericSRS::<Bn254>::read(BufReader::new(File::open(input)?))?;
+            if let Some(n) = max_proofs {
+                let len = 2 * n;
+                if srs.g_alpha_powers.len() < len {
+                    return Err(format!("srs too small to aggregate {} proofs", n).into());
+                }
+                srs.g_alpha_powers.truncate(len);
+                srs.g_beta_powers.truncate(len);
+                srs.h_alpha_powers.truncate(len);
+                srs.h_beta_powers.truncate(len);
+            }
+            srs.write(BufWriter::new(File::create(out)?))?;
+            println!("srs hash: {}", to_hex(&srs.hash()));
+        }
+        Command::Specialize {
+            srs,
+            nproofs,
+            prover_out,
+            verifier_out,
+        } => {
+            let srs = GenericSRS::<Bn254>::read(BufReader::new(File::open(srs)?))?;
+            if !nproofs.is_power_of_two() || srs.g_alpha_powers.len() < 2 * nproofs {
+                return Err(format!("can not specialize the srs for {} proofs", nproofs).into());
+            }
+            let (pk, vk) = srs.specialize(nproofs);
+            pk.write(BufWriter::new(File::create(prover_out)?))?;
+            vk.write(BufWriter::new(File::create(verifier_out)?))?;
+        }
+        Command::Aggregate {
+            dir,
+            vk,
+            srs,
+            out,
+            workers,
+        } => {
+            let pvk = read_vk(&dir, vk)?;
+            let srs = ProverSRS::<Bn254>::read(BufReader::new(File::open(srs)?))?;
+            let (proofs, inputs) = read_proofs(&dir, &pvk)?;
+            if proofs.len() != srs.n {
+                return Err(format!("srs is for {} proofs, found {}", srs.n, proofs.len()).into());
+            }
+            let mut transcript = snarkjs_transcript(&inputs);
+            let proof = if workers.is_empty() {
+                aggregate_proofs(&srs, &mut transcript, &proofs)?
+            } else {
+                let mut workers = workers
+                    .iter()
+                    .map(|addr| {
+                        let stream = TcpStream::connect(addr)?;
+                        stream.set_nodelay(true)?;
+                        Ok(Connection::new(stream))
+                    })
+                    .collect::<CliResult<Vec<_>>>()?;
+                aggregate_distributed(&srs, &mut transcript, &proofs, &mut workers)?
+            };
+            VersionedProof::new(proof, &pvk.vk, TranscriptKind::Merlin)?
+                .write(BufWriter::new(File::create(out)?))?;
+        }
+        Command::AggregateShard {
+            dir,
+            vk,
+            srs,
+            offset,
+            out,
+        } => {
+            let pvk = read_vk(&dir, vk)?;
+            let srs = ProverSRS::<Bn254>::read(BufReader::new(File::open(srs)?))?;
+            let (proofs, inputs) = read_proofs(&dir, &pvk)?;
+            let mut aggregator = Aggregator::shard(&srs, &pvk.vk, offset);
+            for (proof, input) in proofs.iter().zip(inputs.iter()) {
+                aggregator.push(proof, input)?;
+            }
+            aggregator
+                .into_shard()
+                .write(BufWriter::new(File::create(out)?))?;
+        }
+        Command::Merge {
+            vk,
+            srs,
+            out,
+            shards,
+        } => {
+            let vk: SnarkJSVK = serde_json::from_reader(BufReader::new(File::open(vk)?))?;
+            let vk = vk.to_verifying_key()?;
+            let srs = ProverSRS::<Bn254>::read(BufReader::new(File::open(srs)?))?;
+            let shards = shards
+                .iter()
+                .map(|path| Ok(Shard::read(BufReader::new(File::open(path)?))?))
+                .collect::<CliResult<Vec<_>>>()?;
+            let mut transcript = new_merlin_transcript(SNARKJS_TRANSCRIPT_LABEL);
+            let proof = Aggregator::merge(&srs, &vk, shards)?.finalize(&mut transcript)?;
+            VersionedProof::new(proof, &vk, TranscriptKind::Bound)?
+                .write(BufWriter::new(File::create(out)?))?;
+        }
+        Command::Verify {
+            dir,
+            vk,
+            srs,
+            proof,
+            bound,
+        } => {
+            let pvk = read_vk(&dir, vk)?;
+            let srs = VerifierSRS::<Bn254>::read(BufReader::new(File::open(srs)?))?;
+            let kind = if bound {
+                TranscriptKind::Bound
+            } else {
+                TranscriptKind::Merlin
+            };
+            let proof =
+                VersionedProof::read(BufReader::new(File::open(proof)?), &pvk.vk, kind)?.proof;
+            let inputs = proof_dirs(&dir)?
+                .iter()
+                .map(|path| read_public_inputs(path))
+                .collect::<CliResult<Vec<_>>>()?;
+            let rng = ChaChaRng::from_entropy();
+            if bound {
+                let mut transcript = new_merlin_transcript(SNARKJS_TRANSCRIPT_LABEL);
+                verify_bound(
+                    &srs,
+                    &pvk,
+                    &inputs,
+                    &proof,
+                    InputsBinding::Absorb,
+                    rng,
+                    &mut transcript,
+                )?;
+            } else {
+                let mut transcript = snarkjs_transcript(&inputs);
+                verify_aggregate_proof(&srs, &pvk, &inputs, &proof, rng, &mut transcript)?;
+            }
+            println!("aggregate proof is valid");
+        }
+        Command::Worker { listen } => {
+            let listener = TcpListener::bind(listen)?;
+            println!("listening on {}", listener.local_addr()?);
+            std::io::stdout().flush()?;
+            for stream in listener.incoming() {
+                let stream = stream?;
+                stream.set_nodelay(true)?;
+                thread::spawn(move || {
+                    if let Err(e) = serve::<Bn254, _>(stream) {
+                        eprintln!("error: {}", e);
+                    }
+                });
+            }
+        }
+        Command::Inspect { file, kind } => inspect(&file, kind)?,
+    }
+    Ok(())
+}
+
+fn inspect(file: &Path, kind: Kind) -> CliResult<()> {
+    let size = fs::metadata(file)?.len();
+    let reader = BufReader::new(File::open(file)?);
+    println!("size: {} bytes", size);
+    match kind {
+        Kind::Proof => {
+            let VersionedProof { header, proof } = VersionedProof::<Bn254>::read_unchecked(reader)?;
+            println!("format version: {}", header.version);
+            println!("transcript: {:?}", header.transcript);
+            println!("vk hash: {}", to_hex(&header.vk_hash));
+            println!("nproofs: {}", proof.tmipp.gipa.nproofs);
+            println!("gipa rounds: {}", proof.tmipp.gipa.comms_ab.len());
+            match proof.parsing_check() {
+                Ok(()) => println!("well formed: yes"),
+                Err(e) => println!("well formed: no ({})", e),
+            }
+        }
+        Kind::Srs => {
+            let srs = GenericSRS::<Bn254>::read(reader)?;
+            println!("powers: {}", srs.g_alpha_powers.len());
+            println!("max proofs: {}", srs.g_alpha_powers.len() / 2);
+            println!("hash: {}", to_hex(&srs.hash()));
+        }
+        Kind::ProverSrs => {
+            let srs = ProverSRS::<Bn254>::read(reader)?;
+            println!("nproofs: {}", srs.n);
+        }
+        Kind::VerifierSrs => {
+            let srs = VerifierSRS::<Bn254>::read(reader)?;
+            println!("nproofs: {}", srs.n);
+        }
+        Kind::Shard => {
+            let shard = Shard::<Bn254>::read(reader)?;
+            println!("offset: {}", shard.offset());
+            println!("nproofs: {}", shard.len());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the sub-directories of `dir` containing a proof, in lexicographic
+/// order.
+fn proof_dirs(dir: &Path) -> CliResult<Vec<PathBuf>> {
+    let mut dirs = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    dirs.retain(|path| path.join("proof.json").is_file() && path.join("public.json").is_file());
+    dirs.sort();
+    if dirs.is_empty() {
+        return Err(format!("no proof found in {}", dir.display()).into());
+    }
+    Ok(dirs)
+}
+
+/// Reads the proofs found in `dir` with their public inputs, checking each
+/// proof is valid.
+#[allow(clippy::type_complexity)]
+fn read_proofs(
+    dir: &Path,
+    pvk: &PreparedVerifyingKey<Bn254>,
+) -> CliResult<(Vec<Proof<Bn254>>, Vec<Vec<Fr>>)> {
+    let mut proofs = Vec::new();
+    let mut inputs = Vec::new();
+    for path in proof_dirs(dir)? {
+        let file = File::open(path.join("proof.json"))?;
+        let proof: Proof<Bn254> = serde_json::from_reader::<_, SnarkJSProof>(file)?.into();
+        let input = read_public_inputs(&path)?;
+        if !verify_proof(pvk, &proof, &input)? {
+            return Err(format!("invalid proof in {}", path.display()).into());
+        }
+        proofs.push(proof);
+        inputs.push(input);
+    }
+    Ok((proofs, inputs))
+}
+
+fn read_vk(dir: &Path, vk: Option<PathBuf>) -> CliResult<PreparedVerifyingKey<Bn254>> {
+    let path = vk.unwrap_or_else(|| dir.join("verification_key.json"));
+    let vk: SnarkJSVK = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    Ok(prepare_verifying_key(&vk.to_verifying_key()?))
+}
+
+fn read_public_inputs(dir: &Path) -> CliResult<Vec<Fr>> {
+    let file = File::open(dir.join("public.json"))?;
+    let inputs: Vec<String> = serde_json::from_reader(BufReader::new(file))?;
+    inputs
+        .iter()
+        .map(|s| Fr::from_str(s).map_err(|_| format!("invalid public input {}", s).into()))
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,342 @@
+//! Aggregation of BLS signatures with the TIPP and MIPP arguments of
+//! `tipp_mipp`.
+//!
+//! Signatures $\sigma_i \in G_1$ on messages $m_i$ with public keys
+//! $pk_i \in G_2$ are valid when $e(\sigma_i, g_2) = e(H(m_i), pk_i)$. The
+//! prover commits to $H(m_i)$ and $pk_i$ with `commitment::pair` and shows
+//! $\prod e(H(m_i), pk_i)^{r^i} = e(\sum r^i \sigma_i, g_2)$ with a proof of
+//! logarithmic size. The verifier only needs the commitment to the messages and
+//! public keys: when all signers sign the same message, it is derived in
+//! constant time from a `KeysCommitment` computed once for the set of signers.
+//!
+//! ark-ec 0.3 does not provide hash-to-curve, so messages are hashed with a
+//! try-and-increment construction over SHA-256, which is not constant time and
+//! is not compatible with other BLS implementations.
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::Zero;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{cfg_iter, fmt::Debug, rand::Rng, string::ToString, vec, vec::Vec};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    commitment::{self, Output},
+    ip,
+    proof::AggregateProof,
+    srs::{ProverSRS, VerifierSRS},
+    tipp_mipp,
+    transcript::Transcript,
+    Error,
+};
+
+/// Domain separation tag of `hash_to_g1`.
+const HASH_TO_G1_DST: &[u8] = b"snarkpack-bls-hash-to-g1";
+
+/// Hashes a message to a point of $G_1$, using try-and-increment.
+pub fn hash_to_g1<E: PairingEngine>(message: &[u8]) -> E::G1Affine {
+    let mut counter = 0u64;
+    loop {
+        // 64 bytes are enough for the base field of the usual curves
+        let bytes = [0u8, 1]
+            .iter()
+            .flat_map(|i| {
+                let mut hasher = Sha256::new();
+                hasher.update(HASH_TO_G1_DST);
+                hasher.update(counter.to_le_bytes());
+                hasher.update([*i]);
+                hasher.update(message);
+                hasher.finalize()
+            })
+            .collect::<Vec<_>>();
+        if let Some(point) = E::G1Affine::from_random_bytes(&bytes) {
+            let point = point.mul_by_cofactor();
+            if !point.is_zero() {
+                return point;
+            }
+        }
+        counter += 1;
+    }
+}
+
+/// Returns the public key $g_2^{sk}$ of the secret key `secret`.
+pub fn public_key<E: PairingEngine>(secret: &E::Fr) -> E::G2Affine {
+    E::G2Affine::prime_subgroup_generator()
+        .mul(*secret)
+        .into_affine()
+}
+
+/// Signs `message` with the secret key `secret`.
+pub fn sign<E: PairingEngine>(secret: &E::Fr, message: &[u8]) -> E::G1Affine {
+    hash_to_g1::<E>(message).mul(*secret).into_affine()
+}
+
+/// KeysCommitment is the commitment to the public keys of a set of signers. It
+/// gives the commitment for signatures of all signers on the same message in
+/// constant time with `with_message`.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone, PartialEq)]
+pub struct KeysCommitment<E: PairingEngine> {
+    /// $\prod e(w_i, pk_i)$ for both parts of the key $w$
+    keys: Output<E::Fqk>,
+    /// $\sum v_i$ for both parts of the key $v$
+    v_sum: (E::G2Affine, E::G2Affine),
+}
+
+impl<E: PairingEngine> KeysCommitment<E> {
+    /// Commits to the public keys of the signers, in the order the signatures
+    /// are aggregated.
+    pub fn new(srs: &ProverSRS<E>, public_keys: &[E::G2Affine]) -> Result<Self, Error> {
+        if !srs.has_correct_len(public_keys.len()) {
+            return Err(Error::InvalidSRS("SRS len != keys len".to_string()));
+        }
+        let sum = |points: &[E::G2Affine]| {
+            points
+                .iter()
+                .fold(E::G2Projective::zero(), |acc, p| acc + p.into_projective())
+                .into_affine()
+        };
+        try_par! {
+            let t = ip::pairing::<E>(&srs.wkey.a, public_keys),
+            let u = ip::pairing::<E>(&srs.wkey.b, public_keys)
+        };
+        Ok(Self {
+            keys: Output(t, u),
+            v_sum: (sum(&srs.vkey.a), sum(&srs.vkey.b)),
+        })
+    }
+
+    /// Returns the commitment to the public keys and to the hash of `message`
+    /// repeated for each signer, as computed by `commit`.
+    pub fn with_message(&self, message: &[u8]) -> Output<E::Fqk> {
+        let h = hash_to_g1::<E>(message);
+        let mut t = E::pairing(h, self.v_sum.0);
+        let mut u = E::pairing(h, self.v_sum.1);
+        t *= &self.keys.0;
+        u *= &self.keys.1;
+        Output(t, u)
+    }
+}
+
+/// Returns the commitment to the hashes of the messages and to the public keys
+/// of the signers, which the verifier checks the aggregate proof against.
+pub fn commit<E: PairingEngine, M: AsRef<[u8]> + Sync>(
+    srs: &ProverSRS<E>,
+    public_keys: &[E::G2Affine],
+    messages: &[M],
+) -> Result<Output<E::Fqk>, Error> {
+    if messages.len() != public_keys.len() || !srs.has_correct_len(messages.len()) {
+        return Err(Error::InvalidSRS("SRS len != keys len".to_string()));
+    }
+    let hashes = hash_messages::<E, _>(messages);
+    commitment::pair::<E>(&srs.vkey, &srs.wkey, &hashes, public_keys)
+}
+
+/// Aggregates the signatures of each signer on its own message.
+pub fn aggregate<E: PairingEngine + Debug, T: Transcript, M: AsRef<[u8]> + Sync>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    public_keys: &[E::G2Affine],
+    messages: &[M],
+    signatures: &[E::G1Affine],
+) -> Result<AggregateProof<E>, Error> {
+    if messages.len() != public_keys.len() {
+        return Err(Error::InvalidProof(
+            "messages len != public keys len".to_string(),
+        ));
+    }
+    let hashes = hash_messages::<E, _>(messages);
+    tipp_mipp::prove(
+        srs,
+        transcript,
+        hashes,
+        public_keys.to_vec(),
+        signatures.to_vec(),
+    )
+    .map(|(proof, _)| proof)
+}
+
+/// Aggregates the signatures of all signers on the same message.
+pub fn aggregate_same_message<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    public_keys: &[E::G2Affine],
+    message: &[u8],
+    signatures: &[E::G1Affine],
+) -> Result<AggregateProof<E>, Error> {
+    let hashes = vec![hash_to_g1::<E>(message); public_keys.len()];
+    tipp_mipp::prove(
+        srs,
+        transcript,
+        hashes,
+        public_keys.to_vec(),
+        signatures.to_vec(),
+    )
+    .map(|(proof, _)| proof)
+}
+
+/// Verifies that the signatures aggregated in `proof` are valid for the
+/// messages and public keys committed in `commitment`, computed with `commit`
+/// or `KeysCommitment::with_message`.
+pub fn verify<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
+    v_srs: &VerifierSRS<E>,
+    commitment: &Output<E::Fqk>,
+    proof: &AggregateProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    if &proof.com_ab != commitment {
+        return Err(Error::InvalidProof(
+            "proof for different messages or keys".to_string(),
+        ));
+    }
+    tipp_mipp::verify(v_srs, proof, rng, transcript)?;
+    // e(sum r^i sig_i, g2) = prod e(H(m_i), pk_i)^{r^i}
+    let g2 = E::G2Affine::prime_subgroup_generator();
+    match E::pairing(proof.agg_c, g2) == proof.ip_ab {
+        true => Ok(()),
+        false => Err(Error::InvalidProof("invalid signatures".to_string())),
+    }
+}
+
+fn hash_messages<E: PairingEngine, M: AsRef<[u8]> + Sync>(messages: &[M]) -> Vec<E::G1Affine> {
+    cfg_iter!(messages)
+        .map(|m| hash_to_g1::<E>(m.as_ref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{srs::setup_fake_srs, transcript::new_merlin_transcript};
+    use ark_bn254::{Bn254, Fr, G1Projective};
+    use ark_std::UniformRand;
+    use rand_core::SeedableRng;
+
+    /// Checks all signatures at once with a random linear combination, with
+    /// one pairing per signature.
+    fn naive_batch_verify<R: Rng>(
+        public_keys: &[<Bn254 as PairingEngine>::G2Affine],
+        messages: &[Vec<u8>],
+        signatures: &[<Bn254 as PairingEngine>::G1Affine],
+        rng: &mut R,
+    ) -> bool {
+        let rs = (0..signatures.len())
+            .map(|_| Fr::rand(rng))
+            .collect::<Vec<_>>();
+        let agg = signatures
+            .iter()
+            .zip(rs.iter())
+            .fold(G1Projective::zero(), |acc, (s, r)| acc + s.mul(*r));
+        let right = public_keys
+            .iter()
+            .zip(messages.iter())
+            .zip(rs.iter())
+            .map(|((pk, m), r)| Bn254::pairing(hash_to_g1::<Bn254>(m).mul(*r), *pk))
+            .product::<<Bn254 as PairingEngine>::Fqk>();
+        Bn254::pairing(
+            agg,
+            <Bn254 as PairingEngine>::G2Affine::prime_subgroup_generator(),
+        ) == right
+    }
+
+    #[test]
+    fn test_bls_aggregation() {
+        let n = 8;
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let (p_srs, v_srs) = setup_fake_srs::<Bn254, _>(&mut rng, n).specialize(n);
+        let secrets = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let public_keys = secrets.iter().map(public_key::<Bn254>).collect::<Vec<_>>();
+        let messages = (0..n)
+            .map(|i| format!("message {}", i).into_bytes())
+            .collect::<Vec<_>>();
+        let mut signatures = secrets
+            .iter()
+            .zip(messages.iter())
+            .map(|(sk, m)| sign::<Bn254>(sk, m))
+            .collect::<Vec<_>>();
+        assert!(naive_batch_verify(
+            &public_keys,
+            &messages,
+            &signatures,
+            &mut rng
+        ));
+
+        let com = commit(&p_srs, &public_keys, &messages).unwrap();
+        let mut transcript = new_merlin_transcript(b"test bls");
+        let proof = aggregate(
+            &p_srs,
+            &mut transcript,
+            &public_keys,
+            &messages,
+            &signatures,
+        )
+        .unwrap();
+        let mut transcript = new_merlin_transcript(b"test bls");
+        verify(&v_srs, &com, &proof, &mut rng, &mut transcript).unwrap();
+
+        // messages signed in a different order
+        let mut swapped = messages.clone();
+        swapped.swap(0, 1);
+        let other = commit(&p_srs, &public_keys, &swapped).unwrap();
+        let mut transcript = new_merlin_transcript(b"test bls");
+        verify(&v_srs, &other, &proof, &mut rng, &mut transcript)
+            .expect_err("proof for other messages");
+
+        // both verifiers reject an invalid signature
+        signatures[3] = sign::<Bn254>(&secrets[3], b"other message");
+        assert!(!naive_batch_verify(
+            &public_keys,
+            &messages,
+            &signatures,
+            &mut rng
+        ));
+        let mut transcript = new_merlin_transcript(b"test bls");
+        let proof = aggregate(
+            &p_srs,
+            &mut transcript,
+            &public_keys,
+            &messages,
+            &signatures,
+        )
+        .unwrap();
+        let mut transcript = new_merlin_transcript(b"test bls");
+        verify(&v_srs, &com, &proof, &mut rng, &mut transcript).expect_err("invalid signature");
+    }
+
+    #[test]
+    fn test_bls_same_message_aggregation() {
+        let n = 4;
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+        let (p_srs, v_srs) = setup_fake_srs::<Bn254, _>(&mut rng, n).specialize(n);
+        let secrets = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let public_keys = secrets.iter().map(public_key::<Bn254>).collect::<Vec<_>>();
+        let message = b"block 42";
+        let signatures = secrets
+            .iter()
+            .map(|sk| sign::<Bn254>(sk, message))
+            .collect::<Vec<_>>();
+
+        let keys = KeysCommitment::new(&p_srs, &public_keys).unwrap();
+        let com = keys.with_message(message);
+        assert_eq!(
+            com,
+            commit(&p_srs, &public_keys, &vec![message; n]).unwrap()
+        );
+        let mut transcript = new_merlin_transcript(b"test bls same message");
+        let proof =
+            aggregate_same_message(&p_srs, &mut transcript, &public_keys, message, &signatures)
+                .unwrap();
+        let mut transcript = new_merlin_transcript(b"test bls same message");
+        verify(&v_srs, &com, &proof, &mut rng, &mut transcript).unwrap();
+
+        let mut transcript = new_merlin_transcript(b"test bls same message");
+        verify(
+            &v_srs,
+            &keys.with_message(b"block 43"),
+            &proof,
+            &mut rng,
+            &mut transcript,
+        )
+        .expect_err("proof for another message");
+    }
+}
@@ -4,22 +4,25 @@ use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{Field, PrimeField};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::{
+    cfg_iter,
     fmt::Debug,
     io::{Read, Write},
     ops::MulAssign,
     vec::Vec,
 };
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 /// This module implements two binding commitment schemes used in the Groth16
 /// aggregation.
 /// The first one is a commitment scheme that commits to a single vector $a$ of
 /// length n in the second base group $G_1$ (for example):
 /// * it requires a structured SRS $v_1$ of the form $(h,h^u,h^{u^2}, ...
-/// ,g^{h^{n-1}})$ with $h \in G_2$ being a random generator of $G_2$ and $u$ a
-/// random scalar (coming from a power of tau ceremony for example)
+///   ,g^{h^{n-1}})$ with $h \in G_2$ being a random generator of $G_2$ and $u$ a
+///   random scalar (coming from a power of tau ceremony for example)
 /// * it requires a second structured SRS $v_2$ of the form $(h,h^v,h^{v^2},
-/// ...$ with $v$ being a random scalar different than u (coming from another
-/// power of tau ceremony for example)
+///   ...$ with $v$ being a random scalar different than u (coming from another
+///   power of tau ceremony for example)
+///
 /// The Commitment is a tuple $(\prod_{i=0}^{n-1} e(a_i,v_{1,i}),
 /// \prod_{i=0}^{n-1} e(a_i,v_{2,i}))$
 ///
@@ -28,8 +31,9 @@ use rayon::prelude::*;
 /// though:
 /// * $v_1$ and $v_2$ stay the same
 /// * An additional tuple $w_1 = (g^{u^n},g^{u^{n+1}},...g^{u^{2n-1}})$ and $w_2 =
-/// (g^{v^n},g^{v^{n+1},...,g^{v^{2n-1}})$ where $g$ is a random generator of
-/// $G_1$
+///   (g^{v^n},g^{v^{n+1},...,g^{v^{2n-1}})$ where $g$ is a random generator of
+///   $G_1$
+///
 /// The commitment scheme returns a tuple:
 /// * $\prod_{i=0}^{n-1} e(a_i,v_{1,i})e(w_{1,i},b_i)$
 /// * $\prod_{i=0}^{n-1} e(a_i,v_{2,i})e(w_{2,i},b_i)$
@@ -75,11 +79,9 @@ where
         if self.a.len() != s_vec.len() {
             return Err(Error::InvalidKeyLength);
         }
-        let (a, b) = self
-            .a
-            .par_iter()
-            .zip(self.b.par_iter())
-            .zip(s_vec.par_iter())
+        let (a, b) = cfg_iter!(self.a)
+            .zip(cfg_iter!(self.b))
+            .zip(cfg_iter!(s_vec))
             .map(|((ap, bp), si)| {
                 let v1s = ap.mul(si.into_repr()).into_affine();
                 let v2s = bp.mul(si.into_repr()).into_affine();
@@ -87,7 +89,7 @@ where
             })
             .unzip();
 
-        Ok(Self { a: a, b: b })
+        Ok(Self { a, b })
     }
 
     /// Returns the left and right commitment key part. It makes copy.
@@ -114,12 +116,10 @@ where
         if left.a.len() != right.a.len() {
             return Err(Error::InvalidKeyLength);
         }
-        let (a, b): (Vec<G>, Vec<G>) = left
-            .a
-            .par_iter()
-            .zip(left.b.par_iter())
-            .zip(right.a.par_iter())
-            .zip(right.b.par_iter())
+        let (a, b): (Vec<G>, Vec<G>) = cfg_iter!(left.a)
+            .zip(cfg_iter!(left.b))
+            .zip(cfg_iter!(right.a))
+            .zip(cfg_iter!(right.b))
             .map(|(((left_a, left_b), right_a), right_b)| {
                 let mut ra = right_a.mul(scale.into_repr());
                 let mut rb = right_b.mul(scale.into_repr());
@@ -129,7 +129,7 @@ where
             })
             .unzip();
 
-        Ok(Self { a: a, b: b })
+        Ok(Self { a, b })
     }
 
     /// Returns the first values in the vector of v1 and v2 (respectively
@@ -144,6 +144,15 @@ where
 #[derive(PartialEq, CanonicalSerialize, CanonicalDeserialize, Clone, Debug)]
 pub struct Output<F: Field + CanonicalSerialize + CanonicalDeserialize>(pub F, pub F);
 
+/// Multiplying two outputs gives the commitment to the concatenation of the
+/// committed vectors, when each was committed with its part of the key.
+impl<F: Field> MulAssign<&Output<F>> for Output<F> {
+    fn mul_assign(&mut self, other: &Output<F>) {
+        self.0.mul_assign(&other.0);
+        self.1.mul_assign(&other.1);
+    }
+}
+
 /// Commits to a single vector of G1 elements in the following way:
 /// $T = \prod_{i=0}^n e(A_i, v_{1,i})$
 /// $U = \prod_{i=0}^n e(A_i, v_{2,i})$
@@ -0,0 +1,596 @@
+//! Aggregation with the GIPA recursion split across several workers, for
+//! example on different servers when aggregating a large number of proofs.
+//!
+//! Worker $j$ out of $k$ holds the elements $j, j+k, j+2k, ...$ of the vectors
+//! folded by GIPA and of the commitment keys. At each round, the left and right
+//! halves of the vectors are split at the same place in every share, so each
+//! worker computes the pairings and multi-exponentiations of the round on its
+//! own share, and folds it with the challenge of the round. The coordinator
+//! only combines the values returned by the workers and derives the
+//! challenges. Once the vectors have $k$ elements, the coordinator collects
+//! them and finishes the recursion and the KZG openings itself.
+//!
+//! The resulting proof is the same as the one returned by `aggregate_proofs`.
+use ark_ec::{PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, One, Zero};
+use ark_groth16::Proof;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use std::fmt::Debug;
+
+use crate::{
+    commitment::{self, Key, Output},
+    proof::AggregateProof,
+    prover::{check_nproofs, derive_r, prove_tipp_mipp, Folding, GipaVectors, RoundTerms},
+    srs::ProverSRS,
+    transcript::Transcript,
+    Error,
+};
+
+/// Transport carries the messages between the coordinator and one worker.
+/// The coordinator sends its request to all the workers before waiting for
+/// their responses, so that remote workers compute concurrently.
+pub trait Transport {
+    /// Sends a request to the worker.
+    fn send(&mut self, request: Vec<u8>) -> Result<(), Error>;
+
+    /// Waits for the response of the worker to the last request sent.
+    fn receive(&mut self) -> Result<Vec<u8>, Error>;
+}
+
+/// Aggregates the proofs like `aggregate_proofs`, with the commitments and
+/// the first rounds of GIPA computed by the given workers. The number of
+/// workers must be a power of two no larger than the number of proofs.
+pub fn aggregate_distributed<E: PairingEngine + Debug, T: Transcript, W: Transport>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    proofs: &[Proof<E>],
+    workers: &mut [W],
+) -> Result<AggregateProof<E>, Error> {
+    check_nproofs(srs, proofs.len())?;
+    let k = workers.len();
+    if !k.is_power_of_two() || k > proofs.len() {
+        return Err(Error::Distributed(format!(
+            "invalid number of workers {}",
+            k
+        )));
+    }
+    let mut cluster = Cluster::<E, W> {
+        workers,
+        len: proofs.len(),
+        vectors: None,
+    };
+
+    // each worker commits to its share of A, B and C
+    let responses = cluster.broadcast(|j| {
+        Request::Load(Box::new(GipaVectors {
+            a: share(proofs, j, k).map(|proof| proof.a).collect(),
+            b: share(proofs, j, k).map(|proof| proof.b).collect(),
+            c: share(proofs, j, k).map(|proof| proof.c).collect(),
+            d: Vec::new(),
+            r: Vec::new(),
+            vkey: share_key(&srs.vkey, j, k),
+            wkey: share_key(&srs.wkey, j, k),
+        }))
+    })?;
+    let mut com_ab = Output(E::Fqk::one(), E::Fqk::one());
+    let mut com_c = Output(E::Fqk::one(), E::Fqk::one());
+    for response in responses {
+        match response {
+            Response::Commitments(ab, c) => {
+                com_ab *= &ab;
+                com_c *= &c;
+            }
+            _ => return Err(unexpected()),
+        }
+    }
+
+    // worker j rescales its share with r^j, r^{j+k}, r^{j+2k}, ...
+    let r = derive_r::<E, _>(transcript, &com_ab, &com_c);
+    let r_step = r.pow([k as u64]);
+    let responses = cluster.broadcast(|j| Request::Randomize {
+        r_first: r.pow([j as u64]),
+        r_step,
+    })?;
+    let mut ip_ab = E::Fqk::one();
+    let mut agg_c = E::G1Projective::zero();
+    for response in responses {
+        match response {
+            Response::Randomized(ip, c) => {
+                ip_ab *= &ip;
+                agg_c += &c;
+            }
+            _ => return Err(unexpected()),
+        }
+    }
+    let agg_c = agg_c.into_affine();
+    if cluster.len == k {
+        cluster.collect()?;
+    }
+
+    let tmipp = prove_tipp_mipp(srs, transcript, cluster, r, &ip_ab, &agg_c)?;
+    Ok(AggregateProof {
+        com_ab,
+        com_c,
+        ip_ab,
+        agg_c,
+        tmipp,
+    })
+}
+
+/// Returns the elements $j, j+k, j+2k, ...$ of the vector.
+fn share<T>(vec: &[T], j: usize, k: usize) -> impl Iterator<Item = &T> {
+    vec.iter().skip(j).step_by(k)
+}
+
+fn share_key<G: ark_ec::AffineCurve>(key: &Key<G>, j: usize, k: usize) -> Key<G> {
+    Key {
+        a: share(&key.a, j, k).copied().collect(),
+        b: share(&key.b, j, k).copied().collect(),
+    }
+}
+
+fn unexpected() -> Error {
+    Error::Distributed("unexpected response from worker".to_string())
+}
+
+/// Cluster folds the vectors held by the workers, until they are small enough
+/// to be collected by the coordinator.
+struct Cluster<'a, E: PairingEngine, W: Transport> {
+    workers: &'a mut [W],
+    /// length of the vectors split across the workers
+    len: usize,
+    /// vectors once collected from the workers
+    vectors: Option<GipaVectors<E>>,
+}
+
+impl<'a, E: PairingEngine, W: Transport> Cluster<'a, E, W> {
+    /// Sends the request returned by `request` for each worker index, then
+    /// returns the responses of all workers.
+    fn broadcast(
+        &mut self,
+        request: impl Fn(usize) -> Request<E>,
+    ) -> Result<Vec<Response<E>>, Error> {
+        for (j, worker) in self.workers.iter_mut().enumerate() {
+            let mut buff = Vec::new();
+            request(j).serialize(&mut buff)?;
+            worker.send(buff)?;
+        }
+        self.workers
+            .iter_mut()
+            .map(
+                |worker| match Response::deserialize(&worker.receive()?[..])? {
+                    Response::Error(e) => Err(Error::Distributed(format!("worker failed: {}", e))),
+                    response => Ok(response),
+                },
+            )
+            .collect()
+    }
+
+    /// Gathers the shares of the workers back into a single vector.
+    fn collect(&mut self) -> Result<(), Error> {
+        let m = self.len / self.workers.len();
+        let shares = self
+            .broadcast(|_| Request::Collect)?
+            .into_iter()
+            .map(|response| match response {
+                Response::Vectors(vectors) if vectors.has_len(m, m) => Ok(vectors),
+                _ => Err(unexpected()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut vectors = GipaVectors {
+            a: Vec::with_capacity(self.len),
+            b: Vec::with_capacity(self.len),
+            c: Vec::with_capacity(self.len),
+            d: Vec::new(),
+            r: Vec::with_capacity(self.len),
+            vkey: Key {
+                a: Vec::with_capacity(self.len),
+                b: Vec::with_capacity(self.len),
+            },
+            wkey: Key {
+                a: Vec::with_capacity(self.len),
+                b: Vec::with_capacity(self.len),
+            },
+        };
+        // element p of the share j is the element j + p*k of the vectors
+        for p in 0..m {
+            for share in shares.iter() {
+                vectors.a.push(share.a[p]);
+                vectors.b.push(share.b[p]);
+                vectors.c.push(share.c[p]);
+                vectors.r.push(share.r[p]);
+                vectors.vkey.a.push(share.vkey.a[p]);
+                vectors.vkey.b.push(share.vkey.b[p]);
+                vectors.wkey.a.push(share.wkey.a[p]);
+                vectors.wkey.b.push(share.wkey.b[p]);
+            }
+        }
+        self.vectors = Some(vectors);
+        Ok(())
+    }
+}
+
+impl<'a, E: PairingEngine, W: Transport> Folding<E> for Cluster<'a, E, W> {
+    fn len(&self) -> usize {
+        self.vectors
+            .as_ref()
+            .map_or(self.len, |vectors| vectors.len())
+    }
+
+    fn cross_terms(&mut self) -> Result<RoundTerms<E>, Error> {
+        if let Some(vectors) = self.vectors.as_mut() {
+            return vectors.cross_terms();
+        }
+        let mut responses = self.broadcast(|_| Request::CrossTerms)?.into_iter();
+        let mut terms = match responses.next() {
+            Some(Response::CrossTerms(terms)) => terms,
+            _ => return Err(unexpected()),
+        };
+        for response in responses {
+            match response {
+                Response::CrossTerms(other) => terms.merge(&other),
+                _ => return Err(unexpected()),
+            }
+        }
+        Ok(terms)
+    }
+
+    fn compress(&mut self, c: &E::Fr, c_inv: &E::Fr) -> Result<(), Error> {
+        if let Some(vectors) = self.vectors.as_mut() {
+            return vectors.compress(c, c_inv);
+        }
+        let responses = self.broadcast(|_| Request::Compress {
+            c: *c,
+            c_inv: *c_inv,
+        })?;
+        if responses
+            .iter()
+            .any(|response| !matches!(response, Response::Compressed))
+        {
+            return Err(unexpected());
+        }
+        self.len /= 2;
+        if self.len == self.workers.len() {
+            self.collect()?;
+        }
+        Ok(())
+    }
+
+    fn into_vectors(self) -> Result<GipaVectors<E>, Error> {
+        self.vectors
+            .ok_or_else(|| Error::Distributed("vectors not collected".to_string()))
+    }
+}
+
+/// Worker holds a share of the vectors folded by GIPA and answers the requests
+/// of the coordinator running `aggregate_distributed`.
+pub struct Worker<E: PairingEngine> {
+    vectors: Option<GipaVectors<E>>,
+}
+
+impl<E: PairingEngine> Default for Worker<E> {
+    fn default() -> Self {
+        Self { vectors: None }
+    }
+}
+
+impl<E: PairingEngine> Worker<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the response to a request of the coordinator. Errors while
+    /// processing the request are sent back to the coordinator.
+    pub fn handle(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = Request::deserialize(request)
+            .map_err(Error::from)
+            .and_then(|request| self.process(request))
+            .unwrap_or_else(|e| Response::Error(e.to_string()));
+        let mut buff = Vec::new();
+        response.serialize(&mut buff)?;
+        Ok(buff)
+    }
+
+    fn process(&mut self, request: Request<E>) -> Result<Response<E>, Error> {
+        match request {
+            Request::Load(vectors) => {
+                let n = vectors.a.len();
+                if !n.is_power_of_two() || !vectors.has_len(n, 0) {
+                    return Err(Error::InvalidKeyLength);
+                }
+                let (vkey, wkey) = (&vectors.vkey, &vectors.wkey);
+                let (a, b, c) = (&vectors.a, &vectors.b, &vectors.c);
+                try_par! {
+                    let com_ab = commitment::pair::<E>(vkey, wkey, a, b),
+                    let com_c = commitment::single_g1::<E>(vkey, c)
+                };
+                self.vectors = Some(*vectors);
+                Ok(Response::Commitments(com_ab, com_c))
+            }
+            Request::Randomize { r_first, r_step } => {
+                let vectors = self.vectors.take().ok_or_else(not_loaded)?;
+                let mut r_vec = Vec::with_capacity(vectors.a.len());
+                let mut ri = r_first;
+                for _ in 0..vectors.a.len() {
+                    r_vec.push(ri);
+                    ri *= &r_step;
+                }
+                let (vectors, ip_ab, agg_c) = GipaVectors::randomized(
+                    vectors.a,
+                    vectors.b,
+                    vectors.c,
+                    vectors.vkey,
+                    &vectors.wkey,
+                    r_vec,
+                )?;
+                self.vectors = Some(vectors);
+                Ok(Response::Randomized(ip_ab, agg_c))
+            }
+            Request::CrossTerms => {
+                let vectors = self.randomized()?;
+                Ok(Response::CrossTerms(vectors.cross_terms()?))
+            }
+            Request::Compress { c, c_inv } => {
+                self.randomized()?.compress(&c, &c_inv)?;
+                Ok(Response::Compressed)
+            }
+            Request::Collect => {
+                self.randomized()?;
+                Ok(Response::Vectors(
+                    self.vectors.take().ok_or_else(not_loaded)?,
+                ))
+            }
+        }
+    }
+
+    /// Returns the vectors, which must have been rescaled already.
+    fn randomized(&mut self) -> Result<&mut GipaVectors<E>, Error> {
+        match self.vectors.as_mut() {
+            Some(vectors) if vectors.has_len(vectors.a.len(), vectors.a.len()) => Ok(vectors),
+            _ => Err(not_loaded()),
+        }
+    }
+}
+
+fn not_loaded() -> Error {
+    Error::Distributed("worker vectors not loaded".to_string())
+}
+
+/// Local runs a worker in the same process as the coordinator. Workers are run
+/// one after the other, each using all available threads.
+pub struct Local<E: PairingEngine> {
+    worker: Worker<E>,
+    response: Option<Vec<u8>>,
+}
+
+impl<E: PairingEngine> Default for Local<E> {
+    fn default() -> Self {
+        Self {
+            worker: Worker::new(),
+            response: None,
+        }
+    }
+}
+
+impl<E: PairingEngine> Local<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<E: PairingEngine> Transport for Local<E> {
+    fn send(&mut self, request: Vec<u8>) -> Result<(), Error> {
+        self.response = Some(self.worker.handle(&request)?);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, Error> {
+        self.response
+            .take()
+            .ok_or_else(|| Error::Distributed("no request sent".to_string()))
+    }
+}
+
+/// Connection is the transport to a worker running `serve` at the other end
+/// of a stream, e.g. a `TcpStream` or a `UnixStream`.
+pub struct Connection<S: Read + Write> {
+    stream: S,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+}
+
+impl<S: Read + Write> Transport for Connection<S> {
+    fn send(&mut self, request: Vec<u8>) -> Result<(), Error> {
+        write_message(&mut self.stream, &request)
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, Error> {
+        read_message(&mut self.stream)?
+            .ok_or_else(|| Error::Distributed("connection closed by worker".to_string()))
+    }
+}
+
+/// Answers the requests of a coordinator received on the stream until the
+/// coordinator closes it.
+pub fn serve<E: PairingEngine, S: Read + Write>(mut stream: S) -> Result<(), Error> {
+    let mut worker = Worker::<E>::new();
+    while let Some(request) = read_message(&mut stream)? {
+        let response = worker.handle(&request)?;
+        write_message(&mut stream, &response)?;
+    }
+    Ok(())
+}
+
+/// Messages are framed with their length as a little endian u64.
+fn write_message<W: Write>(out: &mut W, message: &[u8]) -> Result<(), Error> {
+    let mut frame = Vec::with_capacity(8 + message.len());
+    frame.extend_from_slice(&(message.len() as u64).to_le_bytes());
+    frame.extend_from_slice(message);
+    out.write_all(&frame).map_err(SerializationError::from)?;
+    out.flush().map_err(SerializationError::from)?;
+    Ok(())
+}
+
+/// Returns the next message, or None if the stream was closed.
+fn read_message<R: Read>(source: &mut R) -> Result<Option<Vec<u8>>, Error> {
+    let mut len = [0u8; 8];
+    match source.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(SerializationError::from(e).into()),
+    }
+    let len = u64::from_le_bytes(len);
+    let mut message = Vec::new();
+    source
+        .take(len)
+        .read_to_end(&mut message)
+        .map_err(SerializationError::from)?;
+    if message.len() as u64 != len {
+        return Err(Error::Distributed("truncated message".to_string()));
+    }
+    Ok(Some(message))
+}
+
+enum Request<E: PairingEngine> {
+    /// Loads the share of the worker, without the vector r, and commits to it
+    Load(Box<GipaVectors<E>>),
+    /// Rescales the share with the powers of r
+    Randomize {
+        r_first: E::Fr,
+        r_step: E::Fr,
+    },
+    CrossTerms,
+    Compress {
+        c: E::Fr,
+        c_inv: E::Fr,
+    },
+    /// Returns the share
+    Collect,
+}
+
+enum Response<E: PairingEngine> {
+    Commitments(Output<E::Fqk>, Output<E::Fqk>),
+    Randomized(E::Fqk, E::G1Projective),
+    CrossTerms(RoundTerms<E>),
+    Compressed,
+    Vectors(GipaVectors<E>),
+    Error(String),
+}
+
+impl<E: PairingEngine> CanonicalSerialize for Request<E> {
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            Request::Load(vectors) => vectors.serialized_size(),
+            Request::Randomize { r_first, r_step } => {
+                r_first.serialized_size() + r_step.serialized_size()
+            }
+            Request::CrossTerms | Request::Collect => 0,
+            Request::Compress { c, c_inv } => c.serialized_size() + c_inv.serialized_size(),
+        }
+    }
+
+    fn serialize<W: Write>(&self, mut out: W) -> Result<(), SerializationError> {
+        match self {
+            Request::Load(vectors) => {
+                0u8.serialize(&mut out)?;
+                vectors.serialize(&mut out)
+            }
+            Request::Randomize { r_first, r_step } => {
+                1u8.serialize(&mut out)?;
+                r_first.serialize(&mut out)?;
+                r_step.serialize(&mut out)
+            }
+            Request::CrossTerms => 2u8.serialize(&mut out),
+            Request::Compress { c, c_inv } => {
+                3u8.serialize(&mut out)?;
+                c.serialize(&mut out)?;
+                c_inv.serialize(&mut out)
+            }
+            Request::Collect => 4u8.serialize(&mut out),
+        }
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for Request<E> {
+    fn deserialize<R: Read>(mut source: R) -> Result<Self, SerializationError> {
+        Ok(match u8::deserialize(&mut source)? {
+            0 => Request::Load(Box::new(GipaVectors::deserialize(&mut source)?)),
+            1 => Request::Randomize {
+                r_first: E::Fr::deserialize(&mut source)?,
+                r_step: E::Fr::deserialize(&mut source)?,
+            },
+            2 => Request::CrossTerms,
+            3 => Request::Compress {
+                c: E::Fr::deserialize(&mut source)?,
+                c_inv: E::Fr::deserialize(&mut source)?,
+            },
+            4 => Request::Collect,
+            _ => return Err(SerializationError::InvalidData),
+        })
+    }
+}
+
+impl<E: PairingEngine> CanonicalSerialize for Response<E> {
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            Response::Commitments(ab, c) => ab.serialized_size() + c.serialized_size(),
+            Response::Randomized(ip_ab, agg_c) => ip_ab.serialized_size() + agg_c.serialized_size(),
+            Response::CrossTerms(terms) => terms.serialized_size(),
+            Response::Compressed => 0,
+            Response::Vectors(vectors) => vectors.serialized_size(),
+            Response::Error(e) => e.serialized_size(),
+        }
+    }
+
+    fn serialize<W: Write>(&self, mut out: W) -> Result<(), SerializationError> {
+        match self {
+            Response::Commitments(ab, c) => {
+                0u8.serialize(&mut out)?;
+                ab.serialize(&mut out)?;
+                c.serialize(&mut out)
+            }
+            Response::Randomized(ip_ab, agg_c) => {
+                1u8.serialize(&mut out)?;
+                ip_ab.serialize(&mut out)?;
+                agg_c.serialize(&mut out)
+            }
+            Response::CrossTerms(terms) => {
+                2u8.serialize(&mut out)?;
+                terms.serialize(&mut out)
+            }
+            Response::Compressed => 3u8.serialize(&mut out),
+            Response::Vectors(vectors) => {
+                4u8.serialize(&mut out)?;
+                vectors.serialize(&mut out)
+            }
+            Response::Error(e) => {
+                5u8.serialize(&mut out)?;
+                e.serialize(&mut out)
+            }
+        }
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for Response<E> {
+    fn deserialize<R: Read>(mut source: R) -> Result<Self, SerializationError> {
+        Ok(match u8::deserialize(&mut source)? {
+            0 => Response::Commitments(
+                Output::deserialize(&mut source)?,
+                Output::deserialize(&mut source)?,
+            ),
+            1 => Response::Randomized(
+                E::Fqk::deserialize(&mut source)?,
+                E::G1Projective::deserialize(&mut source)?,
+            ),
+            2 => Response::CrossTerms(RoundTerms::deserialize(&mut source)?),
+            3 => Response::Compressed,
+            4 => Response::Vectors(GipaVectors::deserialize(&mut source)?),
+            5 => Response::Error(String::deserialize(&mut source)?),
+            _ => return Err(SerializationError::InvalidData),
+        })
+    }
+}
@@ -0,0 +1,405 @@
+//! Versioned container for aggregate proofs.
+//!
+//! The encoding of an `AggregateProof` does not say how it was created: a proof
+//! given to a verifier using another curve, transcript or Groth16 verifying key
+//! is either rejected as invalid or, worse, fails to parse somewhere in the
+//! middle. `VersionedProof` prefixes the proof with a header:
+//!
+//! | field        | size | content                                     |
+//! |--------------|------|---------------------------------------------|
+//! | magic        | 4    | `ENVELOPE_MAGIC`                            |
+//! | version      | 2    | format version, little endian               |
+//! | header size  | 2    | size of the fields below, little endian     |
+//! | curve        | 1    | `CurveId`                                   |
+//! | transcript   | 1    | `TranscriptKind`                            |
+//! | vk hash      | 32   | `verifying_key_hash` of the Groth16 key     |
+//!
+//! followed by the canonical encoding of the proof. Readers skip the header
+//! fields they do not know, so fields can be appended to the header without
+//! changing the version, which is only increased when older readers can not
+//! read the proof anymore.
+use ark_ec::PairingEngine;
+use ark_ff::Field;
+use ark_groth16::VerifyingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::{
+    format,
+    io::{Read, Write},
+    vec,
+};
+
+use crate::{transcript::verifying_key_hash, AggregateProof, Error};
+
+/// Magic bytes starting a `VersionedProof`.
+pub const ENVELOPE_MAGIC: [u8; 4] = *b"SNPK";
+/// Version of the format written by `VersionedProof::write`.
+pub const ENVELOPE_VERSION: u16 = 1;
+/// Size of the header fields known to this version.
+const HEADER_SIZE: u16 = 34;
+
+/// Identifies the pairing curve of a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveId {
+    Bn254 = 1,
+    Bls12_381 = 2,
+}
+
+impl CurveId {
+    /// Returns the identifier of the curve of `E`, found from the modulus of
+    /// its base field.
+    pub fn of<E: PairingEngine>() -> Result<Self, Error> {
+        let modulus = E::Fq::characteristic();
+        if modulus == ark_bn254::Fq::characteristic() {
+            Ok(CurveId::Bn254)
+        } else if modulus == ark_bls12_381::Fq::characteristic() {
+            Ok(CurveId::Bls12_381)
+        } else {
+            Err(Error::UnsupportedCurve)
+        }
+    }
+
+    fn from_u8(id: u8) -> Result<Self, Error> {
+        match id {
+            1 => Ok(CurveId::Bn254),
+            2 => Ok(CurveId::Bls12_381),
+            _ => Err(Error::InvalidEnvelope(format!("unknown curve {}", id))),
+        }
+    }
+}
+
+/// Identifies the transcript used to derive the challenges of a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscriptKind {
+    /// An application specific `Transcript`, as given to `aggregate_proofs`.
+    Merlin = 1,
+    /// A `Transcript` bound to the statement with `bind_statement`, as in
+    /// `aggregate_and_bind` and `Aggregator`.
+    Bound = 2,
+    /// The transcript of bellperson, see `AggregateVersion::V1`.
+    BellpersonV1 = 3,
+    /// The transcript of bellperson, see `AggregateVersion::V2`.
+    BellpersonV2 = 4,
+}
+
+impl TranscriptKind {
+    fn from_u8(id: u8) -> Result<Self, Error> {
+        match id {
+            1 => Ok(TranscriptKind::Merlin),
+            2 => Ok(TranscriptKind::Bound),
+            3 => Ok(TranscriptKind::BellpersonV1),
+            4 => Ok(TranscriptKind::BellpersonV2),
+            _ => Err(Error::InvalidEnvelope(format!("unknown transcript {}", id))),
+        }
+    }
+
+    /// Checks the transcript can be used on the given curve: the bellperson
+    /// transcripts are only defined on BLS12-381.
+    fn check_curve(self, curve: CurveId) -> Result<(), Error> {
+        match self {
+            TranscriptKind::BellpersonV1 | TranscriptKind::BellpersonV2
+                if curve != CurveId::Bls12_381 =>
+            {
+                Err(Error::InvalidEnvelope(format!(
+                    "transcript {:?} is not defined on curve {:?}",
+                    self, curve
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// EnvelopeHeader describes how the proof of a `VersionedProof` was created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub version: u16,
+    pub curve: CurveId,
+    pub transcript: TranscriptKind,
+    /// `verifying_key_hash` of the Groth16 verifying key of the aggregated
+    /// proofs.
+    pub vk_hash: [u8; 32],
+}
+
+impl EnvelopeHeader {
+    /// Returns the header of the proofs of `vk` aggregated with `transcript`.
+    pub fn new<E: PairingEngine>(
+        vk: &VerifyingKey<E>,
+        transcript: TranscriptKind,
+    ) -> Result<Self, Error> {
+        let curve = CurveId::of::<E>()?;
+        transcript.check_curve(curve)?;
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            curve,
+            transcript,
+            vk_hash: verifying_key_hash(vk)?,
+        })
+    }
+
+    /// Writes the header to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let write =
+            |out: &mut W, bytes: &[u8]| out.write_all(bytes).map_err(SerializationError::from);
+        write(&mut out, &ENVELOPE_MAGIC)?;
+        write(&mut out, &self.version.to_le_bytes())?;
+        write(&mut out, &HEADER_SIZE.to_le_bytes())?;
+        write(&mut out, &[self.curve as u8, self.transcript as u8])?;
+        write(&mut out, &self.vk_hash)?;
+        Ok(())
+    }
+
+    /// Reads a header from the given source, checking it is well formed but
+    /// not what it describes.
+    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+        let mut read = |len: usize| {
+            let mut buff = vec![0u8; len];
+            source
+                .read_exact(&mut buff)
+                .map(|_| buff)
+                .map_err(SerializationError::from)
+        };
+        if read(ENVELOPE_MAGIC.len())? != ENVELOPE_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        let sizes = read(4)?;
+        let version = u16::from_le_bytes([sizes[0], sizes[1]]);
+        if version != ENVELOPE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let size = u16::from_le_bytes([sizes[2], sizes[3]]);
+        if size < HEADER_SIZE {
+            return Err(Error::InvalidEnvelope(format!(
+                "header of {} bytes, expected at least {}",
+                size, HEADER_SIZE
+            )));
+        }
+        let fields = read(size as usize)?;
+        let curve = CurveId::from_u8(fields[0])?;
+        let transcript = TranscriptKind::from_u8(fields[1])?;
+        transcript.check_curve(curve)?;
+        let mut vk_hash = [0u8; 32];
+        vk_hash.copy_from_slice(&fields[2..34]);
+        Ok(Self {
+            version,
+            curve,
+            transcript,
+            vk_hash,
+        })
+    }
+
+    /// Checks the header describes a proof on the curve of `E` of the proofs
+    /// of `vk`, aggregated with `transcript`.
+    pub fn check<E: PairingEngine>(
+        &self,
+        vk: &VerifyingKey<E>,
+        transcript: TranscriptKind,
+    ) -> Result<(), Error> {
+        let curve = CurveId::of::<E>()?;
+        if self.curve != curve {
+            return Err(Error::CurveMismatch {
+                expected: curve,
+                found: self.curve,
+            });
+        }
+        if self.transcript != transcript {
+            return Err(Error::TranscriptMismatch {
+                expected: transcript,
+                found: self.transcript,
+            });
+        }
+        if self.vk_hash != verifying_key_hash(vk)? {
+            return Err(Error::VerifyingKeyMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// VersionedProof is an aggregate proof with a header describing the curve,
+/// the transcript and the Groth16 verifying key it must be verified with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedProof<E: PairingEngine> {
+    pub header: EnvelopeHeader,
+    pub proof: AggregateProof<E>,
+}
+
+impl<E: PairingEngine> VersionedProof<E> {
+    /// Wraps a proof aggregating proofs of `vk` with `transcript`.
+    pub fn new(
+        proof: AggregateProof<E>,
+        vk: &VerifyingKey<E>,
+        transcript: TranscriptKind,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            header: EnvelopeHeader::new(vk, transcript)?,
+            proof,
+        })
+    }
+
+    /// Writes the header and the proof to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        self.header.write(&mut out)?;
+        self.proof.serialize(&mut out).map_err(Error::Serialization)
+    }
+
+    /// Reads a proof from the given source and checks it was created on the
+    /// curve of `E` for the proofs of `vk` with `transcript`.
+    pub fn read<R: Read>(
+        mut source: R,
+        vk: &VerifyingKey<E>,
+        transcript: TranscriptKind,
+    ) -> Result<Self, Error> {
+        let header = EnvelopeHeader::read(&mut source)?;
+        header.check(vk, transcript)?;
+        let proof = AggregateProof::deserialize(&mut source).map_err(Error::Serialization)?;
+        Ok(Self { header, proof })
+    }
+
+    /// Reads a proof from the given source, only checking its curve is the
+    /// one of `E`. The header must be checked with `EnvelopeHeader::check`
+    /// before verifying the proof.
+    pub fn read_unchecked<R: Read>(mut source: R) -> Result<Self, Error> {
+        let header = EnvelopeHeader::read(&mut source)?;
+        let curve = CurveId::of::<E>()?;
+        if header.curve != curve {
+            return Err(Error::CurveMismatch {
+                expected: curve,
+                found: header.curve,
+            });
+        }
+        let proof = AggregateProof::deserialize(&mut source).map_err(Error::Serialization)?;
+        Ok(Self { header, proof })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aggregate_proofs, srs, transcript::new_merlin_transcript};
+    use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+    use ark_ec::AffineCurve;
+    use ark_groth16::Proof;
+    use ark_std::{vec::Vec, UniformRand};
+    use rand_core::SeedableRng;
+
+    fn fake_vk<E: PairingEngine>(ninputs: usize) -> VerifyingKey<E> {
+        VerifyingKey {
+            alpha_g1: E::G1Affine::prime_subgroup_generator(),
+            beta_g2: E::G2Affine::prime_subgroup_generator(),
+            gamma_g2: E::G2Affine::prime_subgroup_generator(),
+            delta_g2: E::G2Affine::prime_subgroup_generator(),
+            gamma_abc_g1: vec![E::G1Affine::prime_subgroup_generator(); ninputs + 1],
+        }
+    }
+
+    fn fake_proof() -> AggregateProof<Bn254> {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let generic = srs::setup_fake_srs::<Bn254, _>(&mut rng, 2);
+        let (pk, _) = generic.specialize(2);
+        let proofs = (0..2)
+            .map(|_| Proof {
+                a: G1Affine::prime_subgroup_generator()
+                    .mul(Fr::rand(&mut rng))
+                    .into(),
+                b: G2Affine::prime_subgroup_generator()
+                    .mul(Fr::rand(&mut rng))
+                    .into(),
+                c: G1Affine::prime_subgroup_generator()
+                    .mul(Fr::rand(&mut rng))
+                    .into(),
+            })
+            .collect::<Vec<_>>();
+        let mut transcript = new_merlin_transcript(b"test");
+        aggregate_proofs(&pk, &mut transcript, &proofs).unwrap()
+    }
+
+    #[test]
+    fn test_envelope_io() {
+        let vk = fake_vk::<Bn254>(1);
+        let proof = VersionedProof::new(fake_proof(), &vk, TranscriptKind::Merlin).unwrap();
+        let mut buffer = Vec::new();
+        proof.write(&mut buffer).unwrap();
+        assert_eq!(&buffer[..4], b"SNPK");
+
+        let read = VersionedProof::read(&buffer[..], &vk, TranscriptKind::Merlin).unwrap();
+        assert_eq!(proof, read);
+        let read = VersionedProof::<Bn254>::read_unchecked(&buffer[..]).unwrap();
+        assert_eq!(proof, read);
+
+        // unknown header fields are skipped
+        let mut extended = buffer[..8].to_vec();
+        extended[6] += 3;
+        extended.extend_from_slice(&buffer[8..8 + HEADER_SIZE as usize]);
+        extended.extend_from_slice(&[1, 2, 3]);
+        extended.extend_from_slice(&buffer[8 + HEADER_SIZE as usize..]);
+        let read = VersionedProof::read(&extended[..], &vk, TranscriptKind::Merlin).unwrap();
+        assert_eq!(proof, read);
+    }
+
+    #[test]
+    fn test_envelope_mismatch() {
+        let vk = fake_vk::<Bn254>(1);
+        let proof = VersionedProof::new(fake_proof(), &vk, TranscriptKind::Merlin).unwrap();
+        let mut buffer = Vec::new();
+        proof.write(&mut buffer).unwrap();
+
+        // a raw proof has no header
+        let mut raw = Vec::new();
+        proof.proof.write(&mut raw).unwrap();
+        assert!(matches!(
+            VersionedProof::read(&raw[..], &vk, TranscriptKind::Merlin),
+            Err(Error::InvalidMagic)
+        ));
+
+        let mut newer = buffer.clone();
+        newer[4] = 2;
+        assert!(matches!(
+            VersionedProof::read(&newer[..], &vk, TranscriptKind::Merlin),
+            Err(Error::UnsupportedVersion(2))
+        ));
+
+        let mut short = buffer.clone();
+        short[6] = 2;
+        assert!(matches!(
+            EnvelopeHeader::read(&short[..]),
+            Err(Error::InvalidEnvelope(_))
+        ));
+
+        let mut unknown = buffer.clone();
+        unknown[8] = 7;
+        assert!(matches!(
+            EnvelopeHeader::read(&unknown[..]),
+            Err(Error::InvalidEnvelope(_))
+        ));
+
+        // the bellperson transcripts only exist on BLS12-381
+        let mut bellperson = buffer.clone();
+        bellperson[9] = TranscriptKind::BellpersonV1 as u8;
+        assert!(matches!(
+            EnvelopeHeader::read(&bellperson[..]),
+            Err(Error::InvalidEnvelope(_))
+        ));
+        assert!(matches!(
+            EnvelopeHeader::new(&vk, TranscriptKind::BellpersonV2),
+            Err(Error::InvalidEnvelope(_))
+        ));
+
+        assert!(matches!(
+            VersionedProof::<ark_bls12_381::Bls12_381>::read_unchecked(&buffer[..]),
+            Err(Error::CurveMismatch {
+                expected: CurveId::Bls12_381,
+                found: CurveId::Bn254,
+            })
+        ));
+        assert!(matches!(
+            VersionedProof::read(&buffer[..], &vk, TranscriptKind::Bound),
+            Err(Error::TranscriptMismatch {
+                expected: TranscriptKind::Bound,
+                found: TranscriptKind::Merlin,
+            })
+        ));
+        assert!(matches!(
+            VersionedProof::read(&buffer[..], &fake_vk::<Bn254>(2), TranscriptKind::Merlin),
+            Err(Error::VerifyingKeyMismatch)
+        ));
+    }
+}
@@ -1,26 +1,92 @@
 use ark_serialize::SerializationError;
+use ark_std::string::String;
+
+use crate::envelope::{CurveId, TranscriptKind};
+#[cfg(feature = "std")]
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum Error {
-    #[error("Serialization error: {0}")]
-    Serialization(#[from] SerializationError),
+    #[cfg_attr(feature = "std", error("Serialization error: {0}"))]
+    Serialization(#[cfg_attr(feature = "std", from)] SerializationError),
 
-    #[error("Commitment key length invalid")]
+    #[cfg_attr(feature = "std", error("Commitment key length invalid"))]
     InvalidKeyLength,
 
-    #[error("Vectors length do not match for inner product (IP)")]
+    #[cfg_attr(
+        feature = "std",
+        error("Vectors length do not match for inner product (IP)")
+    )]
     InvalidIPVectorLength,
 
-    #[error("Invalid pairing result")]
+    #[cfg_attr(feature = "std", error("Invalid pairing result"))]
     InvalidPairing,
 
-    #[error("Invalid SRS: {0}")]
+    #[cfg_attr(feature = "std", error("Invalid SRS: {0}"))]
     InvalidSRS(String),
 
-    #[error("Invalid proof: {0}")]
+    #[cfg_attr(feature = "std", error("Invalid proof: {0}"))]
     InvalidProof(String),
 
-    #[error("Malformed Groth16 verifying key")]
+    #[cfg_attr(feature = "std", error("Invalid zkey: {0}"))]
+    InvalidZKey(String),
+
+    #[cfg_attr(feature = "std", error("Invalid witness: {0}"))]
+    InvalidWitness(String),
+
+    #[cfg_attr(
+        feature = "std",
+        error("zkey does not match the verification key: {}", .0.join(", "))
+    )]
+    VerificationKeyMismatch(ark_std::vec::Vec<String>),
+
+    #[cfg_attr(feature = "std", error("Malformed Groth16 verifying key"))]
     MalformedVerifyingKey,
+
+    #[cfg_attr(feature = "std", error("Invalid verifying key: {0}"))]
+    InvalidVerifyingKey(String),
+
+    #[cfg_attr(feature = "std", error("Distributed prover: {0}"))]
+    Distributed(String),
+
+    #[cfg_attr(feature = "std", error("Not a snarkpack proof: invalid magic bytes"))]
+    InvalidMagic,
+
+    #[cfg_attr(feature = "std", error("Unsupported proof format version {0}"))]
+    UnsupportedVersion(u16),
+
+    #[cfg_attr(feature = "std", error("Invalid proof header: {0}"))]
+    InvalidEnvelope(String),
+
+    #[cfg_attr(feature = "std", error("Curve not supported by the proof format"))]
+    UnsupportedCurve,
+
+    #[cfg_attr(
+        feature = "std",
+        error("Proof is on curve {found:?}, expected {expected:?}")
+    )]
+    CurveMismatch { expected: CurveId, found: CurveId },
+
+    #[cfg_attr(
+        feature = "std",
+        error("Proof uses transcript {found:?}, expected {expected:?}")
+    )]
+    TranscriptMismatch {
+        expected: TranscriptKind,
+        found: TranscriptKind,
+    },
+
+    #[cfg_attr(
+        feature = "std",
+        error("Proof aggregates proofs of another verifying key")
+    )]
+    VerifyingKeyMismatch,
+}
+
+#[cfg(not(feature = "std"))]
+impl From<SerializationError> for Error {
+    fn from(e: SerializationError) -> Self {
+        Error::Serialization(e)
+    }
 }
@@ -0,0 +1,335 @@
+//! Parsers for the binary serialization of Groth16 proofs and verifying keys
+//! produced by gnark, i.e. the output of `WriteTo` on its `groth16.Proof` and
+//! `groth16.VerifyingKey`, for BN254 and BLS12-381.
+//!
+//! Points are encoded as in gnark-crypto: the coordinates in big-endian, with
+//! the encoding flags in the most significant bits of the first byte. Both the
+//! compressed and the uncompressed (`WriteRawTo`) encodings are accepted.
+use ark_ec::{short_weierstrass_jacobian::GroupAffine, PairingEngine, SWModelParameters};
+use ark_ff::{BigInteger, Field, FromBytes, PrimeField, Zero};
+use ark_groth16::{Proof, VerifyingKey};
+use ark_serialize::SerializationError;
+use std::{convert::TryFrom, io::Read};
+
+use crate::Error;
+
+/// Curves whose gnark serialization can be read.
+pub trait GnarkCurve: PairingEngine {
+    type G1Parameters: SWModelParameters<ScalarField = Self::Fr>;
+    type G2Parameters: SWModelParameters<ScalarField = Self::Fr>;
+    /// Whether the flags use the three bits of the zcash encoding, as for
+    /// BLS12-381, or the two bits gnark uses for BN254.
+    const ZCASH_FLAGS: bool;
+
+    fn g1(p: GroupAffine<Self::G1Parameters>) -> Self::G1Affine;
+    fn g2(p: GroupAffine<Self::G2Parameters>) -> Self::G2Affine;
+}
+
+impl GnarkCurve for ark_bn254::Bn254 {
+    type G1Parameters = ark_bn254::g1::Parameters;
+    type G2Parameters = ark_bn254::g2::Parameters;
+    const ZCASH_FLAGS: bool = false;
+
+    fn g1(p: GroupAffine<Self::G1Parameters>) -> Self::G1Affine {
+        p
+    }
+
+    fn g2(p: GroupAffine<Self::G2Parameters>) -> Self::G2Affine {
+        p
+    }
+}
+
+impl GnarkCurve for ark_bls12_381::Bls12_381 {
+    type G1Parameters = ark_bls12_381::g1::Parameters;
+    type G2Parameters = ark_bls12_381::g2::Parameters;
+    const ZCASH_FLAGS: bool = true;
+
+    fn g1(p: GroupAffine<Self::G1Parameters>) -> Self::G1Affine {
+        p
+    }
+
+    fn g2(p: GroupAffine<Self::G2Parameters>) -> Self::G2Affine {
+        p
+    }
+}
+
+/// GnarkProof is a gnark Groth16 proof. Since gnark v0.9, proofs of circuits
+/// using its commitment extension also contain the commitments and a proof of
+/// knowledge of them, which are empty for other circuits and absent for proofs
+/// serialized by earlier versions.
+///
+/// A proof with commitments does not verify as a plain Groth16 proof: it can
+/// only be converted to a `Proof` if it has none. Otherwise, its single
+/// commitment is the D point of `aggregate_proofs_with_commitments`, which
+/// does not check the proof of knowledge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GnarkProof<E: PairingEngine> {
+    pub proof: Proof<E>,
+    pub commitments: Vec<E::G1Affine>,
+    pub commitment_pok: Option<E::G1Affine>,
+}
+
+impl<E: PairingEngine> TryFrom<GnarkProof<E>> for Proof<E> {
+    type Error = Error;
+
+    fn try_from(src: GnarkProof<E>) -> Result<Self, Error> {
+        if !src.commitments.is_empty() {
+            return Err(Error::InvalidProof(
+                "gnark proof with commitments".to_string(),
+            ));
+        }
+        Ok(src.proof)
+    }
+}
+
+/// Reads a proof serialized by gnark's `groth16.Proof.WriteTo`.
+pub fn read_gnark_proof<E: GnarkCurve, R: Read>(mut reader: R) -> Result<GnarkProof<E>, Error> {
+    let a = read_g1::<E, _>(&mut reader)?;
+    let b = read_g2::<E, _>(&mut reader)?;
+    let c = read_g1::<E, _>(&mut reader)?;
+
+    let mut rest = Vec::new();
+    reader
+        .read_to_end(&mut rest)
+        .map_err(SerializationError::from)?;
+    let (commitments, commitment_pok) = if rest.is_empty() {
+        (Vec::new(), None)
+    } else {
+        let mut rest = &rest[..];
+        let commitments = read_g1_slice::<E, _>(&mut rest)?;
+        let pok = read_g1::<E, _>(&mut rest)?;
+        (commitments, Some(pok))
+    };
+    Ok(GnarkProof {
+        proof: Proof { a, b, c },
+        commitments,
+        commitment_pok,
+    })
+}
+
+/// Reads a verifying key serialized by gnark's `groth16.VerifyingKey.WriteTo`.
+/// $[\beta]_1$ and $[\delta]_1$ are not part of `VerifyingKey`. Keys of
+/// circuits using the commitment extension are rejected since their proofs
+/// can not be verified with `VerifyingKey` alone.
+pub fn read_gnark_vk<E: GnarkCurve, R: Read>(mut reader: R) -> Result<VerifyingKey<E>, Error> {
+    let alpha_g1 = read_g1::<E, _>(&mut reader)?;
+    let _beta_g1 = read_g1::<E, _>(&mut reader)?;
+    let beta_g2 = read_g2::<E, _>(&mut reader)?;
+    let gamma_g2 = read_g2::<E, _>(&mut reader)?;
+    let _delta_g1 = read_g1::<E, _>(&mut reader)?;
+    let delta_g2 = read_g2::<E, _>(&mut reader)?;
+    let gamma_abc_g1 = read_g1_slice::<E, _>(&mut reader)?;
+    if gamma_abc_g1.is_empty() {
+        return Err(Error::MalformedVerifyingKey);
+    }
+    // since v0.9, the wires committed to by each commitment and the
+    // commitment keys follow, both empty without commitments
+    let mut rest = Vec::new();
+    reader
+        .read_to_end(&mut rest)
+        .map_err(SerializationError::from)?;
+    if !rest.is_empty() && rest != [0u8; 8] {
+        return Err(Error::InvalidVerifyingKey(
+            "gnark verifying key with commitments".to_string(),
+        ));
+    }
+    Ok(VerifyingKey {
+        alpha_g1,
+        beta_g2,
+        gamma_g2,
+        delta_g2,
+        gamma_abc_g1,
+    })
+}
+
+/// Encoding of a point given by the flags of its first byte.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Uncompressed,
+    UncompressedInfinity,
+    CompressedInfinity,
+    /// compressed with the lexicographically smallest or largest y
+    Compressed {
+        largest: bool,
+    },
+}
+
+impl Encoding {
+    /// Returns the encoding and the mask of the flag bits.
+    fn from_byte(zcash: bool, byte: u8) -> Result<(Self, u8), SerializationError> {
+        let encoding = if zcash {
+            match byte & 0b111 << 5 {
+                0b000_00000 => Encoding::Uncompressed,
+                0b010_00000 => Encoding::UncompressedInfinity,
+                0b100_00000 => Encoding::Compressed { largest: false },
+                0b101_00000 => Encoding::Compressed { largest: true },
+                0b110_00000 => Encoding::CompressedInfinity,
+                _ => return Err(SerializationError::UnexpectedFlags),
+            }
+        } else {
+            match byte & 0b11 << 6 {
+                0b00_000000 => Encoding::Uncompressed,
+                0b01_000000 => Encoding::CompressedInfinity,
+                0b10_000000 => Encoding::Compressed { largest: false },
+                _ => Encoding::Compressed { largest: true },
+            }
+        };
+        let mask = if zcash { 0b111 << 5 } else { 0b11 << 6 };
+        Ok((encoding, mask))
+    }
+}
+
+pub(crate) fn read_g1<E: GnarkCurve, R: Read>(reader: R) -> Result<E::G1Affine, Error> {
+    read_point::<E::G1Parameters, _>(reader, E::ZCASH_FLAGS).map(E::g1)
+}
+
+pub(crate) fn read_g2<E: GnarkCurve, R: Read>(reader: R) -> Result<E::G2Affine, Error> {
+    read_point::<E::G2Parameters, _>(reader, E::ZCASH_FLAGS).map(E::g2)
+}
+
+/// Reads a slice of points, prefixed by its length as a big-endian u32.
+fn read_g1_slice<E: GnarkCurve, R: Read>(mut reader: R) -> Result<Vec<E::G1Affine>, Error> {
+    let mut len = [0u8; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(SerializationError::from)?;
+    (0..u32::from_be_bytes(len))
+        .map(|_| read_g1::<E, _>(&mut reader))
+        .collect()
+}
+
+fn read_point<P: SWModelParameters, R: Read>(
+    mut reader: R,
+    zcash: bool,
+) -> Result<GroupAffine<P>, Error> {
+    let size = field_size::<P::BaseField>();
+    let mut buf = vec![0u8; size];
+    reader
+        .read_exact(&mut buf)
+        .map_err(SerializationError::from)?;
+    let (encoding, mask) = Encoding::from_byte(zcash, buf[0])?;
+    buf[0] &= !mask;
+    if encoding == Encoding::Uncompressed || encoding == Encoding::UncompressedInfinity {
+        buf.resize(2 * size, 0);
+        reader
+            .read_exact(&mut buf[size..])
+            .map_err(SerializationError::from)?;
+    }
+    let point = match encoding {
+        Encoding::CompressedInfinity | Encoding::UncompressedInfinity => {
+            if buf.iter().any(|b| *b != 0) {
+                return Err(SerializationError::InvalidData.into());
+            }
+            GroupAffine::zero()
+        }
+        Encoding::Uncompressed => {
+            let x = read_field::<P::BaseField>(&buf[..size])?;
+            let y = read_field::<P::BaseField>(&buf[size..])?;
+            // gnark writes the point at infinity as (0,0) on BN254
+            if x.is_zero() && y.is_zero() {
+                GroupAffine::zero()
+            } else {
+                GroupAffine::new(x, y, false)
+            }
+        }
+        Encoding::Compressed { largest } => {
+            let x = read_field::<P::BaseField>(&buf)?;
+            GroupAffine::get_point_from_x(x, largest).ok_or(SerializationError::InvalidData)?
+        }
+    };
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(SerializationError::InvalidData.into());
+    }
+    Ok(point)
+}
+
+/// Returns the size in bytes of an element of `F`.
+fn field_size<F: Field>() -> usize {
+    let fp_size = (F::BasePrimeField::size_in_bits() + 7) / 8;
+    fp_size * F::extension_degree() as usize
+}
+
+/// Reads an element of `F` from its big-endian coordinates over the prime
+/// field, the highest degree coordinate first.
+fn read_field<F: Field>(bytes: &[u8]) -> Result<F, SerializationError> {
+    let degree = F::extension_degree() as usize;
+    let mut elems = bytes
+        .chunks(bytes.len() / degree)
+        .map(read_prime_field::<F::BasePrimeField>)
+        .collect::<Result<Vec<_>, _>>()?;
+    elems.reverse();
+    F::from_base_prime_field_elems(&elems).ok_or(SerializationError::InvalidData)
+}
+
+fn read_prime_field<F: PrimeField>(bytes: &[u8]) -> Result<F, SerializationError> {
+    let mut le = bytes.iter().rev().copied().collect::<Vec<_>>();
+    let repr_size = F::BigInt::NUM_LIMBS * 8;
+    if le.len() > repr_size {
+        return Err(SerializationError::InvalidData);
+    }
+    le.resize(repr_size, 0);
+    let repr = F::BigInt::read(&le[..])?;
+    // the encoding is canonical: values larger than the modulus are rejected
+    F::from_repr(repr).ok_or(SerializationError::InvalidData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Bls12_381;
+    use ark_bn254::Bn254;
+    use ark_ec::AffineCurve;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_bls12_381_generators() {
+        // zcash encoding of the generators, as written by gnark-crypto
+        let g1 = hex("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb");
+        let g2 = hex("93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8");
+        assert_eq!(
+            read_g1::<Bls12_381, _>(&g1[..]).unwrap(),
+            <Bls12_381 as PairingEngine>::G1Affine::prime_subgroup_generator()
+        );
+        assert_eq!(
+            read_g2::<Bls12_381, _>(&g2[..]).unwrap(),
+            <Bls12_381 as PairingEngine>::G2Affine::prime_subgroup_generator()
+        );
+
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0b110 << 5;
+        assert!(read_g1::<Bls12_381, _>(&infinity[..]).unwrap().is_zero());
+        // flags of an uncompressed point at infinity on a compressed one
+        infinity[0] = 0b111 << 5;
+        read_g1::<Bls12_381, _>(&infinity[..]).expect_err("invalid flags");
+        // not the x coordinate of a point
+        let mut x = g1.clone();
+        x[47] ^= 1;
+        read_g1::<Bls12_381, _>(&x[..]).expect_err("invalid point");
+    }
+
+    #[test]
+    fn test_bn254_generator() {
+        // (1, 2): 2 is the smallest of 2 and -2
+        let mut g1 = [0u8; 32];
+        g1[0] = 0b10 << 6;
+        g1[31] = 1;
+        let generator = <Bn254 as PairingEngine>::G1Affine::prime_subgroup_generator();
+        assert_eq!(read_g1::<Bn254, _>(&g1[..]).unwrap(), generator);
+        g1[0] = 0b11 << 6;
+        assert_eq!(read_g1::<Bn254, _>(&g1[..]).unwrap(), -generator);
+
+        let mut uncompressed = [0u8; 64];
+        uncompressed[31] = 1;
+        uncompressed[63] = 2;
+        assert_eq!(read_g1::<Bn254, _>(&uncompressed[..]).unwrap(), generator);
+        assert!(read_g1::<Bn254, _>(&[0u8; 64][..]).unwrap().is_zero());
+        uncompressed[63] = 3;
+        read_g1::<Bn254, _>(&uncompressed[..]).expect_err("not on the curve");
+    }
+}
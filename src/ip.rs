@@ -1,9 +1,14 @@
 use crate::Error;
 use ark_ec::{msm::VariableBaseMSM, AffineCurve, PairingEngine};
-use ark_ff::PrimeField;
-use ark_std::{cfg_iter, vec::Vec};
+use ark_ff::{One, PrimeField};
+use ark_std::{cfg_chunks, cfg_iter, vec::Vec};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+/// Returns the product of the miller loops of the pairs $e(A_i, B_i)$. The
+/// pairs are split in one chunk per thread, each computed with a single multi
+/// miller loop which shares the squarings and the line evaluations of its
+/// pairs.
 pub(crate) fn pairing_miller_affine<E: PairingEngine>(
     left: &[E::G1Affine],
     right: &[E::G2Affine],
@@ -11,13 +16,38 @@ pub(crate) fn pairing_miller_affine<E: PairingEngine>(
     if left.len() != right.len() {
         return Err(Error::InvalidIPVectorLength);
     }
-    let pairs: Vec<(E::G1Prepared, E::G2Prepared)> = left
-        .par_iter()
-        .map(|e| E::G1Prepared::from(*e))
-        .zip(right.par_iter().map(|e| E::G2Prepared::from(*e)))
-        .collect::<Vec<_>>();
+    let size = chunk_size(left.len());
+    Ok(cfg_chunks!(left, size)
+        .zip(cfg_chunks!(right, size))
+        .map(|(left, right)| {
+            let pairs = left
+                .iter()
+                .zip(right)
+                .map(|(a, b)| (E::G1Prepared::from(*a), E::G2Prepared::from(*b)))
+                .collect::<Vec<_>>();
+            E::miller_loop(pairs.iter())
+        })
+        .product())
+}
+
+/// Same as `pairing_miller_affine` with the points already prepared.
+pub(crate) fn multi_miller_loop<E: PairingEngine>(
+    pairs: &[(E::G1Prepared, E::G2Prepared)],
+) -> E::Fqk {
+    if pairs.is_empty() {
+        return E::Fqk::one();
+    }
+    cfg_chunks!(pairs, chunk_size(pairs.len()))
+        .map(|chunk| E::miller_loop(chunk.iter()))
+        .product()
+}
 
-    Ok(E::miller_loop(pairs.iter()))
+fn chunk_size(len: usize) -> usize {
+    #[cfg(feature = "parallel")]
+    let threads = rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    let threads = 1;
+    ark_std::cmp::max(1, (len + threads - 1) / threads)
 }
 
 /// Returns the miller loop result of the inner pairing product
@@ -41,3 +71,48 @@ pub(crate) fn multiexponentiation<G: AffineCurve>(
         &cfg_iter!(right).map(|s| s.into_repr()).collect::<Vec<_>>(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::{Bn254, G1Projective, G2Projective};
+    use ark_ec::ProjectiveCurve;
+    use ark_std::UniformRand;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_chunked_miller_loop() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let left = (0..7)
+            .map(|_| G1Projective::rand(&mut rng).into_affine())
+            .collect::<Vec<_>>();
+        let right = (0..7)
+            .map(|_| G2Projective::rand(&mut rng).into_affine())
+            .collect::<Vec<_>>();
+        let expected = left
+            .iter()
+            .zip(&right)
+            .map(|(a, b)| Bn254::pairing(*a, *b))
+            .product::<<Bn254 as PairingEngine>::Fqk>();
+        let pairs = left
+            .iter()
+            .zip(&right)
+            .map(|(a, b)| ((*a).into(), (*b).into()))
+            .collect::<Vec<_>>();
+        let check = || {
+            assert_eq!(pairing::<Bn254>(&left, &right).unwrap(), expected);
+            let ml = multi_miller_loop::<Bn254>(&pairs);
+            assert_eq!(Bn254::final_exponentiation(&ml).unwrap(), expected);
+        };
+        check();
+        // the pairs are split in chunks of 3, 3 and 1
+        #[cfg(feature = "parallel")]
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap()
+            .install(check);
+        assert_eq!(multi_miller_loop::<Bn254>(&[]), One::one());
+        pairing::<Bn254>(&left, &right[1..]).expect_err("different lengths");
+    }
+}
@@ -1,32 +1,87 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+
 #[macro_use]
 mod macros;
 
-mod commitment;
+mod aggregator;
+#[cfg(feature = "std")]
+mod bellperson;
+pub mod bls;
+pub mod commitment;
+mod envelope;
 mod errors;
 mod ip;
-mod pairing_check;
+pub mod pairing_check;
 mod proof;
 mod prover;
 pub mod srs;
+pub mod tipp_mipp;
 pub mod transcript;
 mod verifier;
 
+#[cfg(feature = "std")]
+pub mod distributed;
+#[cfg(feature = "std")]
+mod gnark;
+#[cfg(feature = "std")]
 mod snarkjs_proof;
+#[cfg(feature = "std")]
+mod snarkjs_prover;
+#[cfg(feature = "std")]
 mod snarkjs_zkey;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate serde_derive;
 
+pub use aggregator::*;
+#[cfg(feature = "std")]
+pub use bellperson::*;
+pub use envelope::*;
 pub use errors::*;
+#[cfg(feature = "std")]
+pub use gnark::*;
+pub use proof::*;
 pub use prover::*;
+#[cfg(feature = "std")]
 pub use snarkjs_proof::*;
+#[cfg(feature = "std")]
+pub use snarkjs_prover::*;
+#[cfg(feature = "std")]
 pub use snarkjs_zkey::*;
 pub use transcript::*;
 pub use verifier::*;
 
 use ark_ec::{AffineCurve, ProjectiveCurve};
 use ark_ff::Field;
+use ark_std::{cfg_iter, cfg_iter_mut, vec::Vec};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+
+#[cfg(feature = "std")]
+pub(crate) use std::sync::Mutex;
+
+/// Single-threaded stand-in for `std::sync::Mutex`, used to share the
+/// verifier's rng between checks when the crate is built without `std`.
+#[cfg(not(feature = "std"))]
+pub struct Mutex<T>(core::cell::RefCell<T>);
+
+#[cfg(not(feature = "std"))]
+impl<T> Mutex<T> {
+    pub fn new(t: T) -> Self {
+        Mutex(core::cell::RefCell::new(t))
+    }
+
+    pub fn lock(&self) -> Result<core::cell::RefMut<'_, T>, core::convert::Infallible> {
+        Ok(self.0.borrow_mut())
+    }
+}
 /// Returns the vector used for the linear combination fo the inner pairing product
 /// between A and B for the Groth16 aggregation: A^r * B. It is required as it
 /// is not enough to simply prove the ipp of A*B, we need a random linear
@@ -44,13 +99,12 @@ pub(crate) fn structured_scalar_power<F: Field>(num: usize, s: &F) -> Vec<F> {
 /// vec[i+split]^scaler$. The `vec` vector is half of its size after this call.
 pub(crate) fn compress<C: AffineCurve>(vec: &mut Vec<C>, split: usize, scaler: &C::ScalarField) {
     let (left, right) = vec.split_at_mut(split);
-    left.par_iter_mut()
-        .zip(right.par_iter())
+    cfg_iter_mut!(left)
+        .zip(cfg_iter!(right))
         .for_each(|(a_l, a_r)| {
             //let mut x = mul!(a_r.into_projective(), scaler.clone());
-            let sc = scaler.clone();
-            let mut x = a_r.mul(sc);
-            x.add_assign_mixed(&a_l);
+            let mut x = a_r.mul(*scaler);
+            x.add_assign_mixed(a_l);
             *a_l = x.into_affine();
         });
     let len = left.len();
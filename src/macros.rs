@@ -1,3 +1,4 @@
+#[cfg(feature = "parallel")]
 macro_rules! try_par {
     ($(let $name:ident = $f:expr),+) => {
         $(
@@ -16,6 +17,18 @@ macro_rules! try_par {
     };
 }
 
+/// Sequential fallback of `try_par!` when the `parallel` feature is disabled:
+/// each expression is evaluated in order on the current thread.
+#[cfg(not(feature = "parallel"))]
+macro_rules! try_par {
+    ($(let $name:ident = $f:expr),+) => {
+        $(
+            let $name = $f?;
+        )+
+    };
+}
+
+#[cfg(feature = "parallel")]
 macro_rules! par {
     ($(let $name:ident = $f:expr),+) => {
         $(
@@ -55,6 +68,22 @@ macro_rules! par {
     }
 }
 
+/// Sequential fallback of `par!` when the `parallel` feature is disabled.
+#[cfg(not(feature = "parallel"))]
+macro_rules! par {
+    ($(let $name:ident = $f:expr),+) => {
+        $(
+            let $name = $f;
+        )+
+    };
+
+    ($(let ($name1:ident, $name2:ident) = $f:block),+) => {
+        $(
+            let ($name1, $name2) = $f;
+        )+
+    }
+}
+
 macro_rules! mul {
     ($a:expr, $b:expr) => {{
         let mut a = $a;
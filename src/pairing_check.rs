@@ -1,26 +1,32 @@
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{Field, PrimeField};
-use ark_std::{rand::Rng, sync::Mutex, One, UniformRand, Zero};
+use ark_std::{cfg_iter, ops::MulAssign, rand::Rng, vec::Vec, One, UniformRand, Zero};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
-use std::ops::MulAssign;
+use crate::{ip, Error, Mutex};
 
 /// PairingCheck represents a check of the form e(A,B)e(C,D)... = T. Checks can
 /// be aggregated together using random linear combination. The efficiency comes
 /// from keeping the results from the miller loop output before proceding to a final
 /// exponentiation when verifying if all checks are verified.
+///
+/// It is not specific to SnarkPack and can batch any pairing checks, e.g. KZG
+/// openings or BLS signatures, with the ones of an aggregate proof. Checks
+/// computed on different threads are combined with `merge` or by summing them,
+/// e.g. with rayon's `sum`.
 /// It is a tuple:
 /// - a miller loop result that is to be multiplied by other miller loop results
-/// before going into a final exponentiation result
+///   before going into a final exponentiation result
 /// - a right side result which is already in the right subgroup Gt which is to
-/// be compared to the left side when "final_exponentiatiat"-ed
+///   be compared to the left side when "final_exponentiatiat"-ed
 #[derive(Debug, Copy, Clone)]
 pub struct PairingCheck<E: PairingEngine> {
     left: E::Fqk,
     right: E::Fqk,
     /// simple counter tracking number of non_randomized checks. If there are
     /// more than 1 non randomized check, it is invalid.
-    non_randomized: u8,
+    non_randomized: usize,
 }
 
 impl<E> PairingCheck<E>
@@ -36,6 +42,8 @@ where
         }
     }
 
+    /// Returns a check which never verifies, e.g. to merge with the other
+    /// checks when a condition not involving pairings fails.
     pub fn new_invalid() -> PairingCheck<E> {
         Self {
             left: E::Fqk::one(),
@@ -52,7 +60,7 @@ where
     ///
     /// Note the check is NOT randomized and there must be only up to ONE check
     /// only that can not be randomized when merging.
-    fn from_pair(result: E::Fqk, exp: E::Fqk) -> PairingCheck<E> {
+    pub fn from_pair(result: E::Fqk, exp: E::Fqk) -> PairingCheck<E> {
         Self {
             left: result,
             right: exp,
@@ -89,36 +97,39 @@ where
         it: &[(&'a E::G1Affine, &'a E::G2Affine)],
         out: &'a E::Fqk,
     ) -> PairingCheck<E> {
-        let coeff = rand_fr::<E, R>(&rng);
-        let miller_out = it
-            .into_par_iter()
+        let coeff = rand_fr::<E, R>(rng);
+        let pairs = cfg_iter!(it)
             .map(|(a, b)| {
                 let na = a.mul(coeff).into_affine();
-                (
-                    E::G1Prepared::from(na.into()),
-                    E::G2Prepared::from((**b).into()),
-                )
+                (E::G1Prepared::from(na), E::G2Prepared::from(**b))
             })
-            .map(|(a, b)| E::miller_loop([&(a, b)]))
-            .fold(
-                || E::Fqk::one(),
-                |mut acc, res| {
-                    acc.mul_assign(&res);
-                    acc
-                },
-            )
-            .reduce(
-                || E::Fqk::one(),
-                |mut acc, res| {
-                    acc.mul_assign(&res);
-                    acc
-                },
-            );
-        let mut outt = out.clone();
+            .collect::<Vec<_>>();
+        Self::randomized(ip::multi_miller_loop::<E>(&pairs), out, coeff)
+    }
+
+    /// Same as `rand` with the $G_2$ points already prepared, e.g. for the
+    /// fixed points of a verifying key.
+    pub fn rand_prepared<'a, R: Rng + Send>(
+        rng: &Mutex<R>,
+        it: &[(&'a E::G1Affine, &'a E::G2Prepared)],
+        out: &'a E::Fqk,
+    ) -> PairingCheck<E> {
+        let coeff = rand_fr::<E, R>(rng);
+        let pairs = cfg_iter!(it)
+            .map(|(a, b)| {
+                let na = a.mul(coeff).into_affine();
+                (E::G1Prepared::from(na), (*b).clone())
+            })
+            .collect::<Vec<_>>();
+        Self::randomized(ip::multi_miller_loop::<E>(&pairs), out, coeff)
+    }
+
+    fn randomized(miller_out: E::Fqk, out: &E::Fqk, coeff: E::Fr) -> PairingCheck<E> {
+        let mut outt = *out;
         if out != &E::Fqk::one() {
             // we only need to make this expensive operation is the output is
             // not one since 1^r = 1
-            outt = outt.pow(&coeff.into_repr());
+            outt = outt.pow(coeff.into_repr());
         }
         PairingCheck {
             left: miller_out,
@@ -133,23 +144,46 @@ where
         mul_if_not_one::<E>(&mut self.left, &p2.left);
         mul_if_not_one::<E>(&mut self.right, &p2.right);
         // A merged PairingCheck is only randomized if both of its contributors are.
-        self.non_randomized += p2.non_randomized;
+        self.non_randomized = self.non_randomized.saturating_add(p2.non_randomized);
     }
 
-    /// Returns false if there is more than 1 non-random check and otherwise
-    /// returns true if
+    /// Returns an error if there is more than 1 non-random check and otherwise
+    /// checks that
     /// $$
     ///   FinalExponentiation(left) == right
     /// $$
-    pub fn verify(&self) -> bool {
+    pub fn verify(&self) -> Result<(), Error> {
         if self.non_randomized > 1 {
-            dbg!(format!(
-                "Pairing checks have more than 1 non-random checks {}",
-                self.non_randomized
-            ));
-            return false;
+            return Err(Error::InvalidPairing);
         }
-        E::final_exponentiation(&self.left).unwrap() == self.right
+        match E::final_exponentiation(&self.left) {
+            Some(left) if left == self.right => Ok(()),
+            _ => Err(Error::InvalidPairing),
+        }
+    }
+}
+
+impl<E: PairingEngine> Default for PairingCheck<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: PairingEngine> core::iter::Sum for PairingCheck<E> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::new(), |mut acc, check| {
+            acc.merge(&check);
+            acc
+        })
+    }
+}
+
+impl<'a, E: PairingEngine> core::iter::Sum<&'a PairingCheck<E>> for PairingCheck<E> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::new(), |mut acc, check| {
+            acc.merge(check);
+            acc
+        })
     }
 }
 
@@ -165,30 +199,27 @@ fn rand_fr<E: PairingEngine, R: Rng + Send>(r: &Mutex<R>) -> E::Fr {
 fn mul_if_not_one<E: PairingEngine>(left: &mut E::Fqk, right: &E::Fqk) {
     let one = E::Fqk::one();
     if left == &one {
-        *left = right.clone();
-        return;
-    } else if right == &one {
-        // nothing to do here
-        return;
+        *left = *right;
+    } else if right != &one {
+        left.mul_assign(right);
     }
-    left.mul_assign(right);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use ark_bn254::{Bn254, G1Projective, G2Projective};
+    use ark_bn254::{Bn254, Fr, G1Projective, G2Projective};
     use ark_std::{rand::Rng, UniformRand};
     use rand_core::SeedableRng;
 
     fn gen_pairing_check<R: Rng + Send>(r: &mut R) -> PairingCheck<Bn254> {
         let g1r = G1Projective::rand(r);
         let g2r = G2Projective::rand(r);
-        let exp = Bn254::pairing(g1r.clone(), g2r.clone());
+        let exp = Bn254::pairing(g1r, g2r);
         let mr = Mutex::new(r);
         let tuple =
             PairingCheck::<Bn254>::rand(&mr, &[(&g1r.into_affine(), &g2r.into_affine())], &exp);
-        assert!(tuple.verify());
+        tuple.verify().unwrap();
         tuple
     }
     #[test]
@@ -200,9 +231,44 @@ mod test {
         let final_tuple = tuples
             .iter()
             .fold(PairingCheck::<Bn254>::new(), |mut acc, tu| {
-                acc.merge(&tu);
+                acc.merge(tu);
                 acc
             });
-        assert!(final_tuple.verify());
+        final_tuple.verify().unwrap();
+        // checks computed on several threads are merged by summing them
+        let summed: PairingCheck<Bn254> = cfg_iter!(tuples).sum();
+        summed.verify().unwrap();
+    }
+
+    #[test]
+    fn test_pairing_check() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let g1 = G1Projective::rand(&mut rng).into_affine();
+        let g2 = G2Projective::rand(&mut rng).into_affine();
+        let x = Fr::rand(&mut rng);
+        let g1x = g1.mul(x).into_affine();
+        // e(g1, g2)^x = e(g1^x, g2)
+        let out = Bn254::pairing(g1x, g2);
+        let prepared = <Bn254 as PairingEngine>::G2Prepared::from(g2);
+        let rng = Mutex::new(rng);
+        let check = PairingCheck::<Bn254>::rand_prepared(&rng, &[(&g1x, &prepared)], &out);
+        check.verify().unwrap();
+
+        // a single check does not need to be randomized
+        let ml = Bn254::miller_loop([&(g1.mul(x).into_affine().into(), prepared.clone())]);
+        let mut acc = PairingCheck::<Bn254>::from_pair(ml, out);
+        acc.merge(&check);
+        acc.verify().unwrap();
+        let mut twice = acc;
+        twice.merge(&PairingCheck::from_pair(ml, out));
+        assert!(matches!(twice.verify(), Err(Error::InvalidPairing)));
+
+        let wrong = PairingCheck::<Bn254>::rand_prepared(&rng, &[(&g1, &prepared)], &out);
+        assert!(matches!(wrong.verify(), Err(Error::InvalidPairing)));
+        let mut invalid = check;
+        invalid.merge(&PairingCheck::new_invalid());
+        assert!(matches!(invalid.verify(), Err(Error::InvalidPairing)));
+        // the identity check
+        PairingCheck::<Bn254>::default().verify().unwrap();
     }
 }
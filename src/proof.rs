@@ -1,6 +1,11 @@
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
-use std::io::{Read, Write};
+use ark_std::{
+    io::{Read, Write},
+    log2,
+    string::ToString,
+    vec::Vec,
+};
 
 use super::Error;
 use super::{
@@ -11,6 +16,9 @@ use super::{
 /// AggregateProof contains all elements to verify n aggregated Groth16 proofs
 /// using inner pairing product arguments. This proof can be created by any
 /// party in possession of valid Groth16 proofs.
+/// It is also the output of the standalone TIPP and MIPP arguments in
+/// `tipp_mipp`, for which `com_ab`, `com_c`, `ip_ab` and `agg_c` are the
+/// statement.
 #[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
 pub struct AggregateProof<E: PairingEngine> {
     /// commitment to A and B using the pair commitment scheme needed to verify
@@ -53,7 +61,7 @@ impl<E: PairingEngine> AggregateProof<E> {
             ));
         }
         // 3. Check all vectors are of the same length and of the correct length
-        let ref_len = (gipa.nproofs as f32).log2().ceil() as usize;
+        let ref_len = log2(gipa.nproofs as usize) as usize;
         let all_same = ref_len == gipa.comms_ab.len()
             && ref_len == gipa.comms_c.len()
             && ref_len == gipa.z_ab.len()
@@ -71,8 +79,7 @@ impl<E: PairingEngine> AggregateProof<E> {
     /// another arkwork protocol, you can use the underlying implementation of
     /// `CanonicalSerialize`.
     pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
-        self.serialize(&mut out)
-            .map_err(|e| Error::Serialization(e))
+        self.serialize(&mut out).map_err(Error::Serialization)
     }
 
     /// Reads the aggregate proof to the given destination. This method is for
@@ -80,13 +87,92 @@ impl<E: PairingEngine> AggregateProof<E> {
     /// another arkwork protocol, you can use the underlying implementation of
     /// `CanonicalSerialize`.
     pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
-        Self::deserialize(&mut source).map_err(|e| Error::Serialization(e))
+        Self::deserialize(&mut source).map_err(Error::Serialization)
+    }
+}
+
+/// AggregateProofWithCommitments aggregates Groth16 proofs extended with a
+/// commitment $D \in G_1$ to some of the witness, as in LegoGroth16 or in the
+/// commitment extension of gnark, whose verification equation is
+/// $e(A,B) = e(\alpha,\beta)e(S + D,\gamma)e(C,\delta)$ with $S$ the public
+/// inputs term. The D points are folded as a second MIPP vector in the GIPA
+/// loop of the aggregate proof, with the same randomness and challenges, so
+/// only the values of D sent in each round are added to the proof.
+///
+/// Only the verification equation is checked: gnark also proves knowledge of
+/// the committed values with a Pedersen proof of knowledge for each proof,
+/// which is not aggregated nor checked here.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct AggregateProofWithCommitments<E: PairingEngine> {
+    pub aggregate: AggregateProof<E>,
+    /// commitment to the D points with the key v
+    pub com_d: commitment::Output<E::Fqk>,
+    /// $D^r$ is added to the public inputs term of the aggregated equation
+    pub agg_d: E::G1Affine,
+    pub gipa_d: GipaMippProof<E>,
+}
+
+impl<E: PairingEngine> AggregateProofWithCommitments<E> {
+    /// Writes the proof to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        self.serialize(&mut out).map_err(Error::Serialization)
+    }
+
+    /// Reads a proof from the given source.
+    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+        Self::deserialize(&mut source).map_err(Error::Serialization)
+    }
+}
+
+/// InputsCommitment is a KZG commitment, over the powers $g^{a^i}$ of the SRS,
+/// to each column of the public inputs: the column $i$ is committed as the
+/// polynomial $\sum_j a_{j,i} X^j$ where $a_{j,i}$ is the $i$-th public input
+/// of the $j$-th proof. It replaces the public inputs when verifying with
+/// `verify_with_committed_inputs`.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone, PartialEq)]
+pub struct InputsCommitment<E: PairingEngine> {
+    pub columns: Vec<E::G1Affine>,
+}
+
+impl<E: PairingEngine> InputsCommitment<E> {
+    /// Writes the commitment to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        self.serialize(&mut out).map_err(Error::Serialization)
+    }
+
+    /// Reads a commitment from the given source.
+    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+        Self::deserialize(&mut source).map_err(Error::Serialization)
+    }
+}
+
+/// CommittedInputsProof is an aggregate proof together with the evaluations
+/// of the input column polynomials at the random point used to aggregate the
+/// proofs, and a KZG opening proof of a random linear combination of these
+/// evaluations.
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct CommittedInputsProof<E: PairingEngine> {
+    pub aggregate: AggregateProof<E>,
+    pub evaluations: Vec<E::Fr>,
+    pub opening: E::G1Affine,
+}
+
+impl<E: PairingEngine> CommittedInputsProof<E> {
+    /// Writes the proof to the given destination.
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        self.serialize(&mut out).map_err(Error::Serialization)
+    }
+
+    /// Reads a proof from the given source.
+    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+        Self::deserialize(&mut source).map_err(Error::Serialization)
     }
 }
 
 /// It contains all elements derived in the GIPA loop for both TIPP and MIPP at
 /// the same time. Serialization is done manually here for better inspection
 /// (CanonicalSerialization is implemented manually, not via the macro).
+#[allow(clippy::type_complexity)]
 #[derive(Debug, Clone)]
 pub struct GipaProof<E: PairingEngine> {
     pub nproofs: u32,
@@ -103,6 +189,17 @@ pub struct GipaProof<E: PairingEngine> {
     pub final_wkey: (E::G1Affine, E::G1Affine),
 }
 
+/// GipaMippProof contains the values derived in the GIPA loop for an extra
+/// vector proven with MIPP alongside C, such as the D points of
+/// `AggregateProofWithCommitments`.
+#[allow(clippy::type_complexity)]
+#[derive(CanonicalSerialize, CanonicalDeserialize, Debug, Clone)]
+pub struct GipaMippProof<E: PairingEngine> {
+    pub comms_d: Vec<(commitment::Output<E::Fqk>, commitment::Output<E::Fqk>)>,
+    pub z_d: Vec<(E::G1Affine, E::G1Affine)>,
+    pub final_d: E::G1Affine,
+}
+
 impl<E: PairingEngine> PartialEq for GipaProof<E> {
     fn eq(&self, other: &Self) -> bool {
         self.nproofs == other.nproofs
@@ -120,14 +217,14 @@ impl<E: PairingEngine> PartialEq for GipaProof<E> {
 
 impl<E: PairingEngine> GipaProof<E> {
     fn log_proofs(nproofs: usize) -> usize {
-        (nproofs as f32).log2().ceil() as usize
+        log2(nproofs) as usize
     }
 }
 
 impl<E: PairingEngine> CanonicalSerialize for GipaProof<E> {
     fn serialized_size(&self) -> usize {
         let log_proofs = Self::log_proofs(self.nproofs as usize);
-        (self.nproofs as u32).serialized_size()
+        self.nproofs.serialized_size()
             + log_proofs
                 * (self.comms_ab[0].0.serialized_size()
                     + self.comms_ab[0].1.serialized_size()
@@ -303,7 +400,7 @@ mod tests {
         let q = G2Affine::prime_subgroup_generator();
         let a = Bls12::pairing(p, q);
 
-        let proof = AggregateProof::<Bls12> {
+        AggregateProof::<Bls12> {
             com_ab: O(a, a),
             com_c: O(a, a),
             ip_ab: a,
@@ -345,8 +442,7 @@ mod tests {
                     G1Affine::prime_subgroup_generator(),
                 ),
             },
-        };
-        proof
+        }
     }
 
     #[test]
@@ -354,7 +450,7 @@ mod tests {
         let proof = fake_proof();
         let mut buffer = Vec::new();
         proof.write(&mut buffer).unwrap();
-        let out = AggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).unwrap();
+        let out = AggregateProof::<Bls12>::read(ark_std::io::Cursor::new(&buffer)).unwrap();
         assert_eq!(proof, out);
     }
 
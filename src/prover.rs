@@ -1,11 +1,21 @@
 use ark_ec::{msm::VariableBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{Field, One, PrimeField};
-use ark_groth16::Proof;
+use ark_groth16::{Proof, VerifyingKey};
 use ark_poly::polynomial::{univariate::DensePolynomial, UVPolynomial};
-use ark_std::{cfg_iter, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{
+    cfg_into_iter, cfg_iter, cfg_iter_mut,
+    fmt::Debug,
+    format,
+    ops::{AddAssign, MulAssign, Neg},
+    string::ToString,
+    vec,
+    vec::Vec,
+    Zero,
+};
 
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use std::ops::{AddAssign, MulAssign, Neg};
 
 use super::{
     commitment,
@@ -13,13 +23,39 @@ use super::{
     compress,
     errors::Error,
     ip,
-    proof::{AggregateProof, GipaProof, KZGOpening, TippMippProof},
+    proof::{
+        AggregateProof, AggregateProofWithCommitments, CommittedInputsProof, GipaMippProof,
+        GipaProof, InputsCommitment, KZGOpening, TippMippProof,
+    },
     srs::ProverSRS,
     structured_scalar_power,
-    transcript::Transcript,
+    transcript::{bind_statement, AggregateTranscript, GipaRound, InputsBinding, Transcript},
 };
 
+/// Aggregate `n` zkSnark proofs, where `n` must be a power of two, binding the
+/// aggregate proof to its statement: before aggregating, the hash of the
+/// Groth16 verifying key, the hash of the SRS, the number of proofs and the
+/// public inputs (unless `binding` is `InputsBinding::Fixed`) are appended to
+/// the transcript. The proof must be verified with `verify_bound`.
+pub fn aggregate_and_bind<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    vk: &VerifyingKey<E>,
+    public_inputs: &[Vec<E::Fr>],
+    proofs: &[Proof<E>],
+    binding: InputsBinding,
+    transcript: &mut T,
+) -> Result<AggregateProof<E>, Error> {
+    if public_inputs.len() != proofs.len() {
+        return Err(Error::InvalidProof(
+            "public inputs len != number of proofs".to_string(),
+        ));
+    }
+    bind_statement(transcript, vk, &srs.srs_hash, public_inputs, binding)?;
+    aggregate_proofs(srs, transcript, proofs)
+}
+
 /// Aggregate `n` zkSnark proofs, where `n` must be a power of two.
+/// See `aggregate_and_bind` for a version binding the statement automatically.
 /// WARNING: transcript_include represents everything that should be included in
 /// the transcript from outside the boundary of this function. This is especially
 /// relevant for ALL public inputs of ALL individual proofs. In the regular case,
@@ -30,23 +66,177 @@ use super::{
 /// number of proofs and public inputs (+100ms in our case). In the case of Filecoin, the only
 /// non-fixed part of the public inputs are the challenges derived from a seed. Even though this
 /// seed comes from a random beeacon, we are hashing this as a safety precaution.
-pub fn aggregate_proofs<E: PairingEngine + std::fmt::Debug, T: Transcript>(
+/// See `Transcript::append_inputs_digest` to include them.
+pub fn aggregate_proofs<E: PairingEngine + Debug, T: Transcript>(
     srs: &ProverSRS<E>,
     transcript: &mut T,
     proofs: &[Proof<E>],
 ) -> Result<AggregateProof<E>, Error> {
-    if proofs.len() < 2 {
-        return Err(Error::InvalidProof("invalid proof size < 2".to_string()));
+    commit_and_aggregate(srs, transcript, proofs).map(|(proof, _)| proof)
+}
+
+/// Aggregates Groth16 proofs extended with a commitment D, see
+/// `AggregateProofWithCommitments`. `commitments` holds the D point of each
+/// proof, in the same order. The proof must be verified with
+/// `verify_aggregate_proof_with_commitments`.
+pub fn aggregate_proofs_with_commitments<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    proofs: &[Proof<E>],
+    commitments: &[E::G1Affine],
+) -> Result<AggregateProofWithCommitments<E>, Error> {
+    if commitments.len() != proofs.len() {
+        return Err(Error::InvalidProof(
+            "commitments len != number of proofs".to_string(),
+        ));
     }
-    if !proofs.len().is_power_of_two() {
+    check_nproofs(srs, proofs.len())?;
+    // D is committed before r is derived so it is bound by the linear
+    // combination as A, B and C are
+    let com_d = commitment::single_g1::<E>(&srs.vkey, commitments)?;
+    transcript.append(b"D-commitment", &com_d);
+    // D^r is proven by folding D along with C in the GIPA loop of the
+    // aggregate proof, with the same challenges
+    let (aggregate, d, _) =
+        commit_and_aggregate_with_d(srs, transcript, proofs, commitments.to_vec())?;
+    let (agg_d, gipa_d) = d.ok_or_else(|| Error::InvalidProof("no commitments".to_string()))?;
+
+    Ok(AggregateProofWithCommitments {
+        aggregate,
+        com_d,
+        agg_d,
+        gipa_d,
+    })
+}
+
+/// Aggregates the proofs as `aggregate_proofs` does, and proves the evaluation
+/// of the committed public inputs needed by the verifier, so that it does not
+/// need the public inputs. The proof must be verified with
+/// `verify_with_committed_inputs`. `commitment` must be the output of
+/// `commit_inputs` for the public inputs of the proofs: it is appended to the
+/// transcript and is the statement the verifier checks the proof against.
+pub fn aggregate_with_committed_inputs<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    commitment: &InputsCommitment<E>,
+    public_inputs: &[Vec<E::Fr>],
+    proofs: &[Proof<E>],
+) -> Result<CommittedInputsProof<E>, Error> {
+    if public_inputs.len() != proofs.len() {
         return Err(Error::InvalidProof(
-            "invalid proof size: not power of two".to_string(),
+            "public inputs len != number of proofs".to_string(),
         ));
     }
+    let l = commitment.columns.len();
+    if public_inputs.iter().any(|inputs| inputs.len() != l) {
+        return Err(Error::MalformedVerifyingKey);
+    }
+    transcript.append(b"inputs-commitment", &commitment.columns);
+    let (aggregate, r) = commit_and_aggregate(srs, transcript, proofs)?;
+
+    // evaluation at r of the polynomial of each column
+    let powers = structured_scalar_power(proofs.len(), &r);
+    let evaluations = cfg_into_iter!(0..l)
+        .map(|i| {
+            public_inputs
+                .iter()
+                .zip(powers.iter())
+                .map(|(inputs, power)| inputs[i] * power)
+                .sum::<E::Fr>()
+        })
+        .collect::<Vec<_>>();
+    transcript.append(b"inputs-evaluations", &evaluations);
+    let gamma = transcript.challenge_scalar::<E::Fr>(b"inputs-challenge");
+    let gammas = structured_scalar_power(l, &gamma);
+
+    // coefficients of the random linear combination of the columns
+    let coeffs = cfg_iter!(public_inputs)
+        .map(|inputs| {
+            inputs
+                .iter()
+                .zip(gammas.iter())
+                .map(|(a, g)| *a * g)
+                .sum::<E::Fr>()
+        })
+        .collect::<Vec<_>>();
+    // (f(X) - f(r)) / (X - r) by synthetic division
+    let mut quotient = vec![E::Fr::zero(); coeffs.len() - 1];
+    let mut acc = E::Fr::zero();
+    for (q, coeff) in quotient.iter_mut().zip(coeffs.iter().skip(1)).rev() {
+        acc = acc * r + coeff;
+        *q = acc;
+    }
+    let quotient = cfg_iter!(quotient)
+        .map(|q| q.into_repr())
+        .collect::<Vec<_>>();
+    let opening =
+        VariableBaseMSM::multi_scalar_mul(&srs.g_alpha_powers_table[..quotient.len()], &quotient)
+            .into_affine();
+
+    Ok(CommittedInputsProof {
+        aggregate,
+        evaluations,
+        opening,
+    })
+}
 
-    if !srs.has_correct_len(proofs.len()) {
-        return Err(Error::InvalidSRS("SRS len != proofs len".to_string()));
+/// Commits to the public inputs of the proofs to aggregate, see
+/// `InputsCommitment`.
+pub fn commit_inputs<E: PairingEngine>(
+    srs: &ProverSRS<E>,
+    public_inputs: &[Vec<E::Fr>],
+) -> Result<InputsCommitment<E>, Error> {
+    let n = public_inputs.len();
+    if n == 0 || n > srs.n {
+        return Err(Error::InvalidSRS("SRS len < proofs len".to_string()));
     }
+    let l = public_inputs[0].len();
+    if public_inputs.iter().any(|inputs| inputs.len() != l) {
+        return Err(Error::InvalidProof(
+            "proofs have different numbers of public inputs".to_string(),
+        ));
+    }
+    let columns = cfg_into_iter!(0..l)
+        .map(|i| {
+            let column = public_inputs
+                .iter()
+                .map(|inputs| inputs[i].into_repr())
+                .collect::<Vec<_>>();
+            VariableBaseMSM::multi_scalar_mul(&srs.g_alpha_powers_table[..n], &column).into_affine()
+        })
+        .collect();
+    Ok(InputsCommitment { columns })
+}
+
+/// Commits to A, B and C and aggregates the proofs. It returns the proof as
+/// well as the randomness used to combine the proofs.
+pub(crate) fn commit_and_aggregate<E: PairingEngine + Debug, T: AggregateTranscript<E>>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    proofs: &[Proof<E>],
+) -> Result<(AggregateProof<E>, E::Fr), Error> {
+    commit_and_aggregate_with_d(srs, transcript, proofs, Vec::new()).map(|(proof, _, r)| (proof, r))
+}
+
+/// Commits to A, B and C and aggregates the proofs as `commit_and_aggregate`
+/// does. If `d` is not empty, it holds the D point of each proof, which is
+/// folded along with C: $D^r$ and the values of the D vector in GIPA are
+/// returned as well.
+#[allow(clippy::type_complexity)]
+fn commit_and_aggregate_with_d<E: PairingEngine + Debug, T: AggregateTranscript<E>>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    proofs: &[Proof<E>],
+    d: Vec<E::G1Affine>,
+) -> Result<
+    (
+        AggregateProof<E>,
+        Option<(E::G1Affine, GipaMippProof<E>)>,
+        E::Fr,
+    ),
+    Error,
+> {
+    check_nproofs(srs, proofs.len())?;
     // We first commit to A B and C - these commitments are what the verifier
     // will use later to verify the TIPP and MIPP proofs
     par! {
@@ -65,63 +255,363 @@ pub fn aggregate_proofs<E: PairingEngine + std::fmt::Debug, T: Transcript>(
         let com_ab = commitment::pair::<E>(&srs.vkey, &srs.wkey, refa, refb),
         let com_c = commitment::single_g1::<E>(&srs.vkey, refc)
     };
+    aggregate_committed_with_d(srs, transcript, a, b, c, d, com_ab, com_c)
+}
 
-    // Derive a random scalar to perform a linear combination of proofs
-    transcript.append(b"AB-commitment", &com_ab);
-    transcript.append(b"C-commitment", &com_c);
-    let r = transcript.challenge_scalar::<E::Fr>(b"r-random-fiatshamir");
+/// Aggregates the proofs whose A, B and C vectors are committed in `com_ab`
+/// and `com_c` with the commitment keys of the SRS. The vectors are consumed
+/// so GIPA can fold them in place instead of copying them. It returns the
+/// proof as well as the randomness used to combine the proofs.
+pub(crate) fn aggregate_committed<E: PairingEngine + Debug, T: AggregateTranscript<E>>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+    c: Vec<E::G1Affine>,
+    com_ab: commitment::Output<E::Fqk>,
+    com_c: commitment::Output<E::Fqk>,
+) -> Result<(AggregateProof<E>, E::Fr), Error> {
+    aggregate_committed_with_d(srs, transcript, a, b, c, Vec::new(), com_ab, com_c)
+        .map(|(proof, _, r)| (proof, r))
+}
 
+/// Aggregates the committed proofs as `aggregate_committed` does, folding the
+/// D points along with C if `d` is not empty.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn aggregate_committed_with_d<E: PairingEngine + Debug, T: AggregateTranscript<E>>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+    c: Vec<E::G1Affine>,
+    d: Vec<E::G1Affine>,
+    com_ab: commitment::Output<E::Fqk>,
+    com_c: commitment::Output<E::Fqk>,
+) -> Result<
+    (
+        AggregateProof<E>,
+        Option<(E::G1Affine, GipaMippProof<E>)>,
+        E::Fr,
+    ),
+    Error,
+> {
+    let r = derive_r::<E, _>(transcript, &com_ab, &com_c);
     // 1,r, r^2, r^3, r^4 ...
-    let r_vec: Vec<E::Fr> = structured_scalar_power(proofs.len(), &r);
-    // 1,r^-1, r^-2, r^-3
-    let r_inv = r_vec
-        .par_iter()
-        .map(|ri| ri.inverse().unwrap())
-        .collect::<Vec<_>>();
-
-    // B^{r}
-    let b_r = b
-        .par_iter()
-        .zip(r_vec.par_iter())
-        .map(|(bi, ri)| mul!(bi.into_projective(), ri.clone()).into_affine())
-        .collect::<Vec<_>>();
-
-    let refb_r = &b_r;
-    let refr_vec = &r_vec;
-    try_par! {
-        // compute A * B^r for the verifier
-        let ip_ab = ip::pairing::<E>(&refa, &refb_r),
-        // compute C^r for the verifier
-        let agg_c = ip::multiexponentiation::<E::G1Affine>(&refc, &refr_vec)
-    };
+    let r_vec: Vec<E::Fr> = structured_scalar_power(a.len(), &r);
+    let (mut vectors, ip_ab, agg_c) =
+        GipaVectors::<E>::randomized(a, b, c, srs.vkey.clone(), &srs.wkey, r_vec)?;
     let agg_c = agg_c.into_affine();
-    // w^{r^{-1}}
-    let wkey_r_inv = srs.wkey.scale(&r_inv)?;
-
-    // we prove tipp and mipp using the same recursive loop
-    let proof = prove_tipp_mipp(
-        &srs,
-        transcript,
-        &a,
-        &b_r,
-        &c,
-        &wkey_r_inv,
-        &r_vec,
-        &ip_ab,
-        &agg_c,
-    )?;
     debug_assert!({
-        let computed_com_ab = commitment::pair::<E>(&srs.vkey, &wkey_r_inv, &a, &b_r).unwrap();
+        let computed_com_ab =
+            commitment::pair::<E>(&vectors.vkey, &vectors.wkey, &vectors.a, &vectors.b).unwrap();
         com_ab == computed_com_ab
     });
+    let agg_d = if d.is_empty() {
+        None
+    } else {
+        Some(vectors.set_d(d)?.into_affine())
+    };
 
-    Ok(AggregateProof {
+    // we prove tipp and mipp using the same recursive loop
+    let (proof, gipa_d) =
+        prove_tipp_mipp_with_d(srs, transcript, vectors, r, &ip_ab, &agg_c, agg_d.as_ref())?;
+
+    let proof = AggregateProof {
         com_ab,
         com_c,
         ip_ab,
         agg_c,
         tmipp: proof,
-    })
+    };
+    Ok((proof, agg_d.zip(gipa_d), r))
+}
+
+/// Checks the number of proofs can be aggregated with the given SRS.
+pub(crate) fn check_nproofs<E: PairingEngine>(srs: &ProverSRS<E>, n: usize) -> Result<(), Error> {
+    if n < 2 {
+        return Err(Error::InvalidProof("invalid proof size < 2".to_string()));
+    }
+    if !n.is_power_of_two() {
+        return Err(Error::InvalidProof(
+            "invalid proof size: not power of two".to_string(),
+        ));
+    }
+    if !srs.has_correct_len(n) {
+        return Err(Error::InvalidSRS("SRS len != proofs len".to_string()));
+    }
+    Ok(())
+}
+
+/// Derives the random scalar used to perform a linear combination of proofs
+/// from the commitments to A, B and C.
+pub(crate) fn derive_r<E: PairingEngine, T: AggregateTranscript<E>>(
+    transcript: &mut T,
+    com_ab: &commitment::Output<E::Fqk>,
+    com_c: &commitment::Output<E::Fqk>,
+) -> E::Fr {
+    transcript.r_challenge(com_ab, com_c)
+}
+
+/// Vectors folded at each round of GIPA, with the commitment keys folded
+/// along with them. In the context of Groth16 aggregation, B is rescaled by
+/// $r$ and the key $w$ by $r^{-1}$. D is empty unless the proofs have an extra
+/// commitment, in which case it is folded as C is.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct GipaVectors<E: PairingEngine> {
+    pub a: Vec<E::G1Affine>,
+    pub b: Vec<E::G2Affine>,
+    pub c: Vec<E::G1Affine>,
+    pub d: Vec<E::G1Affine>,
+    pub r: Vec<E::Fr>,
+    pub vkey: VKey<E>,
+    pub wkey: WKey<E>,
+}
+
+/// Values computed at each round of GIPA from the left and right halves of the
+/// vectors. They are products of pairings and sums of points, so the values
+/// computed on different parts of the vectors are combined with `merge`.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct RoundTerms<E: PairingEngine> {
+    pub tab_l: commitment::Output<E::Fqk>,
+    pub tab_r: commitment::Output<E::Fqk>,
+    pub zab_l: E::Fqk,
+    pub zab_r: E::Fqk,
+    pub zc_l: E::G1Projective,
+    pub zc_r: E::G1Projective,
+    pub tuc_l: commitment::Output<E::Fqk>,
+    pub tuc_r: commitment::Output<E::Fqk>,
+    pub d: Option<MippTerms<E>>,
+}
+
+/// Values computed at each round of GIPA for the D vector, which are the same
+/// as the MIPP values of C.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct MippTerms<E: PairingEngine> {
+    pub z_l: E::G1Projective,
+    pub z_r: E::G1Projective,
+    pub tu_l: commitment::Output<E::Fqk>,
+    pub tu_r: commitment::Output<E::Fqk>,
+}
+
+impl<E: PairingEngine> RoundTerms<E> {
+    #[cfg(feature = "std")]
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.tab_l.mul_assign(&other.tab_l);
+        self.tab_r.mul_assign(&other.tab_r);
+        self.zab_l.mul_assign(&other.zab_l);
+        self.zab_r.mul_assign(&other.zab_r);
+        self.zc_l.add_assign(&other.zc_l);
+        self.zc_r.add_assign(&other.zc_r);
+        self.tuc_l.mul_assign(&other.tuc_l);
+        self.tuc_r.mul_assign(&other.tuc_r);
+        if let (Some(d), Some(other)) = (self.d.as_mut(), other.d.as_ref()) {
+            d.z_l.add_assign(&other.z_l);
+            d.z_r.add_assign(&other.z_r);
+            d.tu_l.mul_assign(&other.tu_l);
+            d.tu_r.mul_assign(&other.tu_r);
+        }
+    }
+}
+
+/// Folding is implemented by the vectors GIPA recurses on, whether they are
+/// held in memory or split across workers.
+pub(crate) trait Folding<E: PairingEngine> {
+    /// Returns the current length of the vectors.
+    fn len(&self) -> usize;
+
+    /// Computes the values of the current round.
+    fn cross_terms(&mut self) -> Result<RoundTerms<E>, Error>;
+
+    /// Folds the vectors and keys in half with the round challenge.
+    fn compress(&mut self, c: &E::Fr, c_inv: &E::Fr) -> Result<(), Error>;
+
+    /// Returns the vectors once they are folded down to a single element.
+    fn into_vectors(self) -> Result<GipaVectors<E>, Error>;
+}
+
+impl<E: PairingEngine> GipaVectors<E> {
+    /// Returns true if the vectors and keys have `n` elements and r has
+    /// `r_len` elements.
+    #[cfg(feature = "std")]
+    pub(crate) fn has_len(&self, n: usize, r_len: usize) -> bool {
+        self.a.len() == n
+            && self.b.len() == n
+            && self.c.len() == n
+            && (self.d.is_empty() || self.d.len() == n)
+            && self.r.len() == r_len
+            && self.vkey.has_correct_len(n)
+            && self.wkey.has_correct_len(n)
+    }
+
+    /// Returns the vectors to fold, with B rescaled in place by `r_vec` and
+    /// the key $w$ by its inverse, as well as $A * B^r$ and $C^r$ which the
+    /// verifier needs.
+    pub(crate) fn randomized(
+        a: Vec<E::G1Affine>,
+        mut b: Vec<E::G2Affine>,
+        c: Vec<E::G1Affine>,
+        vkey: VKey<E>,
+        wkey: &WKey<E>,
+        r_vec: Vec<E::Fr>,
+    ) -> Result<(Self, E::Fqk, E::G1Projective), Error> {
+        // r^-1, r^-2, r^-3 ...
+        let r_inv = cfg_iter!(r_vec)
+            .map(|ri| ri.inverse().unwrap())
+            .collect::<Vec<_>>();
+
+        // B^{r} - B itself is not needed anymore so it is rescaled in place
+        cfg_iter_mut!(b)
+            .zip(cfg_iter!(r_vec))
+            .for_each(|(bi, ri)| *bi = mul!(bi.into_projective(), *ri).into_affine());
+
+        let refa = &a;
+        let refc = &c;
+        let refb_r = &b;
+        let refr_vec = &r_vec;
+        try_par! {
+            // compute A * B^r for the verifier
+            let ip_ab = ip::pairing::<E>(refa, refb_r),
+            // compute C^r for the verifier
+            let agg_c = ip::multiexponentiation::<E::G1Affine>(refc, refr_vec)
+        };
+        // w^{r^{-1}}
+        let wkey = wkey.scale(&r_inv)?;
+        let vectors = Self {
+            a,
+            b,
+            c,
+            d: Vec::new(),
+            r: r_vec,
+            vkey,
+            wkey,
+        };
+        Ok((vectors, ip_ab, agg_c))
+    }
+
+    /// Sets the D points to fold along with C and returns $D^r$, which the
+    /// verifier needs. It must be called on randomized vectors.
+    pub(crate) fn set_d(&mut self, d: Vec<E::G1Affine>) -> Result<E::G1Projective, Error> {
+        if d.len() != self.c.len() {
+            return Err(Error::InvalidProof(
+                "commitments len != number of proofs".to_string(),
+            ));
+        }
+        let agg_d = ip::multiexponentiation::<E::G1Affine>(&d, &self.r)?;
+        self.d = d;
+        Ok(agg_d)
+    }
+}
+
+impl<E: PairingEngine> Folding<E> for GipaVectors<E> {
+    fn len(&self) -> usize {
+        self.a.len()
+    }
+
+    fn cross_terms(&mut self) -> Result<RoundTerms<E>, Error> {
+        // Recurse with problem of half size
+        let split = self.a.len() / 2;
+
+        // TIPP ///
+        let (a_left, a_right) = self.a.split_at(split);
+        let (b_left, b_right) = self.b.split_at(split);
+        // MIPP ///
+        // c[:n']   c[n':]
+        let (c_left, c_right) = self.c.split_at(split);
+        // r[:n']   r[:n']
+        let (r_left, r_right) = self.r.split_at(split);
+
+        // the keys are copied since they are only split during compression
+        let (vk_left, vk_right) = self.vkey.clone().split(split);
+        let (wk_left, wk_right) = self.wkey.clone().split(split);
+
+        // since we do this in parallel we take reference first so it can be
+        // moved within the macro's rayon scope.
+        let (rvk_left, rvk_right) = (&vk_left, &vk_right);
+        let (rwk_left, rwk_right) = (&wk_left, &wk_right);
+        // See section 3.3 for paper version with equivalent names
+        try_par! {
+            // TIPP part
+            let tab_l = commitment::pair::<E>(rvk_left, rwk_right, a_right, b_left),
+            let tab_r = commitment::pair::<E>(rvk_right, rwk_left, a_left, b_right),
+            // \prod e(A_right,B_left)
+            let zab_l = ip::pairing::<E>(a_right, b_left),
+            let zab_r = ip::pairing::<E>(a_left, b_right),
+
+            // MIPP part
+            // z_l = c[n':] ^ r[:n']
+            let zc_l = ip::multiexponentiation::<E::G1Affine>(c_right, r_left),
+            // Z_r = c[:n'] ^ r[n':]
+            let zc_r = ip::multiexponentiation::<E::G1Affine>(c_left, r_right),
+            // u_l = c[n':] * v[:n']
+            let tuc_l = commitment::single_g1::<E>(rvk_left, c_right),
+            // u_r = c[:n'] * v[n':]
+            let tuc_r = commitment::single_g1::<E>(rvk_right, c_left)
+        };
+        // same MIPP values for D if there is one
+        let d = if self.d.is_empty() {
+            None
+        } else {
+            let (d_left, d_right) = self.d.split_at(split);
+            try_par! {
+                let z_l = ip::multiexponentiation::<E::G1Affine>(d_right, r_left),
+                let z_r = ip::multiexponentiation::<E::G1Affine>(d_left, r_right),
+                let tu_l = commitment::single_g1::<E>(rvk_left, d_right),
+                let tu_r = commitment::single_g1::<E>(rvk_right, d_left)
+            };
+            Some(MippTerms {
+                z_l,
+                z_r,
+                tu_l,
+                tu_r,
+            })
+        };
+        Ok(RoundTerms {
+            tab_l,
+            tab_r,
+            zab_l,
+            zab_r,
+            zc_l,
+            zc_r,
+            tuc_l,
+            tuc_r,
+            d,
+        })
+    }
+
+    fn compress(&mut self, c: &E::Fr, c_inv: &E::Fr) -> Result<(), Error> {
+        let split = self.a.len() / 2;
+        // A[:n'] + A[n':] ^ x
+        compress(&mut self.a, split, c);
+        // B[:n'] + B[n':] ^ x^-1
+        compress(&mut self.b, split, c_inv);
+
+        // c[:n'] + c[n':]^x
+        compress(&mut self.c, split, c);
+        if !self.d.is_empty() {
+            compress(&mut self.d, split, c);
+        }
+        let (r_left, r_right) = self.r.split_at_mut(split);
+        cfg_iter_mut!(r_left)
+            .zip(cfg_iter_mut!(r_right))
+            .for_each(|(r_l, r_r)| {
+                // r[:n'] + r[n':]^x^-1
+                r_r.mul_assign(c_inv);
+                r_l.add_assign(*r_r);
+            });
+        self.r.resize(split, E::Fr::zero()); // shrink to new size
+
+        let (vk_left, vk_right) = self.vkey.clone().split(split);
+        let (wk_left, wk_right) = self.wkey.clone().split(split);
+        // v_left + v_right^x^-1
+        self.vkey = vk_left.compress(&vk_right, c_inv)?;
+        // w_left + w_right^x
+        self.wkey = wk_left.compress(&wk_right, c)?;
+        Ok(())
+    }
+
+    fn into_vectors(self) -> Result<GipaVectors<E>, Error> {
+        Ok(self)
+    }
 }
 
 /// Proves a TIPP relation between A and B as well as a MIPP relation with C and
@@ -130,21 +620,35 @@ pub fn aggregate_proofs<E: PairingEngine + std::fmt::Debug, T: Transcript>(
 /// commitment key v is used to commit to A and C recursively in GIPA such that
 /// only one KZG proof is needed for v. In the original paper version, since the
 /// challenges of GIPA would be different, two KZG proofs would be needed.
-fn prove_tipp_mipp<E: PairingEngine, T: Transcript>(
+#[cfg(feature = "std")]
+pub(crate) fn prove_tipp_mipp<E: PairingEngine, T: AggregateTranscript<E>>(
     srs: &ProverSRS<E>,
     transcript: &mut T,
-    a: &[E::G1Affine],
-    b: &[E::G2Affine],
-    c: &[E::G1Affine],
-    wkey: &WKey<E>, // scaled key w^r^-1
-    r_vec: &[E::Fr],
+    vectors: impl Folding<E>,
+    r_shift: E::Fr,
     ip_ab: &E::Fqk,
     agg_c: &E::G1Affine,
 ) -> Result<TippMippProof<E>, Error> {
-    let r_shift = r_vec[1].clone();
+    prove_tipp_mipp_with_d(srs, transcript, vectors, r_shift, ip_ab, agg_c, None)
+        .map(|(proof, _)| proof)
+}
+
+/// Proves the TIPP and MIPP relations as `prove_tipp_mipp` does. If `agg_d` is
+/// given, the vectors hold D as well and its MIPP relation is proven in the
+/// same GIPA loop: the values of D sent in each round are returned.
+#[allow(clippy::type_complexity)]
+fn prove_tipp_mipp_with_d<E: PairingEngine, T: AggregateTranscript<E>>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    vectors: impl Folding<E>,
+    r_shift: E::Fr,
+    ip_ab: &E::Fqk,
+    agg_c: &E::G1Affine,
+    agg_d: Option<&E::G1Affine>,
+) -> Result<(TippMippProof<E>, Option<GipaMippProof<E>>), Error> {
     // Run GIPA
-    let (proof, mut challenges, mut challenges_inv) =
-        gipa_tipp_mipp(transcript, a, b, c, &srs.vkey, &wkey, r_vec, ip_ab, agg_c)?;
+    let (proof, gipa_d, mut challenges, mut challenges_inv) =
+        gipa_tipp_mipp(transcript, vectors, &r_shift, ip_ab, agg_c, agg_d)?;
 
     // Prove final commitment keys are wellformed
     // we reverse the transcript so the polynomial in kzg opening is constructed
@@ -155,12 +659,7 @@ fn prove_tipp_mipp<E: PairingEngine, T: Transcript>(
     let r_inverse = r_shift.inverse().unwrap();
 
     // KZG challenge point
-    transcript.append(b"kzg-challenge", &challenges[0]);
-    transcript.append(b"vkey0", &proof.final_vkey.0);
-    transcript.append(b"vkey1", &proof.final_vkey.1);
-    transcript.append(b"wkey0", &proof.final_wkey.0);
-    transcript.append(b"wkey1", &proof.final_wkey.1);
-    let z = transcript.challenge_scalar::<E::Fr>(b"z-challenge");
+    let z = transcript.kzg_challenge(&challenges[0], &proof);
     // Complete KZG proofs
     par! {
         let vkey_opening = prove_commitment_v(
@@ -178,162 +677,130 @@ fn prove_tipp_mipp<E: PairingEngine, T: Transcript>(
         )
     };
 
-    Ok(TippMippProof {
+    let proof = TippMippProof {
         gipa: proof,
         vkey_opening: vkey_opening?,
         wkey_opening: wkey_opening?,
-    })
+    };
+    Ok((proof, gipa_d))
 }
 
 /// gipa_tipp_mipp peforms the recursion of the GIPA protocol for TIPP and MIPP.
 /// It returns a proof containing all intermdiate committed values, as well as
 /// the challenges generated necessary to do the polynomial commitment proof
-/// later in TIPP.
+/// later in TIPP. The values of D are returned separately if `agg_d` is given.
+#[allow(clippy::type_complexity)]
 fn gipa_tipp_mipp<E: PairingEngine>(
-    transcript: &mut impl Transcript,
-    a: &[E::G1Affine],
-    b: &[E::G2Affine],
-    c: &[E::G1Affine],
-    vkey: &VKey<E>,
-    wkey: &WKey<E>, // scaled key w^r^-1
-    r: &[E::Fr],
+    transcript: &mut impl AggregateTranscript<E>,
+    mut vectors: impl Folding<E>,
+    r_shift: &E::Fr,
     ip_ab: &E::Fqk,
     agg_c: &E::G1Affine,
-) -> Result<(GipaProof<E>, Vec<E::Fr>, Vec<E::Fr>), Error> {
-    // the values of vectors A and B rescaled at each step of the loop
-    let (mut m_a, mut m_b) = (a.to_vec(), b.to_vec());
-    // the values of vectors C and r rescaled at each step of the loop
-    let (mut m_c, mut m_r) = (c.to_vec(), r.to_vec());
-    // the values of the commitment keys rescaled at each step of the loop
-    let (mut vkey, mut wkey) = (vkey.clone(), wkey.clone());
+    agg_d: Option<&E::G1Affine>,
+) -> Result<
+    (
+        GipaProof<E>,
+        Option<GipaMippProof<E>>,
+        Vec<E::Fr>,
+        Vec<E::Fr>,
+    ),
+    Error,
+> {
+    let nproofs = vectors.len() as u32; // TODO: ensure u32
 
     // storing the values for including in the proof
     let mut comms_ab = Vec::new();
     let mut comms_c = Vec::new();
     let mut z_ab = Vec::new();
     let mut z_c = Vec::new();
+    let mut comms_d = Vec::new();
+    let mut z_d = Vec::new();
     let mut challenges: Vec<E::Fr> = Vec::new();
     let mut challenges_inv: Vec<E::Fr> = Vec::new();
 
-    transcript.append(b"inner-product-ab", ip_ab);
-    transcript.append(b"comm-c", agg_c);
-    let mut c_inv: E::Fr = transcript.challenge_scalar::<E::Fr>(b"first-challenge");
-    let mut c = c_inv.inverse().unwrap();
+    let mut c_inv: E::Fr = transcript.first_challenge(ip_ab, agg_c, agg_d, r_shift);
 
     let mut i = 0;
 
-    while m_a.len() > 1 {
+    while vectors.len() > 1 {
         // recursive step
-        // Recurse with problem of half size
-        let split = m_a.len() / 2;
-
-        // TIPP ///
-        let (a_left, a_right) = m_a.split_at_mut(split);
-        let (b_left, b_right) = m_b.split_at_mut(split);
-        // MIPP ///
-        // c[:n']   c[n':]
-        let (c_left, c_right) = m_c.split_at_mut(split);
-        // r[:n']   r[:n']
-        let (r_left, r_right) = m_r.split_at_mut(split);
-
-        let (vk_left, vk_right) = vkey.split(split);
-        let (wk_left, wk_right) = wkey.split(split);
-
-        // since we do this in parallel we take reference first so it can be
-        // moved within the macro's rayon scope.
-        let (rvk_left, rvk_right) = (&vk_left, &vk_right);
-        let (rwk_left, rwk_right) = (&wk_left, &wk_right);
-        let (ra_left, ra_right) = (&a_left, &a_right);
-        let (rb_left, rb_right) = (&b_left, &b_right);
-        let (rc_left, rc_right) = (&c_left, &c_right);
-        let (rr_left, rr_right) = (&r_left, &r_right);
-        // See section 3.3 for paper version with equivalent names
-        try_par! {
-            // TIPP part
-            let tab_l = commitment::pair::<E>(&rvk_left, &rwk_right, &ra_right, &rb_left),
-            let tab_r = commitment::pair::<E>(&rvk_right, &rwk_left, &ra_left, &rb_right),
-            // \prod e(A_right,B_left)
-            let zab_l = ip::pairing::<E>(&ra_right, &rb_left),
-            let zab_r = ip::pairing::<E>(&ra_left, &rb_right),
-
-            // MIPP part
-            // z_l = c[n':] ^ r[:n']
-            let zc_l = ip::multiexponentiation::<E::G1Affine>(rc_right, rr_left),
-            // Z_r = c[:n'] ^ r[n':]
-            let zc_r = ip::multiexponentiation::<E::G1Affine>(rc_left, rr_right),
-            // u_l = c[n':] * v[:n']
-            let tuc_l = commitment::single_g1::<E>(&rvk_left, rc_right),
-            // u_r = c[:n'] * v[n':]
-            let tuc_r = commitment::single_g1::<E>(&rvk_right, rc_left)
-        };
+        let RoundTerms {
+            tab_l,
+            tab_r,
+            zab_l,
+            zab_r,
+            zc_l,
+            zc_r,
+            tuc_l,
+            tuc_r,
+            d,
+        } = vectors.cross_terms()?;
+        comms_ab.push((tab_l, tab_r));
+        comms_c.push((tuc_l, tuc_r));
+        z_ab.push((zab_l, zab_r));
+        z_c.push((zc_l.into_affine(), zc_r.into_affine()));
+        match (agg_d, d) {
+            (Some(_), Some(d)) => {
+                comms_d.push((d.tu_l, d.tu_r));
+                z_d.push((d.z_l.into_affine(), d.z_r.into_affine()));
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Error::InvalidProof(
+                    "commitments len != number of proofs".to_string(),
+                ))
+            }
+        }
 
         // Fiat-Shamir challenge
         // combine both TIPP and MIPP transcript
-        if i == 0 {
-            // already generated c_inv and c outside of the loop
-        } else {
-            transcript.append(b"c_inv", &c_inv);
-            transcript.append(b"zab_l", &zab_l);
-            transcript.append(b"zab_r", &zab_r);
-            transcript.append(b"zc_l", &zc_l);
-            transcript.append(b"zc_r", &zc_r);
-            transcript.append(b"tab_l", &tab_l);
-            transcript.append(b"tab_r", &tab_r);
-            transcript.append(b"tuc_l", &tuc_l);
-            transcript.append(b"tuc_r", &tuc_r);
-            c_inv = transcript.challenge_scalar::<E::Fr>(b"challenge_i");
-
-            // Optimization for multiexponentiation to rescale G2 elements with
-            // 128-bit challenge Swap 'c' and 'c_inv' since can't control bit size
-            // of c_inv
-            c = c_inv.inverse().unwrap();
-        }
+        let round = GipaRound {
+            comms_ab: &comms_ab[i],
+            comms_c: &comms_c[i],
+            z_ab: &z_ab[i],
+            z_c: &z_c[i],
+            d: agg_d.map(|_| (&comms_d[i], &z_d[i])),
+        };
+        c_inv = transcript.round_challenge(i, &c_inv, round);
+        // Optimization for multiexponentiation to rescale G2 elements with
+        // 128-bit challenge Swap 'c' and 'c_inv' since can't control bit size
+        // of c_inv
+        let c = c_inv.inverse().unwrap();
 
         // Set up values for next step of recursion
-        // A[:n'] + A[n':] ^ x
-        compress(&mut m_a, split, &c);
-        // B[:n'] + B[n':] ^ x^-1
-        compress(&mut m_b, split, &c_inv);
-
-        // c[:n'] + c[n':]^x
-        compress(&mut m_c, split, &c);
-        r_left
-            .par_iter_mut()
-            .zip(r_right.par_iter_mut())
-            .for_each(|(r_l, r_r)| {
-                // r[:n'] + r[n':]^x^-1
-                r_r.mul_assign(&c_inv);
-                r_l.add_assign(r_r.clone());
-            });
-        let len = r_left.len();
-        m_r.resize(len, E::Fr::zero()); // shrink to new size
-
-        // v_left + v_right^x^-1
-        vkey = vk_left.compress(&vk_right, &c_inv)?;
-        // w_left + w_right^x
-        wkey = wk_left.compress(&wk_right, &c)?;
+        vectors.compress(&c, &c_inv)?;
 
-        comms_ab.push((tab_l, tab_r));
-        comms_c.push((tuc_l, tuc_r));
-        z_ab.push((zab_l, zab_r));
-        z_c.push((zc_l.into_affine(), zc_r.into_affine()));
         challenges.push(c);
         challenges_inv.push(c_inv);
 
         i += 1;
     }
 
-    assert!(m_a.len() == 1 && m_b.len() == 1);
-    assert!(m_c.len() == 1 && m_r.len() == 1);
-    assert!(vkey.a.len() == 1 && vkey.b.len() == 1);
-    assert!(wkey.a.len() == 1 && wkey.b.len() == 1);
-
-    let (final_a, final_b, final_c) = (m_a[0], m_b[0], m_c[0]);
-    let (final_vkey, final_wkey) = (vkey.first(), wkey.first());
+    let vectors = vectors.into_vectors()?;
+    assert!(vectors.a.len() == 1 && vectors.b.len() == 1);
+    assert!(vectors.c.len() == 1 && vectors.r.len() == 1);
+    assert!(vectors.vkey.a.len() == 1 && vectors.vkey.b.len() == 1);
+    assert!(vectors.wkey.a.len() == 1 && vectors.wkey.b.len() == 1);
+
+    let (final_a, final_b, final_c) = (vectors.a[0], vectors.b[0], vectors.c[0]);
+    let (final_vkey, final_wkey) = (vectors.vkey.first(), vectors.wkey.first());
+    let gipa_d = match agg_d {
+        Some(_) if vectors.d.len() == 1 => Some(GipaMippProof {
+            comms_d,
+            z_d,
+            final_d: vectors.d[0],
+        }),
+        Some(_) => {
+            return Err(Error::InvalidProof(
+                "commitments len != number of proofs".to_string(),
+            ))
+        }
+        None => None,
+    };
 
     Ok((
         GipaProof {
-            nproofs: a.len() as u32, // TODO: ensure u32
+            nproofs,
             comms_ab,
             comms_c,
             z_ab,
@@ -344,6 +811,7 @@ fn gipa_tipp_mipp<E: PairingEngine>(
             final_vkey,
             final_wkey,
         },
+        gipa_d,
         challenges,
         challenges_inv,
     ))
@@ -362,7 +830,7 @@ fn prove_commitment_v<G: AffineCurve>(
 
     // f_v(z)
     let vkey_poly_z = polynomial_evaluation_product_form_from_transcript(
-        &transcript,
+        transcript,
         kzg_challenge,
         &G::ScalarField::one(),
     );
@@ -392,9 +860,9 @@ fn prove_commitment_w<G: AffineCurve>(
 
     par! {
         // this computes f(z)
-        let fz = polynomial_evaluation_product_form_from_transcript(&transcript, kzg_challenge, &r_shift),
+        let fz = polynomial_evaluation_product_form_from_transcript(transcript, kzg_challenge, r_shift),
         // this computes the "shift" z^n
-        let zn = kzg_challenge.pow(&[n as u64])
+        let zn = kzg_challenge.pow([n as u64])
     };
     // this computes f_w(z) by multiplying by zn
     let mut fwz = fz;
@@ -422,14 +890,11 @@ fn create_kzg_opening<G: AffineCurve>(
     neg_kzg_challenge = neg_kzg_challenge.neg();
 
     if poly.coeffs().len() != srs_powers_alpha_table.len() {
-        return Err(Error::InvalidSRS(
-            format!(
-                "SRS len {} != coefficients len {}",
-                srs_powers_alpha_table.len(),
-                poly.coeffs().len(),
-            )
-            .to_string(),
-        ));
+        return Err(Error::InvalidSRS(format!(
+            "SRS len {} != coefficients len {}",
+            srs_powers_alpha_table.len(),
+            poly.coeffs().len(),
+        )));
     }
 
     // f_v(X) - f_v(z) / (X - z)
@@ -455,10 +920,11 @@ fn create_kzg_opening<G: AffineCurve>(
     // on the curve we are on). that's the extra cost of the commitment scheme
     // used which is compatible with Groth16 CRS insteaf of the original paper
     // of Bunz'19
-    let (a, b) = rayon::join(
-        || VariableBaseMSM::multi_scalar_mul(&srs_powers_alpha_table, &quotient_repr),
-        || VariableBaseMSM::multi_scalar_mul(&srs_powers_beta_table, &quotient_repr),
-    );
+    let quotient_repr = &quotient_repr;
+    par! {
+        let a = VariableBaseMSM::multi_scalar_mul(srs_powers_alpha_table, quotient_repr),
+        let b = VariableBaseMSM::multi_scalar_mul(srs_powers_beta_table, quotient_repr)
+    };
     Ok(KZGOpening::new_from_proj(a, b))
 }
 
@@ -478,10 +944,10 @@ pub(super) fn polynomial_evaluation_product_form_from_transcript<F: Field>(
 
     let one = F::one();
 
-    let mut res = one + transcript[0] * &power_zr;
+    let mut res = one + transcript[0] * power_zr;
     for x in &transcript[1..] {
         power_zr = power_zr.square();
-        res.mul_assign(one + *x * &power_zr);
+        res.mul_assign(one + *x * power_zr);
     }
 
     res
@@ -510,7 +976,7 @@ fn polynomial_coefficients_from_transcript<F: Field>(transcript: &[F], r_shift:
             power_2_r = power_2_r.square();
         }
         for j in 0..n {
-            let coeff = coefficients[j] * &(*x * &power_2_r);
+            let coeff = coefficients[j] * (*x * power_2_r);
             coefficients.push(coeff);
         }
     }
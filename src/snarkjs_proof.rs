@@ -1,20 +1,42 @@
 use ark_bn254::Fq6;
-use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
-use ark_ec::PairingEngine;
-use ark_ff::One;
-use ark_groth16::{
-    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof, Proof,
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::rand::Rng;
+
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use crate::{
+    proof::AggregateProof,
+    srs::VerifierSRS,
+    transcript::{new_merlin_transcript, Transcript},
+    verify_aggregate_proof, Error,
 };
 
-use serde::Deserialize;
-use std::str::FromStr;
+/// Label of the transcript used by the snarkjs tooling, i.e. the `snarkpack`
+/// binary and the wasm bindings.
+pub const SNARKJS_TRANSCRIPT_LABEL: &[u8] = b"snarkpack";
+
+/// Returns the transcript used to aggregate and verify snarkjs proofs: a merlin
+/// transcript labeled `SNARKJS_TRANSCRIPT_LABEL` in which the digest of all
+/// public inputs is appended.
+pub fn snarkjs_transcript(public_inputs: &[Vec<ark_bn254::Fr>]) -> impl Transcript {
+    let mut transcript = new_merlin_transcript(SNARKJS_TRANSCRIPT_LABEL);
+    transcript.append_inputs_digest(b"public-inputs-digest", public_inputs);
+    transcript
+}
 
 pub fn fr_from_str(s: String) -> ark_bn254::Fr {
     ark_bn254::Fr::from_str(&s).unwrap()
 }
 
-pub fn fq_from_str(s: &String) -> ark_bn254::Fq {
-    ark_bn254::Fq::from_str(&s).unwrap()
+pub fn fq_from_str(s: &str) -> ark_bn254::Fq {
+    ark_bn254::Fq::from_str(s).unwrap()
 }
 
 pub fn g1_from_str(g1: &[String]) -> ark_bn254::G1Affine {
@@ -40,6 +62,20 @@ pub fn g2_from_str(g2: &[Vec<String>]) -> ark_bn254::G2Affine {
     ark_bn254::G2Affine::from(ark_bn254::G2Projective::new(x, y, z))
 }
 
+/// Parses the `vk_alphabeta_12` of a snarkjs verification key, whose
+/// coefficients are laid out as in Arkworks.
+pub fn fq12_from_str(fq12: &[Vec<Vec<String>>]) -> Result<ark_bn254::Fq12, Error> {
+    match fq12 {
+        [c0, c1] => Ok(ark_bn254::Fq12::new(
+            fq6_from_json(c0).map_err(Error::InvalidVerifyingKey)?,
+            fq6_from_json(c1).map_err(Error::InvalidVerifyingKey)?,
+        )),
+        _ => Err(Error::InvalidVerifyingKey(
+            "expected 2 coefficients in Fq12".to_string(),
+        )),
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SnarkJSProof {
     pub curve: String,
@@ -74,6 +110,37 @@ pub struct SnarkJSVK {
     pub ic: Vec<Vec<String>>,
 }
 
+impl SnarkJSVK {
+    /// Returns the Groth16 verifying key, checking that it is a BN254 key with
+    /// a point per public input and whose points are in the prime order
+    /// subgroups. Unlike the `From` conversion, a malformed key, e.g. read from
+    /// an untrusted source, is an error rather than a panic.
+    pub fn to_verifying_key(&self) -> Result<ark_groth16::VerifyingKey<Bn254>, Error> {
+        if self.curve != "bn128" || self.protocol != "groth16" {
+            return Err(Error::InvalidVerifyingKey(format!(
+                "unsupported {} key on {}",
+                self.protocol, self.curve
+            )));
+        }
+        if usize::try_from(self.n_public).ok().map(|n| n + 1) != Some(self.ic.len()) {
+            return Err(Error::InvalidVerifyingKey(format!(
+                "{} IC points for {} public inputs",
+                self.ic.len(),
+                self.n_public
+            )));
+        }
+        let g1 = |p: &[String]| g1_from_json(p).map_err(Error::InvalidVerifyingKey);
+        let g2 = |p: &[Vec<String>]| g2_from_json(p).map_err(Error::InvalidVerifyingKey);
+        Ok(ark_groth16::VerifyingKey {
+            alpha_g1: g1(&self.vk_alpha_1)?,
+            beta_g2: g2(&self.vk_beta_2)?,
+            gamma_g2: g2(&self.vk_gamma_2)?,
+            delta_g2: g2(&self.vk_delta_2)?,
+            gamma_abc_g1: self.ic.iter().map(|p| g1(p)).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
 impl From<SnarkJSVK> for ark_groth16::VerifyingKey<ark_bn254::Bn254> {
     fn from(src: SnarkJSVK) -> Self {
         let alpha_g1_ = g1_from_str(&src.vk_alpha_1);
@@ -94,7 +161,12 @@ impl From<SnarkJSVK> for ark_groth16::VerifyingKey<ark_bn254::Bn254> {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Arkworks' `G2Prepared` of BN254, which can be written in binary with
+/// `CanonicalSerialize` and in JSON with serde.
+#[derive(
+    Debug, PartialEq, Clone, CanonicalSerialize, CanonicalDeserialize, Serialize, Deserialize,
+)]
+#[serde(into = "G2PreparedJson", try_from = "G2PreparedJson")]
 pub struct G2Prepared {
     pub ell_coeffs: Vec<(Fq2, Fq2, Fq2)>,
     pub infinity: bool,
@@ -116,7 +188,7 @@ impl From<ark_ec::bn::G2Prepared<ark_bn254::Parameters>> for G2Prepared {
             .into_iter()
             .map(|elem| (elem.0, elem.1, elem.2));
         let ell_coeffs: Vec<(Fq2, Fq2, Fq2)> = ark_ell_coeffs
-            .map(|elem| (elem.0.into(), elem.1.into(), elem.2.into()))
+            .map(|elem| (elem.0, elem.1, elem.2))
             .collect();
         G2Prepared::new(ell_coeffs, src.infinity)
     }
@@ -127,7 +199,7 @@ impl From<G2Prepared> for ark_ec::bn::G2Prepared<ark_bn254::Parameters> {
         let ark_ell_coeffs = src
             .ell_coeffs
             .into_iter()
-            .map(|elem| (elem.0.into(), elem.1.into(), elem.2.into()));
+            .map(|elem| (elem.0, elem.1, elem.2));
         ark_ec::bn::G2Prepared {
             ell_coeffs: ark_ell_coeffs
                 .map(|elem| (elem.0, elem.1, elem.2))
@@ -137,7 +209,12 @@ impl From<G2Prepared> for ark_ec::bn::G2Prepared<ark_bn254::Parameters> {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// An element of $F_{q^{12}}$ of BN254, written in JSON as the
+/// `vk_alphabeta_12` of snarkjs.
+#[derive(
+    Debug, PartialEq, Clone, CanonicalSerialize, CanonicalDeserialize, Serialize, Deserialize,
+)]
+#[serde(into = "Fq12Json", try_from = "Fq12Json")]
 pub struct Fq12 {
     pub c0: Fq6,
     pub c1: Fq6,
@@ -151,8 +228,8 @@ impl Fq12 {
 
 impl From<Fq12> for ark_bn254::Fq12 {
     fn from(src: Fq12) -> ark_bn254::Fq12 {
-        let c0: ark_bn254::Fq6 = src.c0.into();
-        let c1: ark_bn254::Fq6 = src.c1.into();
+        let c0: ark_bn254::Fq6 = src.c0;
+        let c1: ark_bn254::Fq6 = src.c1;
         ark_bn254::Fq12::new(c0, c1)
     }
 }
@@ -161,11 +238,16 @@ impl From<ark_bn254::Fq12> for Fq12 {
     fn from(src: ark_bn254::Fq12) -> Fq12 {
         let c0: ark_bn254::Fq6 = src.c0;
         let c1: ark_bn254::Fq6 = src.c1;
-        Fq12::new(c0.into(), c1.into())
+        Fq12::new(c0, c1)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// A Groth16 verifying key of BN254, whose points are written in JSON in the
+/// projective form of snarkjs.
+#[derive(
+    Debug, PartialEq, Clone, CanonicalSerialize, CanonicalDeserialize, Serialize, Deserialize,
+)]
+#[serde(into = "VerifyingKeyJson", try_from = "VerifyingKeyJson")]
 pub struct VerifyingKey {
     pub alpha_g1: G1Affine,
     pub beta_g2: G2Affine,
@@ -177,15 +259,11 @@ pub struct VerifyingKey {
 impl From<VerifyingKey> for ark_groth16::VerifyingKey<ark_bn254::Bn254> {
     fn from(src: VerifyingKey) -> ark_groth16::VerifyingKey<ark_bn254::Bn254> {
         ark_groth16::VerifyingKey {
-            alpha_g1: src.alpha_g1.into(),
-            beta_g2: src.beta_g2.into(),
-            gamma_g2: src.gamma_g2.into(),
-            delta_g2: src.delta_g2.into(),
-            gamma_abc_g1: src
-                .gamma_abc_g1
-                .into_iter()
-                .map(|elem| elem.into())
-                .collect(),
+            alpha_g1: src.alpha_g1,
+            beta_g2: src.beta_g2,
+            gamma_g2: src.gamma_g2,
+            delta_g2: src.delta_g2,
+            gamma_abc_g1: src.gamma_abc_g1.into_iter().collect(),
         }
     }
 }
@@ -193,20 +271,21 @@ impl From<VerifyingKey> for ark_groth16::VerifyingKey<ark_bn254::Bn254> {
 impl From<ark_groth16::VerifyingKey<ark_bn254::Bn254>> for VerifyingKey {
     fn from(src: ark_groth16::VerifyingKey<ark_bn254::Bn254>) -> VerifyingKey {
         VerifyingKey {
-            alpha_g1: src.alpha_g1.into(),
-            beta_g2: src.beta_g2.into(),
-            gamma_g2: src.gamma_g2.into(),
-            delta_g2: src.delta_g2.into(),
-            gamma_abc_g1: src
-                .gamma_abc_g1
-                .into_iter()
-                .map(|elem| elem.into())
-                .collect(),
+            alpha_g1: src.alpha_g1,
+            beta_g2: src.beta_g2,
+            gamma_g2: src.gamma_g2,
+            delta_g2: src.delta_g2,
+            gamma_abc_g1: src.gamma_abc_g1.into_iter().collect(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// A prepared Groth16 verifying key of BN254, which can be cached in binary
+/// with `CanonicalSerialize` or in JSON with serde, and given to
+/// `verify_aggregate_proof_with_cached_key`.
+#[derive(
+    Debug, PartialEq, Clone, CanonicalSerialize, CanonicalDeserialize, Serialize, Deserialize,
+)]
 pub struct PreparedVerifyingKey {
     pub vk: VerifyingKey,
     pub alpha_g1_beta_g2: Fq12,
@@ -236,7 +315,205 @@ impl From<ark_groth16::PreparedVerifyingKey<ark_bn254::Bn254>> for PreparedVerif
     }
 }
 
-pub fn get_prepared_verifying_key(vkey: SnarkJSVK) -> PreparedVerifyingKey {
-    let parse_vkey: ark_groth16::VerifyingKey<ark_bn254::Bn254> = vkey.into();
-    ark_groth16::prepare_verifying_key(&parse_vkey).into()
+/// Returns the prepared verifying key of a snarkjs verification key. Its
+/// `vk_alphabeta_12` is taken as $e(\alpha,\beta)$ rather than recomputed,
+/// see `AlphaBeta::Precomputed`.
+pub fn get_prepared_verifying_key(vkey: SnarkJSVK) -> Result<PreparedVerifyingKey, Error> {
+    let vk = vkey.to_verifying_key()?;
+    if vkey.vk_alphabeta_12.is_empty() {
+        return Ok(ark_groth16::prepare_verifying_key(&vk).into());
+    }
+    let alpha_g1_beta_g2 = fq12_from_str(&vkey.vk_alphabeta_12)?;
+    Ok(ark_groth16::PreparedVerifyingKey {
+        alpha_g1_beta_g2,
+        gamma_g2_neg_pc: (-vk.gamma_g2).into(),
+        delta_g2_neg_pc: (-vk.delta_g2).into(),
+        vk,
+    }
+    .into())
+}
+
+/// Same as `verify_aggregate_proof` with a prepared verifying key cached with
+/// `CanonicalSerialize` or serde, which saves the pairing of
+/// `get_prepared_verifying_key`.
+pub fn verify_aggregate_proof_with_cached_key<R: Rng + Send, T: Transcript + Send>(
+    ip_verifier_srs: &VerifierSRS<Bn254>,
+    pvk: &PreparedVerifyingKey,
+    public_inputs: &[Vec<Fr>],
+    proof: &AggregateProof<Bn254>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let pvk: ark_groth16::PreparedVerifyingKey<Bn254> = pvk.clone().into();
+    verify_aggregate_proof(ip_verifier_srs, &pvk, public_inputs, proof, rng, transcript)
+}
+
+// The JSON form of the mirror types uses the decimal strings of snarkjs. Unlike
+// the `*_from_str` functions, the values are checked when read: field elements
+// must be canonical and points in the prime order subgroup.
+#[derive(Serialize, Deserialize)]
+struct VerifyingKeyJson {
+    alpha_g1: Vec<String>,
+    beta_g2: Vec<Vec<String>>,
+    gamma_g2: Vec<Vec<String>>,
+    delta_g2: Vec<Vec<String>>,
+    gamma_abc_g1: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct G2PreparedJson {
+    ell_coeffs: Vec<Vec<Vec<String>>>,
+    infinity: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+struct Fq12Json(Vec<Vec<Vec<String>>>);
+
+impl From<VerifyingKey> for VerifyingKeyJson {
+    fn from(vk: VerifyingKey) -> Self {
+        VerifyingKeyJson {
+            alpha_g1: g1_to_json(&vk.alpha_g1),
+            beta_g2: g2_to_json(&vk.beta_g2),
+            gamma_g2: g2_to_json(&vk.gamma_g2),
+            delta_g2: g2_to_json(&vk.delta_g2),
+            gamma_abc_g1: vk.gamma_abc_g1.iter().map(g1_to_json).collect(),
+        }
+    }
+}
+
+impl TryFrom<VerifyingKeyJson> for VerifyingKey {
+    type Error = String;
+
+    fn try_from(vk: VerifyingKeyJson) -> Result<Self, String> {
+        Ok(VerifyingKey {
+            alpha_g1: g1_from_json(&vk.alpha_g1)?,
+            beta_g2: g2_from_json(&vk.beta_g2)?,
+            gamma_g2: g2_from_json(&vk.gamma_g2)?,
+            delta_g2: g2_from_json(&vk.delta_g2)?,
+            gamma_abc_g1: vk
+                .gamma_abc_g1
+                .iter()
+                .map(|p| g1_from_json(p))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<G2Prepared> for G2PreparedJson {
+    fn from(p: G2Prepared) -> Self {
+        G2PreparedJson {
+            ell_coeffs: p
+                .ell_coeffs
+                .iter()
+                .map(|(a, b, c)| vec![fq2_to_json(a), fq2_to_json(b), fq2_to_json(c)])
+                .collect(),
+            infinity: p.infinity,
+        }
+    }
+}
+
+impl TryFrom<G2PreparedJson> for G2Prepared {
+    type Error = String;
+
+    fn try_from(p: G2PreparedJson) -> Result<Self, String> {
+        let ell_coeffs = p
+            .ell_coeffs
+            .iter()
+            .map(|coeffs| match &coeffs[..] {
+                [a, b, c] => Ok((fq2_from_json(a)?, fq2_from_json(b)?, fq2_from_json(c)?)),
+                _ => Err("expected 3 coefficients per line".to_string()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(G2Prepared::new(ell_coeffs, p.infinity))
+    }
+}
+
+impl From<Fq12> for Fq12Json {
+    fn from(f: Fq12) -> Self {
+        Fq12Json(vec![fq6_to_json(&f.c0), fq6_to_json(&f.c1)])
+    }
+}
+
+impl TryFrom<Fq12Json> for Fq12 {
+    type Error = String;
+
+    fn try_from(f: Fq12Json) -> Result<Self, String> {
+        match &f.0[..] {
+            [c0, c1] => Ok(Fq12::new(fq6_from_json(c0)?, fq6_from_json(c1)?)),
+            _ => Err("expected 2 coefficients in Fq12".to_string()),
+        }
+    }
+}
+
+fn fq_to_json(f: &Fq) -> String {
+    num_bigint::BigUint::from_bytes_le(&f.into_repr().to_bytes_le()).to_string()
+}
+
+fn fq_from_json(s: &str) -> Result<Fq, String> {
+    // from_str reduces the values larger than the modulus
+    match Fq::from_str(s) {
+        Ok(f) if fq_to_json(&f) == s => Ok(f),
+        _ => Err(format!("invalid field element {}", s)),
+    }
+}
+
+fn fq2_to_json(f: &Fq2) -> Vec<String> {
+    vec![fq_to_json(&f.c0), fq_to_json(&f.c1)]
+}
+
+fn fq2_from_json(s: &[String]) -> Result<Fq2, String> {
+    match s {
+        [c0, c1] => Ok(Fq2::new(fq_from_json(c0)?, fq_from_json(c1)?)),
+        _ => Err("expected 2 coefficients in Fq2".to_string()),
+    }
+}
+
+fn fq6_to_json(f: &Fq6) -> Vec<Vec<String>> {
+    vec![fq2_to_json(&f.c0), fq2_to_json(&f.c1), fq2_to_json(&f.c2)]
+}
+
+fn fq6_from_json(s: &[Vec<String>]) -> Result<Fq6, String> {
+    match s {
+        [c0, c1, c2] => Ok(Fq6::new(
+            fq2_from_json(c0)?,
+            fq2_from_json(c1)?,
+            fq2_from_json(c2)?,
+        )),
+        _ => Err("expected 3 coefficients in Fq6".to_string()),
+    }
+}
+
+fn g1_to_json(p: &G1Affine) -> Vec<String> {
+    let p = p.into_projective();
+    vec![fq_to_json(&p.x), fq_to_json(&p.y), fq_to_json(&p.z)]
+}
+
+fn g1_from_json(s: &[String]) -> Result<G1Affine, String> {
+    let p = match s {
+        [x, y, z] => G1Projective::new(fq_from_json(x)?, fq_from_json(y)?, fq_from_json(z)?),
+        _ => return Err("expected 3 coordinates in G1".to_string()),
+    }
+    .into_affine();
+    match p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve() {
+        true => Ok(p),
+        false => Err("invalid G1 point".to_string()),
+    }
+}
+
+fn g2_to_json(p: &G2Affine) -> Vec<Vec<String>> {
+    let p = p.into_projective();
+    vec![fq2_to_json(&p.x), fq2_to_json(&p.y), fq2_to_json(&p.z)]
+}
+
+fn g2_from_json(s: &[Vec<String>]) -> Result<G2Affine, String> {
+    let p = match s {
+        [x, y, z] => G2Projective::new(fq2_from_json(x)?, fq2_from_json(y)?, fq2_from_json(z)?),
+        _ => return Err("expected 3 coordinates in G2".to_string()),
+    }
+    .into_affine();
+    match p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve() {
+        true => Ok(p),
+        false => Err("invalid G2 point".to_string()),
+    }
 }
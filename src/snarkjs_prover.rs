@@ -0,0 +1,141 @@
+//! Groth16 prover for the circuits of circom, from a proving key read with
+//! `read_zkey` and a witness read with `read_witness`.
+//!
+//! snarkjs does not compute the quotient polynomial as Arkworks does: it
+//! evaluates it on the coset of the domain shifted by a $2n$-th root of unity,
+//! and its H query is the one of these evaluations, see `write_zkey`. The
+//! constraint matrices are the ones of the zkey, the C matrix is not needed
+//! since the witness satisfies the constraints.
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine, G2Projective};
+use ark_ec::{msm::VariableBaseMSM, AffineCurve, ProjectiveCurve};
+use ark_ff::{One, PrimeField, UniformRand, Zero};
+use ark_groth16::{Proof, ProvingKey};
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use ark_relations::r1cs::ConstraintMatrices;
+use ark_std::{cfg_into_iter, cfg_iter, cfg_iter_mut, rand::Rng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{snarkjs_zkey::circom_h_query, Error};
+
+/// Creates a Groth16 proof for the given witness, whose first element is the
+/// constant one followed by the public inputs of the circuit, as returned by
+/// `read_witness`. The proving key can be read from a zkey or generated by
+/// Arkworks, in which case its H query is converted first.
+pub fn create_random_proof_from_witness<R: Rng>(
+    pk: &ProvingKey<Bn254>,
+    matrices: &ConstraintMatrices<Fr>,
+    witness: &[Fr],
+    rng: &mut R,
+) -> Result<Proof<Bn254>, Error> {
+    let num_inputs = matrices.num_instance_variables;
+    if witness.len() != num_inputs + matrices.num_witness_variables {
+        return Err(Error::InvalidWitness(format!(
+            "{} values, expected {}",
+            witness.len(),
+            num_inputs + matrices.num_witness_variables
+        )));
+    }
+    if !witness[0].is_one() {
+        return Err(Error::InvalidWitness("first value is not one".to_string()));
+    }
+    let well_formed = pk.a_query.len() == witness.len()
+        && pk.b_g1_query.len() == witness.len()
+        && pk.b_g2_query.len() == witness.len()
+        && pk.l_query.len() == matrices.num_witness_variables
+        && matrices.a.len() == matrices.num_constraints
+        && matrices.b.len() == matrices.num_constraints;
+    if !well_formed {
+        return Err(Error::InvalidZKey(
+            "proving key does not match the constraints".to_string(),
+        ));
+    }
+
+    let h = witness_map(matrices, witness)?;
+    let converted;
+    let h_query = match pk.h_query.len() {
+        n if n == h.len() => &pk.h_query,
+        n if n + 1 == h.len() => {
+            converted = circom_h_query(&pk.h_query, h.len());
+            &converted
+        }
+        _ => {
+            return Err(Error::InvalidZKey(
+                "H query does not match the domain size".to_string(),
+            ))
+        }
+    };
+
+    let r = Fr::rand(rng);
+    let s = Fr::rand(rng);
+    let msm = |bases: &[G1Affine], scalars: &[Fr]| {
+        let scalars = cfg_iter!(scalars)
+            .map(|s| s.into_repr())
+            .collect::<Vec<_>>();
+        VariableBaseMSM::multi_scalar_mul(bases, &scalars)
+    };
+    let assignment = cfg_iter!(witness)
+        .map(|s| s.into_repr())
+        .collect::<Vec<_>>();
+
+    // the queries of A and B include the constant one and the public inputs
+    let g_a = msm(&pk.a_query, witness) + pk.vk.alpha_g1.into_projective() + pk.delta_g1.mul(r);
+    let g1_b = msm(&pk.b_g1_query, witness) + pk.beta_g1.into_projective() + pk.delta_g1.mul(s);
+    let g2_b: G2Projective = VariableBaseMSM::multi_scalar_mul(&pk.b_g2_query, &assignment)
+        + pk.vk.beta_g2.into_projective()
+        + pk.vk.delta_g2.mul(s);
+
+    let h_acc = msm(h_query, &h);
+    let l_acc = msm(&pk.l_query, &witness[num_inputs..]);
+    let g_c =
+        g_a.mul(s.into_repr()) + g1_b.mul(r.into_repr()) - pk.delta_g1.mul(r * s) + l_acc + h_acc;
+
+    Ok(Proof {
+        a: g_a.into_affine(),
+        b: G2Affine::from(g2_b),
+        c: g_c.into_affine(),
+    })
+}
+
+/// Returns the evaluations of $A B - C$ on the coset $g \omega^i$ of the
+/// domain, with $g$ a $2n$-th root of unity, as the CircomReduction of snarkjs.
+fn witness_map(matrices: &ConstraintMatrices<Fr>, witness: &[Fr]) -> Result<Vec<Fr>, Error> {
+    let num_constraints = matrices.num_constraints;
+    let num_inputs = matrices.num_instance_variables;
+    let domain = Radix2EvaluationDomain::<Fr>::new(num_constraints + num_inputs)
+        .ok_or_else(|| Error::InvalidZKey("too many constraints".to_string()))?;
+    let size = domain.size();
+    let g = Radix2EvaluationDomain::<Fr>::new(2 * size)
+        .ok_or_else(|| Error::InvalidZKey("too many constraints".to_string()))?
+        .element(1);
+
+    let evaluate = |lc: &[(Fr, usize)]| {
+        lc.iter()
+            .map(|(coeff, i)| witness.get(*i).map(|w| *coeff * w))
+            .sum::<Option<Fr>>()
+            .ok_or_else(|| Error::InvalidZKey("constraint on an unknown signal".to_string()))
+    };
+    let mut a = vec![Fr::zero(); size];
+    let mut b = vec![Fr::zero(); size];
+    for (i, (lc_a, lc_b)) in matrices.a.iter().zip(matrices.b.iter()).enumerate() {
+        a[i] = evaluate(lc_a)?;
+        b[i] = evaluate(lc_b)?;
+    }
+    // the constraints of the public inputs
+    a[num_constraints..num_constraints + num_inputs].copy_from_slice(&witness[..num_inputs]);
+    let mut c = cfg_iter!(a)
+        .zip(cfg_iter!(b))
+        .map(|(a, b)| *a * b)
+        .collect::<Vec<_>>();
+
+    for evals in [&mut a, &mut b, &mut c] {
+        domain.ifft_in_place(evals);
+        Radix2EvaluationDomain::distribute_powers(evals, g);
+        domain.fft_in_place(evals);
+    }
+    cfg_iter_mut!(a)
+        .zip(cfg_into_iter!(b))
+        .zip(cfg_into_iter!(c))
+        .for_each(|((a, b), c)| *a = *a * b - c);
+    Ok(a)
+}
@@ -1,4 +1,4 @@
-//! ZKey Parsing
+//! ZKey Parsing and Writing
 //!
 //! Each ZKey file is broken into sections:
 //!  Header(1)
@@ -25,83 +25,401 @@
 //!  PointsC(8)
 //!  PointsH(9)
 //!  Contributions(10)
-use ark_ff::{BigInteger256, PrimeField};
-use ark_relations::r1cs::ConstraintMatrices;
-use ark_serialize::{CanonicalDeserialize, SerializationError};
+//!
+//! The C matrix of the constraints is not part of the zkey. It is read from
+//! the r1cs file written by circom, whose sections are:
+//!  Header(1)
+//!       n8, prime, NWires, NPubOut, NPubIn, NPrvIn, NLabels, MConstraints
+//!  Constraints(2)
+//!       for each constraint, the terms (wire, coefficient) of A, B and C
+//!  Wire2LabelId(3)
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger256, Field, FpParameters, One, PrimeField};
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use ark_relations::r1cs::{ConstraintMatrices, Matrix};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::log2;
 use byteorder::{LittleEndian, ReadBytesExt};
 
 use std::{
     collections::HashMap,
-    io::{Read, Seek, SeekFrom},
+    io::{Read, Seek, SeekFrom, Write},
+    str::FromStr,
 };
 
-use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_bn254::{
+    Bn254, Fq, Fq12, Fq2, Fq6, FqParameters, Fr, FrParameters, G1Affine, G1Projective, G2Affine,
+    G2Projective,
+};
 use ark_groth16::{ProvingKey, VerifyingKey};
 use num_traits::Zero;
 
+use crate::{Error, SnarkJSVK};
+
 type IoResult<T> = Result<T, SerializationError>;
 
+/// Size of a BN254 base or scalar field element in the zkey and r1cs files.
+const FIELD_SIZE: usize = 32;
+const G1_SIZE: usize = 2 * FIELD_SIZE;
+const G2_SIZE: usize = 4 * FIELD_SIZE;
+
 #[derive(Clone, Debug)]
 struct Section {
     position: u64,
-    #[allow(dead_code)]
     size: usize,
 }
 
-/// Reads a SnarkJS ZKey file into an Arkworks ProvingKey.
+/// Reads a SnarkJS ZKey file into an Arkworks ProvingKey. The C matrix is
+/// left empty since it is not stored in the zkey, see `ZKey::read_with_r1cs`.
 pub fn read_zkey<R: Read + Seek>(
     reader: &mut R,
-) -> IoResult<(ProvingKey<Bn254>, ConstraintMatrices<Fr>)> {
-    let mut binfile = BinFile::new(reader)?;
-    let proving_key = binfile.proving_key()?;
-    let matrices = binfile.matrices()?;
+) -> Result<(ProvingKey<Bn254>, ConstraintMatrices<Fr>), Error> {
+    let mut binfile = BinFile::new(reader, b"zkey", &[1])?;
+    let header = binfile.header()?;
+    let proving_key = binfile.proving_key(&header)?;
+    let matrices = binfile.matrices(&header)?;
     Ok((proving_key, matrices))
 }
 
+/// Reads a circom r1cs file into Arkworks constraint matrices. The extra
+/// constraints which Arkworks and snarkjs add for the public inputs are not
+/// included.
+pub fn read_r1cs<R: Read + Seek>(reader: &mut R) -> Result<ConstraintMatrices<Fr>, Error> {
+    let mut binfile = BinFile::new(reader, b"r1cs", &[1])?;
+    binfile.r1cs_matrices()
+}
+
+/// Writes a proving key and the constraint matrices of its circuit, e.g. set
+/// up with `generate_random_parameters`, into a SnarkJS ZKey file without any
+/// phase 2 contribution. The C matrix is not written.
+///
+/// The H query of Arkworks is made of the powers of $\tau$ while snarkjs
+/// evaluates the quotient polynomial on a coset of the domain, hence the
+/// Arkworks H query, with one element less than the domain, is converted to
+/// the Lagrange basis of this coset. Proving keys read from a zkey are written
+/// as they are.
+pub fn write_zkey<W: Write>(
+    writer: &mut W,
+    pk: &ProvingKey<Bn254>,
+    matrices: &ConstraintMatrices<Fr>,
+) -> Result<(), Error> {
+    let mpc = MpcParams {
+        cs_hash: vec![0u8; 64],
+        contributions: vec![],
+    };
+    write_sections(writer, pk, matrices, &mpc)
+}
+
+/// Reads a witness file written by the witness calculators of circom. The
+/// first element of the witness is the constant one, followed by the public
+/// inputs of the circuit.
+pub fn read_witness<R: Read + Seek>(reader: &mut R) -> Result<Vec<Fr>, Error> {
+    let witness = BinFile::new(reader, b"wtns", &[1, 2]).and_then(|mut f| f.witness());
+    witness.map_err(|e| match e {
+        Error::InvalidZKey(msg) => Error::InvalidWitness(msg),
+        e => e,
+    })
+}
+
+/// ZKey holds all the content of a SnarkJS ZKey file.
+#[derive(Clone, Debug)]
+pub struct ZKey {
+    pub header: ZKeyHeader,
+    pub proving_key: ProvingKey<Bn254>,
+    /// The constraint matrices of the circuit. The C matrix is only filled
+    /// when read with `read_with_r1cs` or completed with `complete_matrices`.
+    pub matrices: ConstraintMatrices<Fr>,
+    /// The history of the contributions to the phase 2 ceremony.
+    pub mpc: MpcParams,
+}
+
+impl ZKey {
+    /// Reads all the sections of a zkey file.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let mut binfile = BinFile::new(reader, b"zkey", &[1])?;
+        let header = binfile.header()?;
+        let proving_key = binfile.proving_key(&header)?;
+        let matrices = binfile.matrices(&header)?;
+        let mpc = binfile.mpc_params()?;
+        Ok(Self {
+            header,
+            proving_key,
+            matrices,
+            mpc,
+        })
+    }
+
+    /// Writes all the sections of a zkey file. The header is derived from the
+    /// proving key and the matrices, see `write_zkey`.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_sections(writer, &self.proving_key, &self.matrices, &self.mpc)
+    }
+
+    /// Reads a zkey file and the r1cs file of its circuit, from which the C
+    /// matrix is taken.
+    pub fn read_with_r1cs<R: Read + Seek, S: Read + Seek>(
+        reader: &mut R,
+        r1cs: &mut S,
+    ) -> Result<Self, Error> {
+        let mut zkey = Self::read(reader)?;
+        zkey.complete_matrices(read_r1cs(r1cs)?)?;
+        Ok(zkey)
+    }
+
+    /// Fills the C matrix with the one of `r1cs`, after checking its A and B
+    /// matrices are the ones of the zkey.
+    pub fn complete_matrices(&mut self, r1cs: ConstraintMatrices<Fr>) -> Result<(), Error> {
+        let m = &self.matrices;
+        let same_shape = m.num_instance_variables == r1cs.num_instance_variables
+            && m.num_witness_variables == r1cs.num_witness_variables
+            && m.num_constraints == r1cs.num_constraints;
+        if !same_shape {
+            return Err(invalid("r1cs dimensions do not match the zkey"));
+        }
+        if !same_matrix(&m.a, &r1cs.a) || !same_matrix(&m.b, &r1cs.b) {
+            return Err(invalid("r1cs constraints do not match the zkey"));
+        }
+        self.matrices.c_num_non_zero = r1cs.c_num_non_zero;
+        self.matrices.c = r1cs.c;
+        Ok(())
+    }
+}
+
+impl ZKey {
+    /// Checks that `vk`, read from the `verification_key.json` exported by
+    /// snarkjs, is the verifying key of this zkey. Every field which differs
+    /// is reported, including `vk_alphabeta_12` which is compared with the
+    /// pairing of $\alpha$ and $\beta$ of the zkey.
+    pub fn check_verification_key(&self, vk: &SnarkJSVK) -> Result<(), Error> {
+        let mut mismatches = Vec::new();
+        let zvk = &self.proving_key.vk;
+        if vk.curve != "bn128" {
+            mismatches.push(format!("curve is {}, expected bn128", vk.curve));
+        }
+        if vk.protocol != "groth16" {
+            mismatches.push(format!("protocol is {}, expected groth16", vk.protocol));
+        }
+        if vk.n_public < 0 || vk.n_public as usize != self.header.n_public {
+            mismatches.push(format!(
+                "nPublic is {}, expected {}",
+                vk.n_public, self.header.n_public
+            ));
+        }
+        if parse_g1(&vk.vk_alpha_1) != Some(zvk.alpha_g1) {
+            mismatches.push("vk_alpha_1 differs".to_string());
+        }
+        let g2_fields = [
+            ("vk_beta_2", &vk.vk_beta_2, zvk.beta_g2),
+            ("vk_gamma_2", &vk.vk_gamma_2, zvk.gamma_g2),
+            ("vk_delta_2", &vk.vk_delta_2, zvk.delta_g2),
+        ];
+        for (name, point, expected) in g2_fields {
+            if parse_g2(point) != Some(expected) {
+                mismatches.push(format!("{} differs", name));
+            }
+        }
+        let alphabeta = Bn254::pairing(zvk.alpha_g1, zvk.beta_g2);
+        if parse_fq12(&vk.vk_alphabeta_12) != Some(alphabeta) {
+            mismatches.push("vk_alphabeta_12 differs".to_string());
+        }
+        if vk.ic.len() != self.header.n_public + 1 {
+            mismatches.push(format!(
+                "IC has {} points, expected {}",
+                vk.ic.len(),
+                self.header.n_public + 1
+            ));
+        }
+        for (i, (point, expected)) in vk.ic.iter().zip(&zvk.gamma_abc_g1).enumerate() {
+            if parse_g1(point) != Some(*expected) {
+                mismatches.push(format!("IC[{}] differs", i));
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::VerificationKeyMismatch(mismatches))
+        }
+    }
+}
+
+/// ZKeyHeader contains the metadata of the headers of a zkey file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZKeyHeader {
+    /// Version of the zkey file format.
+    pub version: u32,
+    /// Proving system, 1 for Groth16.
+    pub protocol: u32,
+    /// Size in bytes of a base field element.
+    pub n8q: u32,
+    /// Base field modulus.
+    pub q: BigInteger256,
+    /// Size in bytes of a scalar field element.
+    pub n8r: u32,
+    /// Scalar field modulus.
+    pub r: BigInteger256,
+    /// Number of signals, including the constant one.
+    pub n_vars: usize,
+    /// Number of public signals, outputs and public inputs.
+    pub n_public: usize,
+    /// Size of the evaluation domain of the constraints.
+    pub domain_size: u32,
+    /// Logarithm of `domain_size`.
+    pub power: u32,
+}
+
+/// MpcParams is the Contributions section of a zkey file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MpcParams {
+    /// BLAKE2b hash of the circuit computed by snarkjs, which the
+    /// contributions are chained from.
+    pub cs_hash: Vec<u8>,
+    pub contributions: Vec<Contribution>,
+}
+
+/// Contribution is one contribution to the phase 2 ceremony.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contribution {
+    /// $\delta$ in $G_1$ after this contribution.
+    pub delta_after: G1Affine,
+    /// The contributed key: $g_1^s$, $g_1^{s x}$ and $g_2^{s x}$ with $x$
+    /// the contributed factor of $\delta$, proving the knowledge of $x$.
+    pub g1_s: G1Affine,
+    pub g1_sx: G1Affine,
+    pub g2_spx: G2Affine,
+    /// Hash of the ceremony transcript the key is derived from.
+    pub transcript: Vec<u8>,
+    /// 0 for a contribution, 1 for a random beacon.
+    pub contribution_type: u32,
+    pub name: Option<String>,
+    /// Logarithm of the number of hash iterations of a beacon.
+    pub num_iterations_exp: Option<u8>,
+    pub beacon_hash: Option<Vec<u8>>,
+}
+
 #[derive(Debug)]
 struct BinFile<'a, R> {
-    #[allow(dead_code)]
-    ftype: String,
-    #[allow(dead_code)]
     version: u32,
     sections: HashMap<u32, Vec<Section>>,
     reader: &'a mut R,
 }
 
 impl<'a, R: Read + Seek> BinFile<'a, R> {
-    fn new(reader: &'a mut R) -> IoResult<Self> {
+    fn new(reader: &'a mut R, ftype: &[u8; 4], versions: &[u32]) -> Result<Self, Error> {
+        // the sections are checked to fit in the file, which bounds the
+        // allocations made from the sizes read in the file
+        let start = reader.stream_position().map_err(SerializationError::from)?;
+        let end = reader
+            .seek(SeekFrom::End(0))
+            .map_err(SerializationError::from)?;
+        reader
+            .seek(SeekFrom::Start(start))
+            .map_err(SerializationError::from)?;
+
         let mut magic = [0u8; 4];
-        reader.read_exact(&mut magic)?;
+        reader
+            .read_exact(&mut magic)
+            .map_err(SerializationError::from)?;
+        if &magic != ftype {
+            return Err(invalid(format!(
+                "not a {} file",
+                String::from_utf8_lossy(ftype)
+            )));
+        }
 
-        let version = reader.read_u32::<LittleEndian>()?;
+        let version = reader
+            .read_u32::<LittleEndian>()
+            .map_err(SerializationError::from)?;
+        if !versions.contains(&version) {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
 
-        let num_sections = reader.read_u32::<LittleEndian>()?;
+        let num_sections = reader
+            .read_u32::<LittleEndian>()
+            .map_err(SerializationError::from)?;
 
         let mut sections = HashMap::new();
         for _ in 0..num_sections {
-            let section_id = reader.read_u32::<LittleEndian>()?;
-            let section_length = reader.read_u64::<LittleEndian>()?;
+            let section_id = reader
+                .read_u32::<LittleEndian>()
+                .map_err(SerializationError::from)?;
+            let section_length = reader
+                .read_u64::<LittleEndian>()
+                .map_err(SerializationError::from)?;
+
+            let position = reader.stream_position().map_err(SerializationError::from)?;
+            if section_length > end.saturating_sub(position) {
+                return Err(invalid(format!("section {} exceeds the file", section_id)));
+            }
 
             let section = sections.entry(section_id).or_insert_with(Vec::new);
             section.push(Section {
-                position: reader.stream_position()?,
+                position,
                 size: section_length as usize,
             });
 
-            reader.seek(SeekFrom::Current(section_length as i64))?;
+            reader
+                .seek(SeekFrom::Current(section_length as i64))
+                .map_err(SerializationError::from)?;
         }
 
         Ok(Self {
-            ftype: std::str::from_utf8(&magic[..]).unwrap().to_string(),
             version,
             sections,
             reader,
         })
     }
 
-    fn proving_key(&mut self) -> IoResult<ProvingKey<Bn254>> {
-        let header = self.groth_header()?;
+    /// Returns the section `id`, which must appear exactly once in the file.
+    fn get_section(&self, id: u32) -> Result<Section, Error> {
+        match self.sections.get(&id).map(|s| s.as_slice()) {
+            Some([section]) => Ok(section.clone()),
+            Some(_) => Err(invalid(format!("duplicated section {}", id))),
+            None => Err(invalid(format!("missing section {}", id))),
+        }
+    }
+
+    /// Moves the reader to the start of section `id`, checking its size is
+    /// `size` if given.
+    fn start_section(&mut self, id: u32, size: Option<usize>) -> Result<(), Error> {
+        let section = self.get_section(id)?;
+        if let Some(size) = size {
+            if section.size != size {
+                return Err(invalid(format!(
+                    "section {} has {} bytes, expected {}",
+                    id, section.size, size
+                )));
+            }
+        }
+        self.reader
+            .seek(SeekFrom::Start(section.position))
+            .map_err(SerializationError::from)?;
+        Ok(())
+    }
+
+    fn header(&mut self) -> Result<ZKeyHeader, Error> {
+        self.start_section(1, Some(4))?;
+        let protocol = u32::deserialize_uncompressed(&mut self.reader)?;
+        if protocol != 1 {
+            return Err(invalid(format!("unsupported protocol {}", protocol)));
+        }
+        let groth = self.groth_header()?;
+        Ok(ZKeyHeader {
+            version: self.version,
+            protocol,
+            n8q: groth.n8q,
+            q: groth.q,
+            n8r: groth.n8r,
+            r: groth.r,
+            n_vars: groth.n_vars,
+            n_public: groth.n_public,
+            domain_size: groth.domain_size,
+            power: groth.power,
+        })
+    }
+
+    fn proving_key(&mut self, header: &ZKeyHeader) -> Result<ProvingKey<Bn254>, Error> {
+        let groth = self.groth_header()?;
         let ic = self.ic(header.n_public)?;
 
         let a_query = self.a_query(header.n_vars)?;
@@ -111,17 +429,17 @@ impl<'a, R: Read + Seek> BinFile<'a, R> {
         let h_query = self.h_query(header.domain_size as usize)?;
 
         let vk = VerifyingKey::<Bn254> {
-            alpha_g1: header.verifying_key.alpha_g1,
-            beta_g2: header.verifying_key.beta_g2,
-            gamma_g2: header.verifying_key.gamma_g2,
-            delta_g2: header.verifying_key.delta_g2,
+            alpha_g1: groth.verifying_key.alpha_g1,
+            beta_g2: groth.verifying_key.beta_g2,
+            gamma_g2: groth.verifying_key.gamma_g2,
+            delta_g2: groth.verifying_key.delta_g2,
             gamma_abc_g1: ic,
         };
 
         let pk = ProvingKey::<Bn254> {
             vk,
-            beta_g1: header.verifying_key.beta_g1,
-            delta_g1: header.verifying_key.delta_g1,
+            beta_g1: groth.verifying_key.beta_g1,
+            delta_g1: groth.verifying_key.delta_g1,
             a_query,
             b_g1_query,
             b_g2_query,
@@ -132,55 +450,68 @@ impl<'a, R: Read + Seek> BinFile<'a, R> {
         Ok(pk)
     }
 
-    fn get_section(&self, id: u32) -> Section {
-        self.sections.get(&id).unwrap()[0].clone()
-    }
-
-    fn groth_header(&mut self) -> IoResult<HeaderGroth> {
-        let section = self.get_section(2);
-        let header = HeaderGroth::new(&mut self.reader, &section)?;
-        Ok(header)
+    fn groth_header(&mut self) -> Result<HeaderGroth, Error> {
+        let section = self.get_section(2)?;
+        HeaderGroth::new(&mut self.reader, &section)
     }
 
-    fn ic(&mut self, n_public: usize) -> IoResult<Vec<G1Affine>> {
+    fn ic(&mut self, n_public: usize) -> Result<Vec<G1Affine>, Error> {
         // the range is non-inclusive so we do +1 to get all inputs
         self.g1_section(n_public + 1, 3)
     }
 
     /// Returns the [`ConstraintMatrices`] corresponding to the zkey
-    pub fn matrices(&mut self) -> IoResult<ConstraintMatrices<Fr>> {
-        let header = self.groth_header()?;
+    fn matrices(&mut self, header: &ZKeyHeader) -> Result<ConstraintMatrices<Fr>, Error> {
+        let section = self.get_section(4)?;
+        self.start_section(4, None)?;
+        let num_coeffs: u32 = u32::deserialize_uncompressed(&mut self.reader)?;
+        if section.size != 4 + num_coeffs as usize * (12 + FIELD_SIZE) {
+            return Err(invalid("coefficients section size mismatch"));
+        }
 
-        let section = self.get_section(4);
-        self.reader.seek(SeekFrom::Start(section.position))?;
-        let num_coeffs: u32 = self.reader.read_u32::<LittleEndian>()?;
+        // the H section holds a point per row of the domain, which bounds the
+        // rows allocated below by the file size
+        let h_size = (header.domain_size as usize).checked_mul(G1_SIZE);
+        if h_size != Some(self.get_section(9)?.size) {
+            return Err(invalid("domain size does not match the H section"));
+        }
 
         // insantiate AB
         let mut matrices = vec![vec![vec![]; header.domain_size as usize]; 2];
         let mut max_constraint_index = 0;
         for _ in 0..num_coeffs {
-            let matrix: u32 = self.reader.read_u32::<LittleEndian>()?;
-            let constraint: u32 = self.reader.read_u32::<LittleEndian>()?;
-            let signal: u32 = self.reader.read_u32::<LittleEndian>()?;
+            let matrix = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+            let constraint = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+            let signal = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+            if matrix > 1 || constraint >= header.domain_size as usize || signal >= header.n_vars {
+                return Err(invalid(format!(
+                    "coefficient out of range: matrix {}, constraint {}, signal {}",
+                    matrix, constraint, signal
+                )));
+            }
 
             let value: Fr = deserialize_field_fr(&mut self.reader)?;
             max_constraint_index = std::cmp::max(max_constraint_index, constraint);
-            matrices[matrix as usize][constraint as usize].push((value, signal as usize));
+            matrices[matrix][constraint].push((value, signal));
         }
 
-        let num_constraints = max_constraint_index as usize - header.n_public;
+        // the last constraints of A are the ones snarkjs adds for each public
+        // signal and the constant one
+        let num_constraints = (max_constraint_index + 1)
+            .checked_sub(header.n_public + 1)
+            .ok_or_else(|| invalid("missing public inputs constraints"))?;
         // Remove the public input constraints, Arkworks adds them later
         matrices.iter_mut().for_each(|m| {
             m.truncate(num_constraints);
         });
         // This is taken from Arkworks' to_matrices() function
-        let a = matrices[0].clone();
-        let b = matrices[1].clone();
+        let b = matrices.pop().unwrap();
+        let a = matrices.pop().unwrap();
         let a_num_non_zero: usize = a.iter().map(|lc| lc.len()).sum();
         let b_num_non_zero: usize = b.iter().map(|lc| lc.len()).sum();
         let matrices = ConstraintMatrices {
             num_instance_variables: header.n_public + 1,
-            num_witness_variables: header.n_vars - header.n_public,
+            num_witness_variables: header.n_vars - header.n_public - 1,
             num_constraints,
 
             a_num_non_zero,
@@ -195,39 +526,382 @@ impl<'a, R: Read + Seek> BinFile<'a, R> {
         Ok(matrices)
     }
 
-    fn a_query(&mut self, n_vars: usize) -> IoResult<Vec<G1Affine>> {
+    fn mpc_params(&mut self) -> Result<MpcParams, Error> {
+        let section = self.get_section(10)?;
+        self.start_section(10, None)?;
+        let mut cs_hash = vec![0u8; 64];
+        self.read_bytes(&mut cs_hash)?;
+        let n = u32::deserialize_uncompressed(&mut self.reader)?;
+        let contributions = (0..n)
+            .map(|_| self.contribution())
+            .collect::<Result<Vec<_>, _>>()?;
+        let end = self
+            .reader
+            .stream_position()
+            .map_err(SerializationError::from)?;
+        if end != section.position + section.size as u64 {
+            return Err(invalid("contributions section size mismatch"));
+        }
+        Ok(MpcParams {
+            cs_hash,
+            contributions,
+        })
+    }
+
+    fn contribution(&mut self) -> Result<Contribution, Error> {
+        let delta_after = deserialize_g1(&mut self.reader)?;
+        let g1_s = deserialize_g1(&mut self.reader)?;
+        let g1_sx = deserialize_g1(&mut self.reader)?;
+        let g2_spx = deserialize_g2(&mut self.reader)?;
+        let mut transcript = vec![0u8; 64];
+        self.read_bytes(&mut transcript)?;
+        let contribution_type = u32::deserialize_uncompressed(&mut self.reader)?;
+
+        let mut contribution = Contribution {
+            delta_after,
+            g1_s,
+            g1_sx,
+            g2_spx,
+            transcript,
+            contribution_type,
+            name: None,
+            num_iterations_exp: None,
+            beacon_hash: None,
+        };
+
+        // the parameters are (type, value) pairs sorted by type
+        let params_len = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        let mut params = vec![0u8; params_len];
+        self.read_bytes(&mut params)?;
+        let mut params = &params[..];
+        let mut last_type = 0;
+        while let Some((&param_type, rest)) = params.split_first() {
+            if param_type <= last_type {
+                return Err(invalid("contribution parameters not sorted"));
+            }
+            last_type = param_type;
+            let (value, rest) = match (param_type, rest.split_first()) {
+                (2, Some((&exp, rest))) => {
+                    contribution.num_iterations_exp = Some(exp);
+                    (None, rest)
+                }
+                (1, Some((&len, rest))) | (3, Some((&len, rest))) if rest.len() >= len as usize => {
+                    let (value, rest) = rest.split_at(len as usize);
+                    (Some(value), rest)
+                }
+                _ => return Err(invalid("invalid contribution parameters")),
+            };
+            match (param_type, value) {
+                (1, Some(name)) => {
+                    contribution.name = Some(
+                        String::from_utf8(name.to_vec())
+                            .map_err(|_| invalid("contribution name is not utf-8"))?,
+                    )
+                }
+                (3, Some(hash)) => contribution.beacon_hash = Some(hash.to_vec()),
+                _ => {}
+            }
+            params = rest;
+        }
+        Ok(contribution)
+    }
+
+    fn r1cs_matrices(&mut self) -> Result<ConstraintMatrices<Fr>, Error> {
+        self.start_section(1, Some(4 + FIELD_SIZE + 28))?;
+        let n8 = u32::deserialize_uncompressed(&mut self.reader)?;
+        let prime = BigInteger256::deserialize_uncompressed(&mut self.reader)?;
+        if n8 as usize != FIELD_SIZE || prime != FrParameters::MODULUS {
+            return Err(invalid("r1cs is not over the BN254 scalar field"));
+        }
+        let n_wires = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        let n_pub_out = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        let n_pub_in = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        let _n_prv_in = u32::deserialize_uncompressed(&mut self.reader)?;
+        let _n_labels = u64::deserialize_uncompressed(&mut self.reader)?;
+        let num_constraints = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        let num_instance_variables = 1 + n_pub_out + n_pub_in;
+        if num_instance_variables > n_wires {
+            return Err(invalid("more public signals than wires"));
+        }
+
+        let section = self.get_section(2)?;
+        self.start_section(2, None)?;
+        let mut matrices = vec![vec![]; 3];
+        for _ in 0..num_constraints {
+            for matrix in matrices.iter_mut() {
+                let n_terms = u32::deserialize_uncompressed(&mut self.reader)?;
+                let lc = (0..n_terms)
+                    .map(|_| {
+                        let wire = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+                        let coeff = BigInteger256::deserialize_uncompressed(&mut self.reader)?;
+                        match Fr::from_repr(coeff) {
+                            Some(coeff) if wire < n_wires => Ok((coeff, wire)),
+                            _ => Err(invalid("invalid r1cs term")),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                matrix.push(lc);
+            }
+        }
+        let end = self
+            .reader
+            .stream_position()
+            .map_err(SerializationError::from)?;
+        if end != section.position + section.size as u64 {
+            return Err(invalid("constraints section size mismatch"));
+        }
+
+        let c = matrices.pop().unwrap();
+        let b = matrices.pop().unwrap();
+        let a = matrices.pop().unwrap();
+        Ok(ConstraintMatrices {
+            num_instance_variables,
+            num_witness_variables: n_wires - num_instance_variables,
+            num_constraints,
+            a_num_non_zero: a.iter().map(|lc| lc.len()).sum(),
+            b_num_non_zero: b.iter().map(|lc| lc.len()).sum(),
+            c_num_non_zero: c.iter().map(|lc| lc.len()).sum(),
+            a,
+            b,
+            c,
+        })
+    }
+
+    fn witness(&mut self) -> Result<Vec<Fr>, Error> {
+        self.start_section(1, Some(4 + FIELD_SIZE + 4))?;
+        let n8 = u32::deserialize_uncompressed(&mut self.reader)?;
+        let prime = BigInteger256::deserialize_uncompressed(&mut self.reader)?;
+        if n8 as usize != FIELD_SIZE || prime != FrParameters::MODULUS {
+            return Err(invalid("witness is not over the BN254 scalar field"));
+        }
+        let n = u32::deserialize_uncompressed(&mut self.reader)? as usize;
+        // the values are written in normal form
+        self.start_section(2, Some(n * FIELD_SIZE))?;
+        (0..n)
+            .map(|_| {
+                let value = BigInteger256::deserialize_uncompressed(&mut self.reader)?;
+                Fr::from_repr(value).ok_or_else(|| invalid("witness value out of range"))
+            })
+            .collect()
+    }
+
+    fn a_query(&mut self, n_vars: usize) -> Result<Vec<G1Affine>, Error> {
         self.g1_section(n_vars, 5)
     }
 
-    fn b_g1_query(&mut self, n_vars: usize) -> IoResult<Vec<G1Affine>> {
+    fn b_g1_query(&mut self, n_vars: usize) -> Result<Vec<G1Affine>, Error> {
         self.g1_section(n_vars, 6)
     }
 
-    fn b_g2_query(&mut self, n_vars: usize) -> IoResult<Vec<G2Affine>> {
+    fn b_g2_query(&mut self, n_vars: usize) -> Result<Vec<G2Affine>, Error> {
         self.g2_section(n_vars, 7)
     }
 
-    fn l_query(&mut self, n_vars: usize) -> IoResult<Vec<G1Affine>> {
+    fn l_query(&mut self, n_vars: usize) -> Result<Vec<G1Affine>, Error> {
         self.g1_section(n_vars, 8)
     }
 
-    fn h_query(&mut self, n_vars: usize) -> IoResult<Vec<G1Affine>> {
+    fn h_query(&mut self, n_vars: usize) -> Result<Vec<G1Affine>, Error> {
         self.g1_section(n_vars, 9)
     }
 
-    fn g1_section(&mut self, num: usize, section_id: usize) -> IoResult<Vec<G1Affine>> {
-        let section = self.get_section(section_id as u32);
-        self.reader.seek(SeekFrom::Start(section.position))?;
-        deserialize_g1_vec(self.reader, num as u32)
+    fn g1_section(&mut self, num: usize, section_id: u32) -> Result<Vec<G1Affine>, Error> {
+        let size = num.checked_mul(G1_SIZE);
+        self.start_section(
+            section_id,
+            Some(size.ok_or_else(|| invalid("section too large"))?),
+        )?;
+        Ok(deserialize_g1_vec(self.reader, num as u32)?)
+    }
+
+    fn g2_section(&mut self, num: usize, section_id: u32) -> Result<Vec<G2Affine>, Error> {
+        let size = num.checked_mul(G2_SIZE);
+        self.start_section(
+            section_id,
+            Some(size.ok_or_else(|| invalid("section too large"))?),
+        )?;
+        Ok(deserialize_g2_vec(self.reader, num as u32)?)
     }
 
-    fn g2_section(&mut self, num: usize, section_id: usize) -> IoResult<Vec<G2Affine>> {
-        let section = self.get_section(section_id as u32);
-        self.reader.seek(SeekFrom::Start(section.position))?;
-        deserialize_g2_vec(self.reader, num as u32)
+    fn read_bytes(&mut self, buff: &mut [u8]) -> Result<(), Error> {
+        self.reader
+            .read_exact(buff)
+            .map_err(|e| Error::Serialization(e.into()))
     }
 }
 
+fn write_sections<W: Write>(
+    writer: &mut W,
+    pk: &ProvingKey<Bn254>,
+    matrices: &ConstraintMatrices<Fr>,
+    mpc: &MpcParams,
+) -> Result<(), Error> {
+    let n_public = matrices.num_instance_variables - 1;
+    let n_vars = matrices.num_instance_variables + matrices.num_witness_variables;
+    let domain_size = (matrices.num_constraints + matrices.num_instance_variables)
+        .checked_next_power_of_two()
+        .ok_or_else(|| invalid("too many constraints"))?;
+    let well_formed = pk.vk.gamma_abc_g1.len() == n_public + 1
+        && pk.a_query.len() == n_vars
+        && pk.b_g1_query.len() == n_vars
+        && pk.b_g2_query.len() == n_vars
+        && pk.l_query.len() == matrices.num_witness_variables
+        && matrices.a.len() == matrices.num_constraints
+        && matrices.b.len() == matrices.num_constraints;
+    if !well_formed {
+        return Err(invalid("proving key does not match the constraints"));
+    }
+    let h_query = if pk.h_query.len() + 1 == domain_size {
+        circom_h_query(&pk.h_query, domain_size)
+    } else if pk.h_query.len() == domain_size {
+        pk.h_query.clone()
+    } else {
+        return Err(invalid("H query does not match the domain size"));
+    };
+
+    let mut header = Vec::new();
+    1u32.serialize_uncompressed(&mut header)?;
+
+    let mut groth = Vec::new();
+    (FIELD_SIZE as u32).serialize_uncompressed(&mut groth)?;
+    FqParameters::MODULUS.serialize_uncompressed(&mut groth)?;
+    (FIELD_SIZE as u32).serialize_uncompressed(&mut groth)?;
+    FrParameters::MODULUS.serialize_uncompressed(&mut groth)?;
+    (n_vars as u32).serialize_uncompressed(&mut groth)?;
+    (n_public as u32).serialize_uncompressed(&mut groth)?;
+    (domain_size as u32).serialize_uncompressed(&mut groth)?;
+    serialize_g1(&mut groth, &pk.vk.alpha_g1)?;
+    serialize_g1(&mut groth, &pk.beta_g1)?;
+    serialize_g2(&mut groth, &pk.vk.beta_g2)?;
+    serialize_g2(&mut groth, &pk.vk.gamma_g2)?;
+    serialize_g1(&mut groth, &pk.delta_g1)?;
+    serialize_g2(&mut groth, &pk.vk.delta_g2)?;
+
+    // the coefficients of A and B, constraint by constraint, followed by the
+    // constraints of the public signals
+    let mut coeffs = Vec::new();
+    let num_coeffs = matrices.a_num_non_zero + matrices.b_num_non_zero + n_public + 1;
+    (num_coeffs as u32).serialize_uncompressed(&mut coeffs)?;
+    let mut write_coeff = |matrix: u32, constraint: usize, (value, signal): (Fr, usize)| {
+        matrix.serialize_uncompressed(&mut coeffs)?;
+        (constraint as u32).serialize_uncompressed(&mut coeffs)?;
+        (signal as u32).serialize_uncompressed(&mut coeffs)?;
+        serialize_field_fr(&mut coeffs, &value)
+    };
+    let mut written = 0;
+    for (i, (a, b)) in matrices.a.iter().zip(matrices.b.iter()).enumerate() {
+        for term in a {
+            write_coeff(0, i, *term)?;
+        }
+        for term in b {
+            write_coeff(1, i, *term)?;
+        }
+        written += a.len() + b.len();
+    }
+    for signal in 0..=n_public {
+        write_coeff(0, matrices.num_constraints + signal, (Fr::one(), signal))?;
+    }
+    if written + n_public + 1 != num_coeffs {
+        return Err(invalid("wrong number of non zero coefficients"));
+    }
+
+    let mut contributions = mpc.cs_hash.clone();
+    (mpc.contributions.len() as u32).serialize_uncompressed(&mut contributions)?;
+    for c in &mpc.contributions {
+        serialize_contribution(&mut contributions, c)?;
+    }
+
+    let sections = [
+        header,
+        groth,
+        serialize_g1_vec(&pk.vk.gamma_abc_g1)?,
+        coeffs,
+        serialize_g1_vec(&pk.a_query)?,
+        serialize_g1_vec(&pk.b_g1_query)?,
+        serialize_g2_vec(&pk.b_g2_query)?,
+        serialize_g1_vec(&pk.l_query)?,
+        serialize_g1_vec(&h_query)?,
+        contributions,
+    ];
+    let mut write = |bytes: &[u8]| writer.write_all(bytes).map_err(SerializationError::from);
+    write(b"zkey")?;
+    write(&1u32.to_le_bytes())?;
+    write(&(sections.len() as u32).to_le_bytes())?;
+    for (id, section) in sections.iter().enumerate() {
+        write(&(id as u32 + 1).to_le_bytes())?;
+        write(&(section.len() as u64).to_le_bytes())?;
+        write(section)?;
+    }
+    Ok(())
+}
+
+fn serialize_contribution(out: &mut Vec<u8>, c: &Contribution) -> IoResult<()> {
+    serialize_g1(out, &c.delta_after)?;
+    serialize_g1(out, &c.g1_s)?;
+    serialize_g1(out, &c.g1_sx)?;
+    serialize_g2(out, &c.g2_spx)?;
+    out.extend_from_slice(&c.transcript);
+    c.contribution_type.serialize_uncompressed(&mut *out)?;
+
+    let mut params = Vec::new();
+    if let Some(name) = &c.name {
+        params.extend_from_slice(&[1, name.len() as u8]);
+        params.extend_from_slice(name.as_bytes());
+    }
+    if let Some(exp) = c.num_iterations_exp {
+        params.extend_from_slice(&[2, exp]);
+    }
+    if let Some(hash) = &c.beacon_hash {
+        params.extend_from_slice(&[3, hash.len() as u8]);
+        params.extend_from_slice(hash);
+    }
+    (params.len() as u32).serialize_uncompressed(&mut *out)?;
+    out.extend_from_slice(&params);
+    Ok(())
+}
+
+/// Converts the H query of Arkworks, $\tau^i Z(\tau) / \delta$ for $i$ up to
+/// the domain size $n$ minus two, into the one of snarkjs. The quotient
+/// polynomial $h$ is evaluated by snarkjs on the coset $g \omega^i$, with $g$
+/// a $2n$-th root of unity, where $Z$ is $-2$: its H query is $-L_i(\tau)
+/// Z(\tau) / (2 \delta)$ with $L_i$ the Lagrange polynomials of the coset,
+/// whose coefficients are $(g \omega^i)^{-j} / n$. This is an inverse FFT of
+/// the Arkworks query multiplied by the powers of $g^{-1}$.
+pub(crate) fn circom_h_query(h_query: &[G1Affine], domain_size: usize) -> Vec<G1Affine> {
+    let domain = Radix2EvaluationDomain::<Fr>::new(domain_size).unwrap();
+    let g = Radix2EvaluationDomain::<Fr>::new(2 * domain_size)
+        .unwrap()
+        .element(1);
+    let mut points = h_query
+        .iter()
+        .map(|p| p.into_projective())
+        .collect::<Vec<_>>();
+    points.resize(domain_size, G1Projective::zero());
+    let minus_half = -Fr::from(2u64).inverse().unwrap();
+    Radix2EvaluationDomain::distribute_powers_and_mul_by_const(
+        &mut points,
+        g.inverse().unwrap(),
+        minus_half,
+    );
+    domain.ifft_in_place(&mut points);
+    G1Projective::batch_normalization_into_affine(&points)
+}
+
+fn invalid<S: ToString>(msg: S) -> Error {
+    Error::InvalidZKey(msg.to_string())
+}
+
+/// Returns true if both matrices have the same rows, up to the order of the
+/// terms of each row.
+fn same_matrix(a: &Matrix<Fr>, b: &Matrix<Fr>) -> bool {
+    let sorted = |row: &[(Fr, usize)]| {
+        let mut row = row.to_vec();
+        row.sort_by_key(|(_, i)| *i);
+        row
+    };
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| sorted(x) == sorted(y))
+}
+
 #[derive(Default, Clone, Debug, CanonicalDeserialize)]
 pub struct ZVerifyingKey {
     alpha_g1: G1Affine,
@@ -260,45 +934,57 @@ impl ZVerifyingKey {
 
 #[derive(Clone, Debug)]
 struct HeaderGroth {
-    #[allow(dead_code)]
     n8q: u32,
-    #[allow(dead_code)]
     q: BigInteger256,
-    #[allow(dead_code)]
     n8r: u32,
-    #[allow(dead_code)]
     r: BigInteger256,
 
     n_vars: usize,
     n_public: usize,
 
     domain_size: u32,
-    #[allow(dead_code)]
     power: u32,
 
     verifying_key: ZVerifyingKey,
 }
 
 impl HeaderGroth {
-    fn new<R: Read + Seek>(reader: &mut R, section: &Section) -> IoResult<Self> {
-        reader.seek(SeekFrom::Start(section.position))?;
+    fn new<R: Read + Seek>(reader: &mut R, section: &Section) -> Result<Self, Error> {
+        reader
+            .seek(SeekFrom::Start(section.position))
+            .map_err(SerializationError::from)?;
         Self::read(reader)
     }
 
-    fn read<R: Read>(mut reader: &mut R) -> IoResult<Self> {
+    fn read<R: Read>(mut reader: &mut R) -> Result<Self, Error> {
         // TODO: Impl From<u32> in Arkworks
         let n8q: u32 = u32::deserialize_uncompressed(&mut reader)?;
-        // group order r of Bn254
+        if n8q as usize != FIELD_SIZE {
+            return Err(invalid("zkey is not over BN254"));
+        }
+        // Prime field modulus
         let q = BigInteger256::deserialize_uncompressed(&mut reader)?;
 
         let n8r: u32 = u32::deserialize_uncompressed(&mut reader)?;
-        // Prime field modulus
+        if n8r as usize != FIELD_SIZE {
+            return Err(invalid("zkey is not over BN254"));
+        }
+        // group order r of Bn254
         let r = BigInteger256::deserialize_uncompressed(&mut reader)?;
+        if q != FqParameters::MODULUS || r != FrParameters::MODULUS {
+            return Err(invalid("zkey is not over BN254"));
+        }
 
         let n_vars = u32::deserialize_uncompressed(&mut reader)? as usize;
         let n_public = u32::deserialize_uncompressed(&mut reader)? as usize;
+        if n_public >= n_vars {
+            return Err(invalid("more public signals than signals"));
+        }
 
         let domain_size: u32 = u32::deserialize_uncompressed(&mut reader)?;
+        if !domain_size.is_power_of_two() {
+            return Err(invalid("domain size is not a power of two"));
+        }
         let power = log2(domain_size as usize);
 
         let verifying_key = ZVerifyingKey::new(&mut reader)?;
@@ -317,11 +1003,51 @@ impl HeaderGroth {
     }
 }
 
+// the points of verification_key.json are in projective coordinates, written
+// in decimal
+fn parse_fq(s: &str) -> Option<Fq> {
+    Fq::from_str(s).ok()
+}
+
+fn parse_fq2(s: &[String]) -> Option<Fq2> {
+    match s {
+        [c0, c1] => Some(Fq2::new(parse_fq(c0)?, parse_fq(c1)?)),
+        _ => None,
+    }
+}
+
+fn parse_g1(s: &[String]) -> Option<G1Affine> {
+    let p = match s {
+        [x, y, z] => G1Projective::new(parse_fq(x)?, parse_fq(y)?, parse_fq(z)?).into_affine(),
+        _ => return None,
+    };
+    p.is_on_curve().then_some(p)
+}
+
+fn parse_g2(s: &[Vec<String>]) -> Option<G2Affine> {
+    let p = match s {
+        [x, y, z] => G2Projective::new(parse_fq2(x)?, parse_fq2(y)?, parse_fq2(z)?).into_affine(),
+        _ => return None,
+    };
+    p.is_on_curve().then_some(p)
+}
+
+fn parse_fq12(s: &[Vec<Vec<String>>]) -> Option<Fq12> {
+    let fq6 = |s: &[Vec<String>]| match s {
+        [c0, c1, c2] => Some(Fq6::new(parse_fq2(c0)?, parse_fq2(c1)?, parse_fq2(c2)?)),
+        _ => None,
+    };
+    match s {
+        [c0, c1] => Some(Fq12::new(fq6(c0)?, fq6(c1)?)),
+        _ => None,
+    }
+}
+
 // need to divide by R, since snarkjs outputs the zkey with coefficients
 // multiplieid by R^2
 fn deserialize_field_fr<R: Read>(reader: &mut R) -> IoResult<Fr> {
     let bigint = BigInteger256::deserialize_uncompressed(reader)?;
-    Ok(Fr::new(bigint))
+    Ok(Fr::new(Fr::new(bigint).into_repr()))
 }
 
 // skips the multiplication by R because Circom points are already in Montgomery form
@@ -341,15 +1067,19 @@ pub fn deserialize_field2<R: Read>(reader: &mut R) -> IoResult<Fq2> {
 fn deserialize_g1<R: Read>(reader: &mut R) -> IoResult<G1Affine> {
     let x = deserialize_field(reader)?;
     let y = deserialize_field(reader)?;
-    let infinity = x.is_zero() && y.is_zero();
-    Ok(G1Affine::new(x, y, infinity))
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero());
+    }
+    Ok(G1Affine::new(x, y, false))
 }
 
 fn deserialize_g2<R: Read>(reader: &mut R) -> IoResult<G2Affine> {
     let f1 = deserialize_field2(reader)?;
     let f2 = deserialize_field2(reader)?;
-    let infinity = f1.is_zero() && f2.is_zero();
-    Ok(G2Affine::new(f1, f2, infinity))
+    if f1.is_zero() && f2.is_zero() {
+        return Ok(G2Affine::zero());
+    }
+    Ok(G2Affine::new(f1, f2, false))
 }
 
 fn deserialize_g1_vec<R: Read>(reader: &mut R, n_vars: u32) -> IoResult<Vec<G1Affine>> {
@@ -359,3 +1089,522 @@ fn deserialize_g1_vec<R: Read>(reader: &mut R, n_vars: u32) -> IoResult<Vec<G1Af
 fn deserialize_g2_vec<R: Read>(reader: &mut R, n_vars: u32) -> IoResult<Vec<G2Affine>> {
     (0..n_vars).map(|_| deserialize_g2(reader)).collect()
 }
+
+// multiplies by R^2, the inverse of `deserialize_field_fr`
+fn serialize_field_fr(out: &mut Vec<u8>, value: &Fr) -> IoResult<()> {
+    let r2 = Fr::from_repr(FrParameters::R2).unwrap();
+    (*value * r2).into_repr().serialize_uncompressed(out)
+}
+
+// writes the Montgomery form, the inverse of `deserialize_field`
+fn serialize_field(out: &mut Vec<u8>, value: &Fq) -> IoResult<()> {
+    value.0.serialize_uncompressed(out)
+}
+
+fn serialize_g1(out: &mut Vec<u8>, p: &G1Affine) -> IoResult<()> {
+    // the point at infinity is written as zeros
+    let (x, y) = if p.infinity {
+        (Fq::zero(), Fq::zero())
+    } else {
+        (p.x, p.y)
+    };
+    serialize_field(out, &x)?;
+    serialize_field(out, &y)
+}
+
+fn serialize_g2(out: &mut Vec<u8>, p: &G2Affine) -> IoResult<()> {
+    let (x, y) = if p.infinity {
+        (Fq2::zero(), Fq2::zero())
+    } else {
+        (p.x, p.y)
+    };
+    for f in &[x.c0, x.c1, y.c0, y.c1] {
+        serialize_field(out, f)?;
+    }
+    Ok(())
+}
+
+fn serialize_g1_vec(points: &[G1Affine]) -> IoResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(points.len() * G1_SIZE);
+    for p in points {
+        serialize_g1(&mut out, p)?;
+    }
+    Ok(out)
+}
+
+fn serialize_g2_vec(points: &[G2Affine]) -> IoResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(points.len() * G2_SIZE);
+    for p in points {
+        serialize_g2(&mut out, p)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{BigInteger, ToBytes};
+    use ark_groth16::generate_random_parameters;
+    use ark_relations::{
+        lc,
+        r1cs::{
+            ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError,
+            SynthesisMode, Variable,
+        },
+    };
+    use ark_std::{test_rng, UniformRand};
+    use std::io::Cursor;
+
+    // signals: 1, c, d, a, b with c = a * b and d = a + b
+    fn matrices() -> ConstraintMatrices<Fr> {
+        let one = Fr::one();
+        ConstraintMatrices {
+            num_instance_variables: 3,
+            num_witness_variables: 2,
+            num_constraints: 2,
+            a_num_non_zero: 3,
+            b_num_non_zero: 2,
+            c_num_non_zero: 2,
+            a: vec![vec![(one, 3)], vec![(one, 3), (one, 4)]],
+            b: vec![vec![(one, 4)], vec![(one, 0)]],
+            c: vec![vec![(one, 1)], vec![(one, 2)]],
+        }
+    }
+
+    fn g1s(n: usize) -> Vec<G1Affine> {
+        let rng = &mut test_rng();
+        (0..n)
+            .map(|_| {
+                G1Affine::prime_subgroup_generator()
+                    .mul(Fr::rand(rng))
+                    .into_affine()
+            })
+            .collect()
+    }
+
+    fn g2s(n: usize) -> Vec<G2Affine> {
+        let rng = &mut test_rng();
+        (0..n)
+            .map(|_| {
+                G2Affine::prime_subgroup_generator()
+                    .mul(Fr::rand(rng))
+                    .into_affine()
+            })
+            .collect()
+    }
+
+    fn proving_key() -> ProvingKey<Bn254> {
+        ProvingKey {
+            vk: VerifyingKey {
+                alpha_g1: g1s(1)[0],
+                beta_g2: g2s(2)[1],
+                gamma_g2: g2s(3)[2],
+                delta_g2: g2s(4)[3],
+                gamma_abc_g1: g1s(3),
+            },
+            beta_g1: g1s(5)[4],
+            delta_g1: g1s(6)[5],
+            a_query: g1s(5),
+            b_g1_query: g1s(5),
+            b_g2_query: g2s(5),
+            h_query: g1s(8),
+            l_query: g1s(2),
+        }
+    }
+
+    fn write_g1(out: &mut Vec<u8>, p: &G1Affine) {
+        // points are written in montgomery form, the infinity as zeros
+        p.x.0.write(&mut *out).unwrap();
+        p.y.0.write(&mut *out).unwrap();
+    }
+
+    fn write_g2(out: &mut Vec<u8>, p: &G2Affine) {
+        for f in &[p.x.c0, p.x.c1, p.y.c0, p.y.c1] {
+            f.0.write(&mut *out).unwrap();
+        }
+    }
+
+    fn contribution() -> Contribution {
+        Contribution {
+            delta_after: g1s(6)[5],
+            g1_s: g1s(1)[0],
+            g1_sx: g1s(2)[1],
+            g2_spx: g2s(1)[0],
+            transcript: vec![7u8; 64],
+            contribution_type: 0,
+            name: Some("alice".to_string()),
+            num_iterations_exp: None,
+            beacon_hash: None,
+        }
+    }
+
+    fn bin_file(ftype: &[u8], sections: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = ftype.to_vec();
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        for (id, section) in sections {
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&(section.len() as u64).to_le_bytes());
+            out.extend_from_slice(section);
+        }
+        out
+    }
+
+    fn zkey_sections() -> Vec<(u32, Vec<u8>)> {
+        let pk = proving_key();
+        let m = matrices();
+        let g1_section = |points: &[G1Affine]| {
+            let mut out = Vec::new();
+            points.iter().for_each(|p| write_g1(&mut out, p));
+            out
+        };
+
+        let mut groth = Vec::new();
+        groth.extend_from_slice(&32u32.to_le_bytes());
+        FqParameters::MODULUS.write(&mut groth).unwrap();
+        groth.extend_from_slice(&32u32.to_le_bytes());
+        FrParameters::MODULUS.write(&mut groth).unwrap();
+        groth.extend_from_slice(&5u32.to_le_bytes());
+        groth.extend_from_slice(&2u32.to_le_bytes());
+        groth.extend_from_slice(&8u32.to_le_bytes());
+        write_g1(&mut groth, &pk.vk.alpha_g1);
+        write_g1(&mut groth, &pk.beta_g1);
+        write_g2(&mut groth, &pk.vk.beta_g2);
+        write_g2(&mut groth, &pk.vk.gamma_g2);
+        write_g1(&mut groth, &pk.delta_g1);
+        write_g2(&mut groth, &pk.vk.delta_g2);
+
+        // coefficients are multiplied by R^2, A and B constraint by
+        // constraint, with the constraints of the public signals appended to A
+        let r2 = Fr::from_repr(FrParameters::R2).unwrap();
+        let mut coeffs = Vec::new();
+        let mut terms = Vec::new();
+        for i in 0..m.num_constraints {
+            terms.extend(m.a[i].iter().map(|t| (0u32, i, *t)));
+            terms.extend(m.b[i].iter().map(|t| (1u32, i, *t)));
+        }
+        terms.extend((0..3).map(|i| (0u32, m.num_constraints + i, (Fr::one(), i))));
+        coeffs.extend_from_slice(&(terms.len() as u32).to_le_bytes());
+        for (matrix, constraint, (value, signal)) in terms {
+            coeffs.extend_from_slice(&matrix.to_le_bytes());
+            coeffs.extend_from_slice(&(constraint as u32).to_le_bytes());
+            coeffs.extend_from_slice(&(signal as u32).to_le_bytes());
+            (value * r2).into_repr().write(&mut coeffs).unwrap();
+        }
+
+        let c = contribution();
+        let mut mpc = vec![3u8; 64];
+        mpc.extend_from_slice(&1u32.to_le_bytes());
+        write_g1(&mut mpc, &c.delta_after);
+        write_g1(&mut mpc, &c.g1_s);
+        write_g1(&mut mpc, &c.g1_sx);
+        write_g2(&mut mpc, &c.g2_spx);
+        mpc.extend_from_slice(&c.transcript);
+        mpc.extend_from_slice(&0u32.to_le_bytes());
+        mpc.extend_from_slice(&7u32.to_le_bytes());
+        mpc.extend_from_slice(&[1, 5]);
+        mpc.extend_from_slice(b"alice");
+
+        let mut b2 = Vec::new();
+        pk.b_g2_query.iter().for_each(|p| write_g2(&mut b2, p));
+        vec![
+            (1, 1u32.to_le_bytes().to_vec()),
+            (2, groth),
+            (3, g1_section(&pk.vk.gamma_abc_g1)),
+            (4, coeffs),
+            (5, g1_section(&pk.a_query)),
+            (6, g1_section(&pk.b_g1_query)),
+            (7, b2),
+            (8, g1_section(&pk.l_query)),
+            (9, g1_section(&pk.h_query)),
+            (10, mpc),
+        ]
+    }
+
+    fn r1cs_file(m: &ConstraintMatrices<Fr>) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&32u32.to_le_bytes());
+        FrParameters::MODULUS.write(&mut header).unwrap();
+        for n in &[5u32, 1, 1, 2] {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        header.extend_from_slice(&5u64.to_le_bytes());
+        header.extend_from_slice(&(m.num_constraints as u32).to_le_bytes());
+        let mut constraints = Vec::new();
+        for i in 0..m.num_constraints {
+            for matrix in &[&m.a, &m.b, &m.c] {
+                constraints.extend_from_slice(&(matrix[i].len() as u32).to_le_bytes());
+                for (value, wire) in &matrix[i] {
+                    constraints.extend_from_slice(&(*wire as u32).to_le_bytes());
+                    value.into_repr().write(&mut constraints).unwrap();
+                }
+            }
+        }
+        bin_file(b"r1cs", &[(1, header), (2, constraints)])
+    }
+
+    #[test]
+    fn test_read_zkey() {
+        let file = bin_file(b"zkey", &zkey_sections());
+        let zkey = ZKey::read(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(zkey.header.n_vars, 5);
+        assert_eq!(zkey.header.n_public, 2);
+        assert_eq!(zkey.header.domain_size, 8);
+        assert_eq!(zkey.header.power, 3);
+        assert_eq!(zkey.proving_key, proving_key());
+        let mut expected = matrices();
+        expected.c = vec![];
+        expected.c_num_non_zero = 0;
+        assert_eq!(zkey.matrices, expected);
+        assert_eq!(zkey.mpc.cs_hash, vec![3u8; 64]);
+        assert_eq!(zkey.mpc.contributions, vec![contribution()]);
+
+        let (pk, m) = read_zkey(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(pk, zkey.proving_key);
+        assert_eq!(m, zkey.matrices);
+
+        let r1cs = r1cs_file(&matrices());
+        let zkey = ZKey::read_with_r1cs(&mut Cursor::new(&file), &mut Cursor::new(&r1cs)).unwrap();
+        assert_eq!(zkey.matrices, matrices());
+    }
+
+    #[test]
+    fn test_invalid_zkey() {
+        let is_invalid = |file: &[u8], msg: &str| match ZKey::read(&mut Cursor::new(file)) {
+            Err(Error::InvalidZKey(e)) => assert_eq!(e, msg),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        };
+        let sections = zkey_sections();
+
+        let missing = bin_file(b"zkey", &sections[..9]);
+        is_invalid(&missing, "missing section 10");
+        // the contributions are not needed for the proving key
+        read_zkey(&mut Cursor::new(&missing)).unwrap();
+
+        let mut duplicated = sections.clone();
+        duplicated.push(sections[2].clone());
+        is_invalid(&bin_file(b"zkey", &duplicated), "duplicated section 3");
+
+        let mut short = sections.clone();
+        short[8].1.truncate(G1_SIZE);
+        is_invalid(
+            &bin_file(b"zkey", &short),
+            "section 9 has 64 bytes, expected 512",
+        );
+
+        is_invalid(&bin_file(b"r1cs", &sections), "not a zkey file");
+
+        // sizes read in the file must not allocate more than the file holds
+        let mut file = bin_file(b"zkey", &sections[..1]);
+        file[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        is_invalid(&file, "section 1 exceeds the file");
+        let mut huge = sections.clone();
+        huge[1].1[80..84].copy_from_slice(&(1u32 << 31).to_le_bytes());
+        let mut file = Cursor::new(bin_file(b"zkey", &huge));
+        let mut binfile = BinFile::new(&mut file, b"zkey", &[1]).unwrap();
+        let header = binfile.header().unwrap();
+        assert_eq!(header.domain_size, 1 << 31);
+        match binfile.matrices(&header) {
+            Err(Error::InvalidZKey(e)) => {
+                assert_eq!(e, "domain size does not match the H section")
+            }
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+
+        // the r1cs must have the constraints of the zkey
+        let file = bin_file(b"zkey", &sections);
+        let mut other = matrices();
+        other.a[1].pop();
+        let r1cs = r1cs_file(&other);
+        match ZKey::read_with_r1cs(&mut Cursor::new(&file), &mut Cursor::new(&r1cs)) {
+            Err(Error::InvalidZKey(e)) => assert_eq!(e, "r1cs constraints do not match the zkey"),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_read_witness() {
+        let witness = vec![Fr::one(), Fr::from(3u64), Fr::from(5u64), -Fr::one()];
+        let wtns = |witness: &[Fr], n: u32| {
+            let mut header = 32u32.to_le_bytes().to_vec();
+            FrParameters::MODULUS.write(&mut header).unwrap();
+            header.extend_from_slice(&n.to_le_bytes());
+            let mut values = Vec::new();
+            witness
+                .iter()
+                .for_each(|w| w.into_repr().write(&mut values).unwrap());
+            bin_file(b"wtns", &[(1, header), (2, values)])
+        };
+        let file = wtns(&witness, 4);
+        assert_eq!(read_witness(&mut Cursor::new(&file)).unwrap(), witness);
+
+        let is_invalid = |file: &[u8], msg: &str| match read_witness(&mut Cursor::new(file)) {
+            Err(Error::InvalidWitness(e)) => assert_eq!(e, msg),
+            r => panic!("unexpected result {:?}", r),
+        };
+        is_invalid(&wtns(&witness, 5), "section 2 has 128 bytes, expected 160");
+        let mut zkey = file.clone();
+        zkey[..4].copy_from_slice(b"zkey");
+        is_invalid(&zkey, "not a wtns file");
+        let mut out_of_range = file.clone();
+        let len = out_of_range.len();
+        out_of_range[len - 32..].copy_from_slice(&[0xff; 32]);
+        is_invalid(&out_of_range, "witness value out of range");
+    }
+
+    fn snarkjs_vk(vk: &VerifyingKey<Bn254>) -> SnarkJSVK {
+        let fq =
+            |f: &Fq| num_bigint::BigUint::from_bytes_le(&f.into_repr().to_bytes_le()).to_string();
+        let fq2 = |f: &Fq2| vec![fq(&f.c0), fq(&f.c1)];
+        let g1 = |p: &G1Affine| vec![fq(&p.x), fq(&p.y), "1".to_string()];
+        let g2 = |p: &G2Affine| vec![fq2(&p.x), fq2(&p.y), vec!["1".to_string(), "0".to_string()]];
+        let fq6 = |f: &Fq6| vec![fq2(&f.c0), fq2(&f.c1), fq2(&f.c2)];
+        let alphabeta = Bn254::pairing(vk.alpha_g1, vk.beta_g2);
+        SnarkJSVK {
+            curve: "bn128".to_string(),
+            protocol: "groth16".to_string(),
+            n_public: vk.gamma_abc_g1.len() as i32 - 1,
+            vk_alpha_1: g1(&vk.alpha_g1),
+            vk_beta_2: g2(&vk.beta_g2),
+            vk_gamma_2: g2(&vk.gamma_g2),
+            vk_delta_2: g2(&vk.delta_g2),
+            vk_alphabeta_12: vec![fq6(&alphabeta.c0), fq6(&alphabeta.c1)],
+            ic: vk.gamma_abc_g1.iter().map(g1).collect(),
+        }
+    }
+
+    #[test]
+    fn test_check_verification_key() {
+        let file = bin_file(b"zkey", &zkey_sections());
+        let zkey = ZKey::read(&mut Cursor::new(&file)).unwrap();
+        let vk = snarkjs_vk(&zkey.proving_key.vk);
+        zkey.check_verification_key(&vk).unwrap();
+
+        // vk_alphabeta_12 is laid out as Fq12 in snarkjs
+        let json = std::fs::read_to_string("tests/secret/000/verification_key.json").unwrap();
+        let json: SnarkJSVK = serde_json::from_str(&json).unwrap();
+        let alphabeta = Bn254::pairing(
+            parse_g1(&json.vk_alpha_1).unwrap(),
+            parse_g2(&json.vk_beta_2).unwrap(),
+        );
+        assert_eq!(parse_fq12(&json.vk_alphabeta_12), Some(alphabeta));
+
+        let mut wrong = vk.clone();
+        wrong.n_public = 3;
+        wrong.vk_delta_2 = vk.vk_gamma_2.clone();
+        wrong.vk_alphabeta_12[1][2][0] = "1".to_string();
+        wrong.ic.pop();
+        wrong.ic[0][1] = "2".to_string();
+        match zkey.check_verification_key(&wrong) {
+            Err(Error::VerificationKeyMismatch(m)) => assert_eq!(
+                m,
+                vec![
+                    "nPublic is 3, expected 2",
+                    "vk_delta_2 differs",
+                    "vk_alphabeta_12 differs",
+                    "IC has 2 points, expected 3",
+                    "IC[0] differs",
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_write_zkey() {
+        // a zkey is written back as it was read
+        let file = bin_file(b"zkey", &zkey_sections());
+        let zkey = ZKey::read(&mut Cursor::new(&file)).unwrap();
+        let mut out = Vec::new();
+        zkey.write(&mut out).unwrap();
+        assert_eq!(out, file);
+
+        let mut out = Vec::new();
+        write_zkey(&mut out, &zkey.proving_key, &zkey.matrices).unwrap();
+        let (pk, m) = read_zkey(&mut Cursor::new(&out)).unwrap();
+        assert_eq!(pk, zkey.proving_key);
+        assert_eq!(m, zkey.matrices);
+        let written = ZKey::read(&mut Cursor::new(&out)).unwrap();
+        assert!(written.mpc.contributions.is_empty());
+
+        let mut m = zkey.matrices;
+        m.a_num_non_zero += 1;
+        write_zkey(&mut Vec::new(), &zkey.proving_key, &m).expect_err("wrong number of terms");
+        let mut pk = zkey.proving_key;
+        pk.h_query.pop();
+        pk.h_query.pop();
+        write_zkey(&mut Vec::new(), &pk, &m).expect_err("H query too short");
+    }
+
+    #[derive(Clone)]
+    struct MulCircuit {
+        a: Option<Fr>,
+        b: Option<Fr>,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.new_witness_variable(|| self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.new_input_variable(|| {
+                Ok(self.a.ok_or(SynthesisError::AssignmentMissing)?
+                    * self.b.ok_or(SynthesisError::AssignmentMissing)?)
+            })?;
+            let d = cs.new_input_variable(|| {
+                Ok(self.a.ok_or(SynthesisError::AssignmentMissing)?
+                    + self.b.ok_or(SynthesisError::AssignmentMissing)?)
+            })?;
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)?;
+            cs.enforce_constraint(lc!() + a + b, lc!() + Variable::One, lc!() + d)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_arkworks_zkey() {
+        let rng = &mut test_rng();
+        let circuit = MulCircuit { a: None, b: None };
+        let pk = generate_random_parameters::<Bn254, _, _>(circuit.clone(), rng).unwrap();
+        let cs = ConstraintSystem::new_ref();
+        cs.set_mode(SynthesisMode::Setup);
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.finalize();
+        let mut matrices = cs.to_matrices().unwrap();
+
+        let mut out = Vec::new();
+        write_zkey(&mut out, &pk, &matrices).unwrap();
+        let zkey = ZKey::read(&mut Cursor::new(&out)).unwrap();
+        assert_eq!(zkey.header.n_vars, 5);
+        assert_eq!(zkey.header.n_public, 2);
+        assert_eq!(zkey.header.domain_size, 8);
+        matrices.c = vec![];
+        matrices.c_num_non_zero = 0;
+        assert_eq!(zkey.matrices, matrices);
+        let mut expected = pk.clone();
+        expected.h_query = zkey.proving_key.h_query.clone();
+        assert_eq!(zkey.proving_key, expected);
+
+        // the snarkjs H query applied to the evaluations of any quotient on
+        // the coset, multiplied by Z = -2, gives the Arkworks H query applied
+        // to its coefficients
+        let n = zkey.header.domain_size as usize;
+        let h = (0..n - 1).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
+        let g = Radix2EvaluationDomain::<Fr>::new(2 * n).unwrap().element(1);
+        let domain = Radix2EvaluationDomain::<Fr>::new(n).unwrap();
+        let eval = |x: Fr| h.iter().rev().fold(Fr::zero(), |acc, c| acc * x + c);
+        let arkworks = pk
+            .h_query
+            .iter()
+            .zip(h.iter())
+            .map(|(p, c)| p.mul(*c))
+            .sum::<G1Projective>();
+        let snarkjs = zkey
+            .proving_key
+            .h_query
+            .iter()
+            .zip(domain.elements())
+            .map(|(p, w)| p.mul(-eval(g * w).double()))
+            .sum::<G1Projective>();
+        assert_eq!(arkworks, snarkjs);
+    }
+}
@@ -2,16 +2,19 @@ use ark_ec::msm::FixedBaseMSM;
 use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::PrimeField;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
-use ark_std::{rand::Rng, One, UniformRand};
+use ark_std::{
+    io::{Read, Write},
+    ops::MulAssign,
+    rand::Rng,
+    string::ToString,
+    vec::Vec,
+    One, UniformRand,
+};
 use sha2::{Digest, Sha256};
-use std::io::{Read, Write};
-
-use std::clone::Clone;
 
 use super::commitment::{VKey, WKey};
 use crate::Error;
 
-use std::ops::MulAssign;
 /// Maximum size of the generic SRS constructed from Filecoin and Zcash power of
 /// taus.
 ///
@@ -43,7 +46,7 @@ pub struct GenericSRS<E: PairingEngine> {
 /// aggregate. It contains as well the commitment keys for this specific size.
 /// Note the size must be a power of two for the moment - if it is not, padding must be
 /// applied.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
 pub struct ProverSRS<E: PairingEngine> {
     /// number of proofs to aggregate
     pub n: usize,
@@ -63,6 +66,9 @@ pub struct ProverSRS<E: PairingEngine> {
     pub vkey: VKey<E>,
     /// commitment key using in TIPP
     pub wkey: WKey<E>,
+    /// hash of the generic SRS this SRS is specialized from, see
+    /// `GenericSRS::hash`
+    pub srs_hash: Vec<u8>,
 }
 
 /// Contains the necessary elements to verify an aggregated Groth16 proof; it is of fixed size
@@ -77,6 +83,93 @@ pub struct VerifierSRS<E: PairingEngine> {
     pub g_beta: E::G1Projective,
     pub h_alpha: E::G2Projective,
     pub h_beta: E::G2Projective,
+    /// hash of the generic SRS this SRS is specialized from, see
+    /// `GenericSRS::hash`
+    pub srs_hash: Vec<u8>,
+    /// $-h$ prepared once for the Miller loops of the KZG checks. It is not
+    /// serialized but computed when the SRS is specialized or read.
+    pub(crate) h_neg_prepared: E::G2Prepared,
+}
+
+/// The serialized fields of a `VerifierSRS`, in order.
+type VerifierSRSFields<E> = (
+    (
+        usize,
+        <E as PairingEngine>::G1Projective,
+        <E as PairingEngine>::G2Projective,
+        <E as PairingEngine>::G1Projective,
+    ),
+    (
+        <E as PairingEngine>::G1Projective,
+        <E as PairingEngine>::G2Projective,
+        <E as PairingEngine>::G2Projective,
+        Vec<u8>,
+    ),
+);
+
+impl<E: PairingEngine> VerifierSRS<E> {
+    fn from_fields(fields: VerifierSRSFields<E>) -> Self {
+        let ((n, g, h, g_alpha), (g_beta, h_alpha, h_beta, srs_hash)) = fields;
+        VerifierSRS {
+            n,
+            g,
+            h,
+            g_alpha,
+            g_beta,
+            h_alpha,
+            h_beta,
+            srs_hash,
+            h_neg_prepared: (-h).into_affine().into(),
+        }
+    }
+
+    fn fields(&self) -> VerifierSRSFields<E> {
+        (
+            (self.n, self.g, self.h, self.g_alpha),
+            (
+                self.g_beta,
+                self.h_alpha,
+                self.h_beta,
+                self.srs_hash.clone(),
+            ),
+        )
+    }
+}
+
+impl<E: PairingEngine> CanonicalSerialize for VerifierSRS<E> {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.fields().serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.fields().serialized_size()
+    }
+
+    fn serialize_uncompressed<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.fields().serialize_uncompressed(writer)
+    }
+
+    fn serialize_unchecked<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.fields().serialize_unchecked(writer)
+    }
+
+    fn uncompressed_size(&self) -> usize {
+        self.fields().uncompressed_size()
+    }
+}
+
+impl<E: PairingEngine> CanonicalDeserialize for VerifierSRS<E> {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        VerifierSRSFields::<E>::deserialize(reader).map(Self::from_fields)
+    }
+
+    fn deserialize_uncompressed<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        VerifierSRSFields::<E>::deserialize_uncompressed(reader).map(Self::from_fields)
+    }
+
+    fn deserialize_unchecked<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        VerifierSRSFields::<E>::deserialize_unchecked(reader).map(Self::from_fields)
+    }
 }
 
 impl<E: PairingEngine> PartialEq for GenericSRS<E> {
@@ -96,6 +189,7 @@ impl<E: PairingEngine> PartialEq for VerifierSRS<E> {
             && self.g_beta == other.g_beta
             && self.h_alpha == other.h_alpha
             && self.h_beta == other.h_beta
+            && self.srs_hash == other.srs_hash
     }
 }
 
@@ -107,6 +201,43 @@ impl<E: PairingEngine> ProverSRS<E> {
     pub fn has_correct_len(&self, n: usize) -> bool {
         self.vkey.has_correct_len(n) && self.wkey.has_correct_len(n)
     }
+
+    /// Writes the prover SRS to the given destination so it can be reused
+    /// across aggregations without specializing the generic SRS again.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        self.serialize(&mut writer).map_err(Error::Serialization)
+    }
+
+    /// Reads a prover SRS previously written with `write`.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let pk = Self::deserialize(&mut reader).map_err(Error::Serialization)?;
+        if pk.n > MAX_SRS_SIZE {
+            return Err(Error::InvalidSRS("SRS len > maximum".to_string()));
+        }
+        if !pk.has_correct_len(pk.n) {
+            return Err(Error::InvalidSRS(
+                "commitment keys of wrong size".to_string(),
+            ));
+        }
+        Ok(pk)
+    }
+}
+
+impl<E: PairingEngine> VerifierSRS<E> {
+    /// Writes the verifier SRS to the given destination, so it can be shipped
+    /// to verifiers that do not hold the generic SRS (e.g. a browser).
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        self.serialize(&mut writer).map_err(Error::Serialization)
+    }
+
+    /// Reads a verifier SRS previously written with `write`.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let vk = Self::deserialize(&mut reader).map_err(Error::Serialization)?;
+        if vk.n > MAX_SRS_SIZE {
+            return Err(Error::InvalidSRS("SRS len > maximum".to_string()));
+        }
+        Ok(vk)
+    }
 }
 
 impl<E: PairingEngine> GenericSRS<E> {
@@ -134,6 +265,7 @@ impl<E: PairingEngine> GenericSRS<E> {
         let h_alpha_powers_table = self.h_alpha_powers[h_low..h_up].to_vec();
         let h_beta_powers_table = self.h_beta_powers[h_low..h_up].to_vec();
 
+        #[cfg(feature = "std")]
         println!(
             "\nPROVER SRS -- nun_proofs {}, tn {}, alpha_power_table {}\n",
             num_proofs,
@@ -151,6 +283,7 @@ impl<E: PairingEngine> GenericSRS<E> {
         let w2 = self.g_beta_powers[n..g_up].to_vec();
         let wkey = WKey::<E> { a: w1, b: w2 };
         assert!(wkey.has_correct_len(n));
+        let srs_hash = self.hash();
         let pk = ProverSRS::<E> {
             g_alpha_powers_table,
             g_beta_powers_table,
@@ -159,16 +292,22 @@ impl<E: PairingEngine> GenericSRS<E> {
             vkey,
             wkey,
             n,
+            srs_hash: srs_hash.clone(),
         };
-        let vk = VerifierSRS::<E> {
-            n: n,
-            g: self.g_alpha_powers[0].into_projective(),
-            h: self.h_alpha_powers[0].into_projective(),
-            g_alpha: self.g_alpha_powers[1].into_projective(),
-            g_beta: self.g_beta_powers[1].into_projective(),
-            h_alpha: self.h_alpha_powers[1].into_projective(),
-            h_beta: self.h_beta_powers[1].into_projective(),
-        };
+        let vk = VerifierSRS::<E>::from_fields((
+            (
+                n,
+                self.g_alpha_powers[0].into_projective(),
+                self.h_alpha_powers[0].into_projective(),
+                self.g_alpha_powers[1].into_projective(),
+            ),
+            (
+                self.g_beta_powers[1].into_projective(),
+                self.h_alpha_powers[1].into_projective(),
+                self.h_beta_powers[1].into_projective(),
+                srs_hash,
+            ),
+        ));
         (pk, vk)
     }
 
@@ -217,15 +356,15 @@ impl<E: PairingEngine> GenericSRS<E> {
     }
 
     pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
-        let len = u32::deserialize(&mut reader).map_err(|e| Error::Serialization(e))?;
+        let len = u32::deserialize(&mut reader).map_err(Error::Serialization)?;
         if len > MAX_SRS_SIZE as u32 {
             return Err(Error::InvalidSRS("SRS len > maximum".to_string()));
         }
 
-        let g_alpha_powers = read_vec(len, &mut reader).map_err(|e| Error::Serialization(e))?;
-        let g_beta_powers = read_vec(len, &mut reader).map_err(|e| Error::Serialization(e))?;
-        let h_alpha_powers = read_vec(len, &mut reader).map_err(|e| Error::Serialization(e))?;
-        let h_beta_powers = read_vec(len, &mut reader).map_err(|e| Error::Serialization(e))?;
+        let g_alpha_powers = read_vec(len, &mut reader).map_err(Error::Serialization)?;
+        let g_beta_powers = read_vec(len, &mut reader).map_err(Error::Serialization)?;
+        let h_alpha_powers = read_vec(len, &mut reader).map_err(Error::Serialization)?;
+        let h_beta_powers = read_vec(len, &mut reader).map_err(Error::Serialization)?;
 
         Ok(Self {
             g_alpha_powers,
@@ -244,34 +383,16 @@ pub fn setup_fake_srs<E: PairingEngine, R: Rng>(rng: &mut R, size: usize) -> Gen
     let g = E::G1Projective::prime_subgroup_generator();
     let h = E::G2Projective::prime_subgroup_generator();
 
-    let mut g_alpha_powers = Vec::new();
-    let mut g_beta_powers = Vec::new();
-    let mut h_alpha_powers = Vec::new();
-    let mut h_beta_powers = Vec::new();
-    rayon::scope(|s| {
-        let alpha = &alpha;
-        let h = &h;
-        let g = &g;
-        let beta = &beta;
-        let g_alpha_powers = &mut g_alpha_powers;
-        s.spawn(move |_| {
-            *g_alpha_powers = structured_generators_scalar_power(2 * size, g, alpha);
-        });
-        let g_beta_powers = &mut g_beta_powers;
-        s.spawn(move |_| {
-            *g_beta_powers = structured_generators_scalar_power(2 * size, g, beta);
-        });
-
-        let h_alpha_powers = &mut h_alpha_powers;
-        s.spawn(move |_| {
-            *h_alpha_powers = structured_generators_scalar_power(2 * size, h, alpha);
-        });
-
-        let h_beta_powers = &mut h_beta_powers;
-        s.spawn(move |_| {
-            *h_beta_powers = structured_generators_scalar_power(2 * size, h, beta);
-        });
-    });
+    let alpha = &alpha;
+    let beta = &beta;
+    let g = &g;
+    let h = &h;
+    par! {
+        let g_alpha_powers = structured_generators_scalar_power(2 * size, g, alpha),
+        let g_beta_powers = structured_generators_scalar_power(2 * size, g, beta),
+        let h_alpha_powers = structured_generators_scalar_power(2 * size, h, alpha),
+        let h_beta_powers = structured_generators_scalar_power(2 * size, h, beta)
+    };
 
     debug_assert!(h_alpha_powers[0] == E::G2Affine::prime_subgroup_generator());
     debug_assert!(h_beta_powers[0] == E::G2Affine::prime_subgroup_generator());
@@ -299,7 +420,7 @@ pub(crate) fn structured_generators_scalar_power<G: ProjectiveCurve>(
     }
     let scalar_bits = G::ScalarField::size_in_bits();
     let window_size = FixedBaseMSM::get_mul_window_size(num);
-    let g_table = FixedBaseMSM::get_window_table::<G>(scalar_bits, window_size, g.clone());
+    let g_table = FixedBaseMSM::get_window_table::<G>(scalar_bits, window_size, *g);
     let powers_of_g = FixedBaseMSM::multi_scalar_mul::<G>(
         //let powers_of_g = msm::fixed_base::multi_scalar_mul::<G>(
         scalar_bits,
@@ -328,8 +449,8 @@ fn read_vec<G: CanonicalDeserialize, R: Read>(
 mod test {
     use super::*;
     use ark_bn254::Bn254;
+    use ark_std::io::Cursor;
     use rand_core::SeedableRng;
-    use std::io::Cursor;
 
     #[test]
     fn test_srs_invalid_length() {
@@ -358,4 +479,24 @@ mod test {
         GenericSRS::<Bn254>::read(&mut Cursor::new(&new_buffer))
             .expect_err("this should have failed");
     }
+
+    #[test]
+    fn test_specialized_srs_io() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let srs = setup_fake_srs::<Bn254, _>(&mut rng, 4);
+        let (pk, vk) = srs.specialize(4);
+        let mut buffer = Vec::new();
+        vk.write(&mut buffer).expect("writing to buffer failed");
+        let out = VerifierSRS::<Bn254>::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(vk, out);
+
+        let mut buffer = Vec::new();
+        pk.write(&mut buffer).expect("writing to buffer failed");
+        let out = ProverSRS::<Bn254>::read(&mut Cursor::new(&buffer)).unwrap();
+        assert_eq!(pk.n, out.n);
+        assert_eq!(pk.g_alpha_powers_table, out.g_alpha_powers_table);
+        assert_eq!(pk.vkey.a, out.vkey.a);
+        assert_eq!(pk.wkey.b, out.wkey.b);
+        assert_eq!(pk.srs_hash, srs.hash());
+    }
 }
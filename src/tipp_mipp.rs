@@ -0,0 +1,199 @@
+//! Standalone TIPP and MIPP arguments over vectors supplied by the caller.
+//!
+//! For vectors $A \in G_1^n$, $B \in G_2^n$ and $C \in G_1^n$ committed with
+//! the `VKey` and `WKey` of the SRS, `prove` shows that
+//! * $Z_{AB} = \prod_{i=0}^{n-1} e(A_i, B_i)^{r^i}$ (TIPP)
+//! * $Z_C = \sum_{i=0}^{n-1} r^i C_i$ (MIPP)
+//!
+//! where $r$ is derived from the commitments with the transcript. Both
+//! arguments share the same GIPA challenges, as in the Groth16 aggregation,
+//! and the proof is an `AggregateProof` whose `com_ab`, `com_c`, `ip_ab` and
+//! `agg_c` are the statement. `prove_tipp` and `prove_mipp` prove one relation
+//! alone by setting the vectors of the other one to the identity.
+//!
+//! For example, BLS signatures $\sigma_i$ over messages $m_i$ with public keys
+//! $pk_i \in G_2$ are all valid, with overwhelming probability, if the proof for
+//! $A_i = H(m_i)$, $B_i = pk_i$ and $C_i = \sigma_i$ verifies and
+//! $Z_{AB} = e(Z_C, h)$.
+use ark_ec::{PairingEngine, ProjectiveCurve};
+use ark_ff::{One, Zero};
+use ark_std::{fmt::Debug, rand::Rng, string::ToString, vec, vec::Vec};
+
+use crate::{
+    commitment::{self, Output},
+    proof::AggregateProof,
+    prover::{aggregate_committed, check_nproofs},
+    srs::{ProverSRS, VerifierSRS},
+    transcript::{AggregateTranscript, Transcript},
+    verifier::verify_tipp_mipp,
+    Error, Mutex,
+};
+
+/// Proves the TIPP relation between `a` and `b` and the MIPP relation of `c`.
+/// The vectors must have as many elements as the SRS has been specialized
+/// for. It returns the proof as well as the randomness $r$.
+pub fn prove<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+    c: Vec<E::G1Affine>,
+) -> Result<(AggregateProof<E>, E::Fr), Error> {
+    check_nproofs(srs, a.len())?;
+    if b.len() != a.len() || c.len() != a.len() {
+        return Err(Error::InvalidProof(
+            "vectors have different lengths".to_string(),
+        ));
+    }
+    let (refa, refb, refc) = (&a, &b, &c);
+    try_par! {
+        let com_ab = commitment::pair::<E>(&srs.vkey, &srs.wkey, refa, refb),
+        let com_c = commitment::single_g1::<E>(&srs.vkey, refc)
+    };
+    aggregate_committed(srs, transcript, a, b, c, com_ab, com_c)
+}
+
+/// Proves the TIPP relation between `a` and `b` only.
+pub fn prove_tipp<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    a: Vec<E::G1Affine>,
+    b: Vec<E::G2Affine>,
+) -> Result<(AggregateProof<E>, E::Fr), Error> {
+    let c = vec![E::G1Affine::zero(); a.len()];
+    prove(srs, transcript, a, b, c)
+}
+
+/// Proves the MIPP relation of `c` only.
+pub fn prove_mipp<E: PairingEngine + Debug, T: Transcript>(
+    srs: &ProverSRS<E>,
+    transcript: &mut T,
+    c: Vec<E::G1Affine>,
+) -> Result<(AggregateProof<E>, E::Fr), Error> {
+    let a = vec![E::G1Affine::zero(); c.len()];
+    let b = vec![E::G2Affine::zero(); c.len()];
+    prove(srs, transcript, a, b, c)
+}
+
+/// Verifies a proof created with `prove` and returns the randomness $r$. The
+/// caller must check that `com_ab` and `com_c` are the commitments to its
+/// vectors and that `ip_ab` and `agg_c` satisfy its relation.
+pub fn verify<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
+    v_srs: &VerifierSRS<E>,
+    proof: &AggregateProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<E::Fr, Error> {
+    proof.parsing_check()?;
+    if proof.tmipp.gipa.nproofs as usize > v_srs.n {
+        return Err(Error::InvalidSRS("SRS len < proofs len".to_string()));
+    }
+    let r = AggregateTranscript::<E>::r_challenge(transcript, &proof.com_ab, &proof.com_c);
+    let rng = Mutex::new(rng);
+    match verify_tipp_mipp(v_srs, proof, None, &r, transcript, &rng).verify() {
+        Ok(()) => Ok(r),
+        Err(_) => Err(Error::InvalidProof("Proof Verification Failed".to_string())),
+    }
+}
+
+/// Verifies a proof created with `prove_tipp` for the vectors committed in
+/// `com_ab` and returns the randomness $r$.
+pub fn verify_tipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
+    v_srs: &VerifierSRS<E>,
+    com_ab: &Output<E::Fqk>,
+    proof: &AggregateProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<E::Fr, Error> {
+    if &proof.com_ab != com_ab {
+        return Err(Error::InvalidProof("invalid commitment".to_string()));
+    }
+    if proof.com_c != Output(E::Fqk::one(), E::Fqk::one()) || !proof.agg_c.is_zero() {
+        return Err(Error::InvalidProof("unexpected MIPP relation".to_string()));
+    }
+    verify(v_srs, proof, rng, transcript)
+}
+
+/// Verifies a proof created with `prove_mipp` for the vector committed in
+/// `com_c` and returns the randomness $r$.
+pub fn verify_mipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
+    v_srs: &VerifierSRS<E>,
+    com_c: &Output<E::Fqk>,
+    proof: &AggregateProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<E::Fr, Error> {
+    if &proof.com_c != com_c {
+        return Err(Error::InvalidProof("invalid commitment".to_string()));
+    }
+    if proof.com_ab != Output(E::Fqk::one(), E::Fqk::one()) || !proof.ip_ab.is_one() {
+        return Err(Error::InvalidProof("unexpected TIPP relation".to_string()));
+    }
+    verify(v_srs, proof, rng, transcript)
+}
+
+/// Returns $\sum_{i=0}^{n-1} r^i C_i$, the value the MIPP argument proves, for
+/// callers checking the relation against their own vector.
+pub fn mipp_value<E: PairingEngine>(c: &[E::G1Affine], r: &E::Fr) -> Result<E::G1Affine, Error> {
+    let powers = crate::structured_scalar_power(c.len(), r);
+    crate::ip::multiexponentiation::<E::G1Affine>(c, &powers).map(|v| v.into_affine())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{srs::setup_fake_srs, transcript::new_merlin_transcript};
+    use ark_bn254::{Bn254, Fr, G1Projective, G2Affine};
+    use ark_ec::AffineCurve;
+    use ark_std::UniformRand;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_tipp_mipp_bls_relation() {
+        let n = 8;
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let (p_srs, v_srs) = setup_fake_srs::<Bn254, _>(&mut rng, n).specialize(n);
+        let h = G2Affine::prime_subgroup_generator();
+        // B_i = h^{s_i} and C_i = A_i^{s_i} so that e(A_i, B_i) = e(C_i, h)
+        let (mut a, mut b, mut c) = (Vec::new(), Vec::new(), Vec::new());
+        for _ in 0..n {
+            let ai = G1Projective::rand(&mut rng).into_affine();
+            let si = Fr::rand(&mut rng);
+            a.push(ai);
+            b.push(h.mul(si).into_affine());
+            c.push(ai.mul(si).into_affine());
+        }
+
+        let mut transcript = new_merlin_transcript(b"test tipp mipp");
+        let (proof, r) = prove(&p_srs, &mut transcript, a.clone(), b.clone(), c.clone()).unwrap();
+        let mut transcript = new_merlin_transcript(b"test tipp mipp");
+        let vr = verify(&v_srs, &proof, &mut rng, &mut transcript).unwrap();
+        assert_eq!(r, vr);
+        assert_eq!(proof.ip_ab, Bn254::pairing(proof.agg_c, h));
+        assert_eq!(proof.agg_c, mipp_value::<Bn254>(&c, &r).unwrap());
+
+        let mut wrong = proof.clone();
+        wrong.agg_c = a[0];
+        let mut transcript = new_merlin_transcript(b"test tipp mipp");
+        verify(&v_srs, &wrong, &mut rng, &mut transcript).expect_err("invalid MIPP value");
+
+        let com_ab = commitment::pair::<Bn254>(&p_srs.vkey, &p_srs.wkey, &a, &b).unwrap();
+        let mut transcript = new_merlin_transcript(b"test tipp");
+        let (tipp, _) = prove_tipp(&p_srs, &mut transcript, a.clone(), b).unwrap();
+        let mut transcript = new_merlin_transcript(b"test tipp");
+        verify_tipp(&v_srs, &com_ab, &tipp, &mut rng, &mut transcript).unwrap();
+        let mut transcript = new_merlin_transcript(b"test tipp");
+        verify_tipp(&v_srs, &proof.com_c, &tipp, &mut rng, &mut transcript)
+            .expect_err("wrong commitment");
+
+        let com_c = commitment::single_g1::<Bn254>(&p_srs.vkey, &c).unwrap();
+        let mut transcript = new_merlin_transcript(b"test mipp");
+        let (mipp, r) = prove_mipp(&p_srs, &mut transcript, c.clone()).unwrap();
+        let mut transcript = new_merlin_transcript(b"test mipp");
+        verify_mipp(&v_srs, &com_c, &mipp, &mut rng, &mut transcript).unwrap();
+        assert_eq!(mipp.agg_c, mipp_value::<Bn254>(&c, &r).unwrap());
+        let mut transcript = new_merlin_transcript(b"test mipp");
+        verify_tipp(&v_srs, &com_ab, &mipp, &mut rng, &mut transcript)
+            .expect_err("not a TIPP proof");
+    }
+}
@@ -1,6 +1,14 @@
+use ark_ec::PairingEngine;
 use ark_ff::fields::Field;
+use ark_groth16::VerifyingKey;
 use ark_serialize::CanonicalSerialize;
+use ark_std::{cfg_chunks, cfg_iter, vec, vec::Vec};
 use merlin::Transcript as Merlin;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{commitment::Output, Error, GipaProof};
 
 /// must be specific to the application.
 pub fn new_merlin_transcript(label: &'static [u8]) -> impl Transcript {
@@ -12,8 +20,20 @@ pub fn new_merlin_transcript(label: &'static [u8]) -> impl Transcript {
 /// prover/verifier so that the transcript can be fed with any other data first.
 pub trait Transcript {
     fn domain_sep(&mut self);
-    fn append<S: CanonicalSerialize>(&mut self, label: &'static [u8], point: &S);
+    fn append<S: CanonicalSerialize + ?Sized>(&mut self, label: &'static [u8], point: &S);
     fn challenge_scalar<F: Field>(&mut self, label: &'static [u8]) -> F;
+
+    /// Appends the digest of the public inputs returned by
+    /// `public_inputs_digest`. The inputs are hashed in parallel, which makes
+    /// it the cheap way to include them in the transcript compared to
+    /// appending them, see `cargo bench --bench inputs_digest`.
+    fn append_inputs_digest<F: CanonicalSerialize + Sync>(
+        &mut self,
+        label: &'static [u8],
+        public_inputs: &[Vec<F>],
+    ) {
+        self.append(label, &public_inputs_digest(public_inputs)[..]);
+    }
 }
 
 impl Transcript for Merlin {
@@ -21,7 +41,7 @@ impl Transcript for Merlin {
         self.append_message(b"dom-sep", b"groth16-aggregation-snarkpack");
     }
 
-    fn append<S: CanonicalSerialize>(&mut self, label: &'static [u8], element: &S) {
+    fn append<S: CanonicalSerialize + ?Sized>(&mut self, label: &'static [u8], element: &S) {
         let mut buff: Vec<u8> = vec![0; element.serialized_size()];
         element.serialize(&mut buff).expect("serialization failed");
         self.append_message(label, &buff);
@@ -35,7 +55,7 @@ impl Transcript for Merlin {
         loop {
             match F::from_random_bytes(&buf) {
                 Some(e) => {
-                    if let Some(_) = e.inverse() {
+                    if e.inverse().is_some() {
                         return e;
                     } else {
                         continue;
@@ -52,6 +72,223 @@ impl Transcript for Merlin {
     }
 }
 
+/// Values sent by the prover in one round of GIPA, as stored in `GipaProof`.
+pub(crate) struct GipaRound<'a, E: PairingEngine> {
+    pub comms_ab: &'a (Output<E::Fqk>, Output<E::Fqk>),
+    pub comms_c: &'a (Output<E::Fqk>, Output<E::Fqk>),
+    pub z_ab: &'a (E::Fqk, E::Fqk),
+    pub z_c: &'a (E::G1Affine, E::G1Affine),
+    /// commitments and values of D, for proofs with an extra commitment
+    #[allow(clippy::type_complexity)]
+    pub d: Option<(
+        &'a (Output<E::Fqk>, Output<E::Fqk>),
+        &'a (E::G1Affine, E::G1Affine),
+    )>,
+}
+
+/// AggregateTranscript derives the Fiat-Shamir challenges of the aggregation
+/// itself. It is implemented by every `Transcript` and, for BLS12-381, by the
+/// transcript of bellperson so its aggregate proofs can be checked here.
+pub(crate) trait AggregateTranscript<E: PairingEngine> {
+    /// Returns the randomness $r$ used to combine the proofs.
+    fn r_challenge(&mut self, com_ab: &Output<E::Fqk>, com_c: &Output<E::Fqk>) -> E::Fr;
+
+    /// Returns the inverse of the challenge of the first round of GIPA.
+    /// `agg_d` is $D^r$ for proofs with an extra commitment.
+    fn first_challenge(
+        &mut self,
+        ip_ab: &E::Fqk,
+        agg_c: &E::G1Affine,
+        agg_d: Option<&E::G1Affine>,
+        r_shift: &E::Fr,
+    ) -> E::Fr;
+
+    /// Returns the inverse of the challenge of the round `i` of GIPA, where
+    /// `c_inv` is the one of the previous round or the first challenge.
+    fn round_challenge(&mut self, i: usize, c_inv: &E::Fr, round: GipaRound<E>) -> E::Fr;
+
+    /// Returns the point at which the final commitment keys are opened,
+    /// from the challenge of the last round of GIPA and the GIPA proof, which
+    /// holds the keys.
+    fn kzg_challenge(&mut self, c: &E::Fr, gipa: &GipaProof<E>) -> E::Fr;
+}
+
+impl<E: PairingEngine, T: Transcript> AggregateTranscript<E> for T {
+    fn r_challenge(&mut self, com_ab: &Output<E::Fqk>, com_c: &Output<E::Fqk>) -> E::Fr {
+        self.append(b"AB-commitment", com_ab);
+        self.append(b"C-commitment", com_c);
+        self.challenge_scalar::<E::Fr>(b"r-random-fiatshamir")
+    }
+
+    fn first_challenge(
+        &mut self,
+        ip_ab: &E::Fqk,
+        agg_c: &E::G1Affine,
+        agg_d: Option<&E::G1Affine>,
+        _: &E::Fr,
+    ) -> E::Fr {
+        self.append(b"inner-product-ab", ip_ab);
+        self.append(b"comm-c", agg_c);
+        if let Some(agg_d) = agg_d {
+            self.append(b"comm-d", agg_d);
+        }
+        self.challenge_scalar::<E::Fr>(b"first-challenge")
+    }
+
+    fn round_challenge(&mut self, i: usize, c_inv: &E::Fr, round: GipaRound<E>) -> E::Fr {
+        if i == 0 {
+            // the first challenge is used for the first round
+            return *c_inv;
+        }
+        self.append(b"c_inv", c_inv);
+        self.append(b"zab_l", &round.z_ab.0);
+        self.append(b"zab_r", &round.z_ab.1);
+        self.append(b"zc_l", &round.z_c.0);
+        self.append(b"zc_r", &round.z_c.1);
+        self.append(b"tab_l", &round.comms_ab.0);
+        self.append(b"tab_r", &round.comms_ab.1);
+        self.append(b"tuc_l", &round.comms_c.0);
+        self.append(b"tuc_r", &round.comms_c.1);
+        if let Some((comms_d, z_d)) = round.d {
+            self.append(b"zd_l", &z_d.0);
+            self.append(b"zd_r", &z_d.1);
+            self.append(b"tud_l", &comms_d.0);
+            self.append(b"tud_r", &comms_d.1);
+        }
+        self.challenge_scalar::<E::Fr>(b"challenge_i")
+    }
+
+    fn kzg_challenge(&mut self, c: &E::Fr, gipa: &GipaProof<E>) -> E::Fr {
+        self.append(b"kzg-challenge", c);
+        self.append(b"vkey0", &gipa.final_vkey.0);
+        self.append(b"vkey1", &gipa.final_vkey.1);
+        self.append(b"wkey0", &gipa.final_wkey.0);
+        self.append(b"wkey1", &gipa.final_wkey.1);
+        self.challenge_scalar::<E::Fr>(b"z-challenge")
+    }
+}
+
+/// Number of public inputs hashed together in a chunk of `inputs_leaf`.
+const INPUTS_PER_CHUNK: usize = 1024;
+
+/// Returns a digest of the public inputs of all proofs. The canonical encoding
+/// of the public inputs of one proof is split in chunks of a fixed number of
+/// inputs hashed with SHA-256, and the leaf of the proof is the hash of the
+/// number of inputs and of the chunk hashes. Leaves are then hashed pairwise in
+/// a Merkle tree, so the work is spread across cores whether there are many
+/// proofs or a few proofs with many inputs. The root is finally hashed
+/// together with the number of proofs.
+pub fn public_inputs_digest<F: CanonicalSerialize + Sync>(public_inputs: &[Vec<F>]) -> [u8; 32] {
+    let leaves = cfg_iter!(public_inputs)
+        .map(|inputs| inputs_leaf(inputs))
+        .collect::<Vec<_>>();
+    digest_from_leaves(leaves)
+}
+
+/// Returns the leaf of `public_inputs_digest` for the public inputs of one
+/// proof.
+pub(crate) fn inputs_leaf<F: CanonicalSerialize + Sync>(inputs: &[F]) -> [u8; 32] {
+    let chunks = cfg_chunks!(inputs, INPUTS_PER_CHUNK)
+        .map(|chunk| {
+            let mut buff = Vec::with_capacity(chunk.iter().map(|f| f.serialized_size()).sum());
+            chunk
+                .iter()
+                .for_each(|f| f.serialize(&mut buff).expect("serialization failed"));
+            let mut hasher = Sha256::new();
+            hasher.update(b"chunk");
+            hasher.update(&buff);
+            hasher.finalize().into()
+        })
+        .collect::<Vec<[u8; 32]>>();
+    let mut hasher = Sha256::new();
+    hasher.update(b"leaf");
+    hasher.update((inputs.len() as u64).to_le_bytes());
+    chunks.iter().for_each(|chunk| hasher.update(chunk));
+    hasher.finalize().into()
+}
+
+/// Returns `public_inputs_digest` from the leaves of all the proofs.
+pub(crate) fn digest_from_leaves(leaves: Vec<[u8; 32]>) -> [u8; 32] {
+    let nleaves = leaves.len() as u64;
+    let mut level = leaves;
+    while level.len() > 1 {
+        level = cfg_chunks!(level, 2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update(b"node");
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize().into()
+                }
+                // an odd node is carried to the next level as is
+                _ => pair[0],
+            })
+            .collect();
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(b"root");
+    hasher.update(nleaves.to_le_bytes());
+    hasher.update(level.first().copied().unwrap_or_default());
+    hasher.finalize().into()
+}
+
+/// Returns the SHA-256 hash of the compressed encoding of a Groth16 verifying
+/// key.
+pub fn verifying_key_hash<E: PairingEngine>(vk: &VerifyingKey<E>) -> Result<[u8; 32], Error> {
+    let mut buff = Vec::with_capacity(vk.serialized_size());
+    vk.serialize(&mut buff)?;
+    Ok(Sha256::digest(&buff).into())
+}
+
+/// Specifies whether `aggregate_and_bind` and `verify_bound` absorb the public
+/// inputs of the aggregated proofs in the transcript.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputsBinding {
+    /// All public inputs of all proofs are absorbed. This is the safe choice.
+    #[default]
+    Absorb,
+    /// Public inputs are NOT absorbed. This is only sound if ALL the public
+    /// inputs are **fixed, and public before the aggregation time**, as for
+    /// Filecoin.
+    Fixed,
+}
+
+/// Appends to the transcript the statement an aggregate proof is bound to: the
+/// hash of the Groth16 verifying key, the hash of the generic SRS, the number
+/// of proofs and, depending on `binding`, the digest of all the public inputs.
+pub fn bind_statement<E: PairingEngine, T: Transcript>(
+    transcript: &mut T,
+    vk: &VerifyingKey<E>,
+    srs_hash: &[u8],
+    public_inputs: &[Vec<E::Fr>],
+    binding: InputsBinding,
+) -> Result<(), Error> {
+    let digest = match binding {
+        InputsBinding::Absorb => Some(public_inputs_digest(public_inputs)),
+        InputsBinding::Fixed => None,
+    };
+    append_statement(transcript, vk, srs_hash, public_inputs.len(), digest)
+}
+
+/// Same as `bind_statement` with the digest of the public inputs already
+/// computed, or `None` if they are not bound.
+pub(crate) fn append_statement<E: PairingEngine, T: Transcript>(
+    transcript: &mut T,
+    vk: &VerifyingKey<E>,
+    srs_hash: &[u8],
+    nproofs: usize,
+    inputs_digest: Option<[u8; 32]>,
+) -> Result<(), Error> {
+    transcript.append(b"vk-hash", &verifying_key_hash(vk)?[..]);
+    transcript.append(b"srs-hash", srs_hash);
+    transcript.append(b"nproofs", &(nproofs as u32));
+    if let Some(digest) = inputs_digest {
+        transcript.append(b"public-inputs-digest", &digest[..]);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -68,4 +305,38 @@ mod test {
         let f2 = transcript2.challenge_scalar::<Fr>(b"scalar");
         assert_eq!(f1, f2);
     }
+
+    #[test]
+    fn inputs_digest() {
+        let inputs = (0..5u64)
+            .map(|i| vec![Fr::from(i), Fr::from(i + 1)])
+            .collect::<Vec<_>>();
+        let d = public_inputs_digest(&inputs);
+        assert_eq!(d, public_inputs_digest(&inputs.clone()));
+
+        let mut swapped = inputs.clone();
+        swapped.swap(0, 4);
+        assert!(d != public_inputs_digest(&swapped));
+
+        let mut changed = inputs.clone();
+        changed[3][1] = Fr::from(42u64);
+        assert!(d != public_inputs_digest(&changed));
+
+        // the split of the inputs between proofs is bound as well
+        let flat = vec![inputs.concat()];
+        assert!(d != public_inputs_digest(&flat));
+        assert!(d != public_inputs_digest(&inputs[..4]));
+
+        // inputs spanning several chunks are all bound
+        let many = vec![(0..2 * INPUTS_PER_CHUNK as u64 + 1)
+            .map(Fr::from)
+            .collect::<Vec<_>>()];
+        let d = public_inputs_digest(&many);
+        let mut changed = many.clone();
+        changed[0][INPUTS_PER_CHUNK + 1] = Fr::from(0u64);
+        assert!(d != public_inputs_digest(&changed));
+        let mut shorter = many.clone();
+        shorter[0].pop();
+        assert!(d != public_inputs_digest(&shorter));
+    }
 }
@@ -1,29 +1,67 @@
 use ark_ec::{msm::VariableBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
 use ark_ff::{Field, PrimeField};
 use ark_groth16::PreparedVerifyingKey;
-use ark_std::{rand::Rng, sync::Mutex, One, Zero};
-use crossbeam_channel::{bounded, Sender};
+use ark_std::{
+    borrow::Cow,
+    cfg_into_iter, cfg_iter, end_timer,
+    fmt::Debug,
+    ops::{AddAssign, MulAssign, Neg, SubAssign},
+    rand::Rng,
+    start_timer,
+    string::ToString,
+    vec,
+    vec::Vec,
+    One, Zero,
+};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use std::ops::{AddAssign, MulAssign, Neg, SubAssign};
 
 use super::{
     commitment::Output,
     ip,
     pairing_check::PairingCheck,
-    proof::{AggregateProof, KZGOpening},
+    proof::{
+        AggregateProof, AggregateProofWithCommitments, CommittedInputsProof, InputsCommitment,
+        KZGOpening,
+    },
     prover::polynomial_evaluation_product_form_from_transcript,
     srs::VerifierSRS,
     structured_scalar_power,
-    transcript::Transcript,
+    transcript::{bind_statement, AggregateTranscript, GipaRound, InputsBinding, Transcript},
 };
-use crate::Error;
+use crate::{Error, Mutex};
 
-use std::default::Default;
-use std::time::Instant;
+/// Verifies an aggregate proof created with `aggregate_and_bind`: the
+/// statement is appended to the transcript in the same way, with the same
+/// `binding`, before verifying the proof.
+pub fn verify_bound<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: Transcript + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    binding: InputsBinding,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    bind_statement(
+        transcript,
+        &pvk.pvk().vk,
+        &ip_verifier_srs.srs_hash,
+        public_inputs,
+        binding,
+    )?;
+    verify_aggregate_proof(ip_verifier_srs, pvk, public_inputs, proof, rng, transcript)
+}
 
 /// Verifies the aggregated proofs thanks to the Groth16 verifying key, the
 /// verifier SRS from the aggregation scheme, all the public inputs of the
 /// proofs and the aggregated proof.
+/// See `verify_bound` for a version binding the statement automatically.
 ///
 /// WARNING: transcript_include represents everything that should be included in
 /// the transcript from outside the boundary of this function. This is especially
@@ -35,22 +73,148 @@ use std::time::Instant;
 /// number of proofs and public inputs (+100ms in our case). In the case of Filecoin, the only
 /// non-fixed part of the public inputs are the challenges derived from a seed. Even though this
 /// seed comes from a random beeacon, we are hashing this as a safety precaution.
+/// See `Transcript::append_inputs_digest` to include them.
 pub fn verify_aggregate_proof<
-    E: PairingEngine + std::fmt::Debug,
+    E: PairingEngine + Debug,
     R: Rng + Send,
     T: Transcript + Send,
+    K: VerifierKey<E>,
 >(
     ip_verifier_srs: &VerifierSRS<E>,
-    pvk: &PreparedVerifyingKey<E>,
+    pvk: &K,
     public_inputs: &[Vec<E::Fr>],
     proof: &AggregateProof<E>,
     rng: R,
-    mut transcript: &mut T,
+    transcript: &mut T,
 ) -> Result<(), Error> {
-    dbg!("verify_aggregate_proof");
+    verify_with_transcript(
+        ip_verifier_srs,
+        pvk,
+        public_inputs,
+        proof,
+        AlphaBeta::fastest(),
+        rng,
+        transcript,
+    )
+}
+
+/// A Groth16 verifying key prepared for the verifier. The Miller loop of
+/// `AlphaBeta::MillerLoop` needs $\beta$ prepared, which Arkworks'
+/// `PreparedVerifyingKey` does not hold, so it prepares $\beta$ on each
+/// verification while `PreparedBetaKey` prepares it once.
+pub trait VerifierKey<E: PairingEngine>: Sync {
+    /// Returns the prepared verifying key.
+    fn pvk(&self) -> &PreparedVerifyingKey<E>;
+
+    /// Returns $\beta$ of the verifying key prepared for the Miller loop.
+    fn beta_g2_prepared(&self) -> Cow<'_, E::G2Prepared>;
+}
+
+impl<E: PairingEngine> VerifierKey<E> for PreparedVerifyingKey<E> {
+    fn pvk(&self) -> &PreparedVerifyingKey<E> {
+        self
+    }
+
+    fn beta_g2_prepared(&self) -> Cow<'_, E::G2Prepared> {
+        Cow::Owned(self.vk.beta_g2.into())
+    }
+}
+
+/// A prepared Groth16 verifying key along with its $\beta$ prepared, to verify
+/// many aggregate proofs against the same key, see `VerifierKey`.
+#[derive(Clone, Debug)]
+pub struct PreparedBetaKey<E: PairingEngine> {
+    pub pvk: PreparedVerifyingKey<E>,
+    pub beta_g2_pc: E::G2Prepared,
+}
+
+impl<E: PairingEngine> From<PreparedVerifyingKey<E>> for PreparedBetaKey<E> {
+    fn from(pvk: PreparedVerifyingKey<E>) -> Self {
+        let beta_g2_pc = pvk.vk.beta_g2.into();
+        PreparedBetaKey { pvk, beta_g2_pc }
+    }
+}
+
+impl<E: PairingEngine> VerifierKey<E> for PreparedBetaKey<E> {
+    fn pvk(&self) -> &PreparedVerifyingKey<E> {
+        &self.pvk
+    }
+
+    fn beta_g2_prepared(&self) -> Cow<'_, E::G2Prepared> {
+        Cow::Borrowed(&self.beta_g2_pc)
+    }
+}
+
+/// How the $e(\alpha,\beta)^{\sum r}$ term of the aggregated Groth16 equation
+/// is computed by the verifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaBeta {
+    /// A Miller loop on $\alpha^{\sum r}$ and $\beta$, whose final
+    /// exponentiation is shared with the other pairings.
+    MillerLoop,
+    /// An exponentiation in $G_T$ of `alpha_g1_beta_g2` of the prepared
+    /// verifying key, e.g. the `vk_alphabeta_12` of snarkjs, see
+    /// `get_prepared_verifying_key`. No operation is done in $G_2$.
+    Precomputed,
+}
+
+impl AlphaBeta {
+    /// Returns the fastest way to compute the term. `benches/alphabeta.rs`
+    /// shows the Miller loop to be faster on both BN254 and BLS12-381, so it
+    /// does not depend on the curve.
+    pub fn fastest() -> Self {
+        AlphaBeta::MillerLoop
+    }
+}
+
+/// Same as `verify_aggregate_proof`, with the given computation of the
+/// $e(\alpha,\beta)^{\sum r}$ term instead of the fastest one. The result
+/// is the same as long as `alpha_g1_beta_g2` is the pairing of $\alpha$ and
+/// $\beta$ of the verifying key.
+pub fn verify_aggregate_proof_with_alphabeta<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: Transcript + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    alphabeta: AlphaBeta,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    verify_with_transcript(
+        ip_verifier_srs,
+        pvk,
+        public_inputs,
+        proof,
+        alphabeta,
+        rng,
+        transcript,
+    )
+}
+
+/// Same as `verify_aggregate_proof` with any derivation of the challenges.
+pub(crate) fn verify_with_transcript<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: AggregateTranscript<E> + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    alphabeta: AlphaBeta,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let timer = start_timer!(|| "verify_aggregate_proof");
     proof.parsing_check()?;
     for pub_input in public_inputs {
-        if (pub_input.len() + 1) != pvk.vk.gamma_abc_g1.len() {
+        if (pub_input.len() + 1) != pvk.pvk().vk.gamma_abc_g1.len() {
             return Err(Error::MalformedVerifyingKey);
         }
     }
@@ -62,172 +226,463 @@ pub fn verify_aggregate_proof<
     }
 
     let mut_rng = Mutex::new(rng);
+    let (acc, _) = aggregate_checks(
+        ip_verifier_srs,
+        pvk,
+        alphabeta,
+        Inputs::All(public_inputs),
+        None,
+        proof,
+        &mut_rng,
+        transcript,
+    );
+    let res = acc.verify();
+    end_timer!(timer);
+    res.map_err(|_| Error::InvalidProof("Proof Verification Failed".to_string()))
+}
+
+/// Verifies proofs aggregated with `aggregate_proofs_with_commitments`: the
+/// Groth16 equation is checked with the aggregated commitments D added to the
+/// public inputs term. As for `verify_aggregate_proof`, the caller is
+/// responsible for including the public inputs in the transcript.
+pub fn verify_aggregate_proof_with_commitments<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: Transcript + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProofWithCommitments<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let timer = start_timer!(|| "verify_aggregate_proof_with_commitments");
+    let aggregate = &proof.aggregate;
+    aggregate.parsing_check()?;
+    // D is folded in the same GIPA loop so it has a value for each round
+    let rounds = aggregate.tmipp.gipa.comms_c.len();
+    if proof.gipa_d.comms_d.len() != rounds || proof.gipa_d.z_d.len() != rounds {
+        return Err(Error::InvalidProof(
+            "Proof vectors unequal sizes".to_string(),
+        ));
+    }
+    for pub_input in public_inputs {
+        if (pub_input.len() + 1) != pvk.pvk().vk.gamma_abc_g1.len() {
+            return Err(Error::MalformedVerifyingKey);
+        }
+    }
+    if public_inputs.len() != aggregate.tmipp.gipa.nproofs as usize {
+        return Err(Error::InvalidProof(
+            "public inputs len != number of proofs".to_string(),
+        ));
+    }
+
+    let mut_rng = Mutex::new(rng);
+    transcript.append(b"D-commitment", &proof.com_d);
+    let (acc, _) = aggregate_checks(
+        ip_verifier_srs,
+        pvk,
+        AlphaBeta::fastest(),
+        Inputs::All(public_inputs),
+        Some(proof),
+        aggregate,
+        &mut_rng,
+        transcript,
+    );
+    let res = acc.verify();
+    end_timer!(timer);
+    res.map_err(|_| Error::InvalidProof("Proof Verification Failed".to_string()))
+}
+
+/// Public inputs of a batch of proofs in which some inputs have the same value
+/// in all proofs, such as a common Merkle root. The shared values are only
+/// given once and the verifier does not iterate over the proofs for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedInputs<E: PairingEngine> {
+    /// position in the public inputs of a proof and value of each input
+    /// shared by all proofs, by increasing position
+    pub shared: Vec<(usize, E::Fr)>,
+    /// the other public inputs of each proof, in order
+    pub varying: Vec<Vec<E::Fr>>,
+}
+
+impl<E: PairingEngine> SharedInputs<E> {
+    /// Splits the public inputs of all proofs into the inputs at the given
+    /// positions, which must have the same value in all proofs, and the
+    /// other ones.
+    pub fn from_inputs(public_inputs: &[Vec<E::Fr>], positions: &[usize]) -> Result<Self, Error> {
+        let first = public_inputs
+            .first()
+            .ok_or_else(|| Error::InvalidProof("no public inputs".to_string()))?;
+        if positions.windows(2).any(|w| w[0] >= w[1]) || positions.iter().any(|i| *i >= first.len())
+        {
+            return Err(Error::InvalidProof(
+                "invalid shared inputs positions".to_string(),
+            ));
+        }
+        if public_inputs
+            .iter()
+            .any(|inputs| positions.iter().any(|i| inputs.get(*i) != Some(&first[*i])))
+        {
+            return Err(Error::InvalidProof(
+                "shared public inputs differ between proofs".to_string(),
+            ));
+        }
+        let shared = positions.iter().map(|i| (*i, first[*i])).collect();
+        let varying = public_inputs
+            .iter()
+            .map(|inputs| {
+                inputs
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| positions.binary_search(i).is_err())
+                    .map(|(_, input)| *input)
+                    .collect()
+            })
+            .collect();
+        Ok(Self { shared, varying })
+    }
+}
+
+/// Verifies an aggregate proof as `verify_aggregate_proof` does, given public
+/// inputs some of which are shared by all the proofs. As for
+/// `verify_aggregate_proof`, the caller is responsible for including the
+/// public inputs in the transcript.
+pub fn verify_with_shared_inputs<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: Transcript + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    inputs: &SharedInputs<E>,
+    proof: &AggregateProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let timer = start_timer!(|| "verify_with_shared_inputs");
+    proof.parsing_check()?;
+    let l = pvk.pvk().vk.gamma_abc_g1.len() - 1;
+    if inputs.shared.windows(2).any(|w| w[0].0 >= w[1].0)
+        || inputs.shared.iter().any(|(i, _)| *i >= l)
+        || inputs
+            .varying
+            .iter()
+            .any(|row| row.len() + inputs.shared.len() != l)
+    {
+        return Err(Error::MalformedVerifyingKey);
+    }
+    if inputs.varying.len() != proof.tmipp.gipa.nproofs as usize {
+        return Err(Error::InvalidProof(
+            "public inputs len != number of proofs".to_string(),
+        ));
+    }
+
+    let mut_rng = Mutex::new(rng);
+    let (acc, _) = aggregate_checks(
+        ip_verifier_srs,
+        pvk,
+        AlphaBeta::fastest(),
+        Inputs::Shared(inputs),
+        None,
+        proof,
+        &mut_rng,
+        transcript,
+    );
+    let res = acc.verify();
+    end_timer!(timer);
+    res.map_err(|_| Error::InvalidProof("Proof Verification Failed".to_string()))
+}
 
+/// Verifies an aggregate proof created with `aggregate_with_committed_inputs`
+/// against the commitment to the public inputs. The verifier does not need the
+/// public inputs themselves and its work only depends on the number of inputs
+/// of one proof, not on the number of proofs.
+pub fn verify_with_committed_inputs<
+    E: PairingEngine + Debug,
+    R: Rng + Send,
+    T: Transcript + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    commitment: &InputsCommitment<E>,
+    proof: &CommittedInputsProof<E>,
+    rng: R,
+    transcript: &mut T,
+) -> Result<(), Error> {
+    let timer = start_timer!(|| "verify_with_committed_inputs");
+    proof.aggregate.parsing_check()?;
+    let l = pvk.pvk().vk.gamma_abc_g1.len() - 1;
+    if commitment.columns.len() != l || proof.evaluations.len() != l {
+        return Err(Error::MalformedVerifyingKey);
+    }
+    if proof.aggregate.tmipp.gipa.nproofs as usize > ip_verifier_srs.n {
+        return Err(Error::InvalidSRS("SRS len < proofs len".to_string()));
+    }
+
+    let mut_rng = Mutex::new(rng);
+    transcript.append(b"inputs-commitment", &commitment.columns);
+    let (mut acc, r) = aggregate_checks(
+        ip_verifier_srs,
+        pvk,
+        AlphaBeta::fastest(),
+        Inputs::Evaluations(&proof.evaluations),
+        None,
+        &proof.aggregate,
+        &mut_rng,
+        transcript,
+    );
+
+    // the evaluations of all columns are checked with a single opening of
+    // their random linear combination
+    transcript.append(b"inputs-evaluations", &proof.evaluations);
+    let gamma = transcript.challenge_scalar::<E::Fr>(b"inputs-challenge");
+    let gammas = structured_scalar_power(l, &gamma);
+    let combined = ip::multiexponentiation::<E::G1Affine>(&commitment.columns, &gammas)?;
+    let y = proof
+        .evaluations
+        .iter()
+        .zip(gammas.iter())
+        .map(|(y, g)| *y * g)
+        .sum::<E::Fr>();
+    acc.merge(&kzg_check_w::<E, R>(
+        ip_verifier_srs,
+        &ip_verifier_srs.h_neg_prepared,
+        r,
+        y,
+        combined,
+        ip_verifier_srs.h_alpha,
+        proof.opening,
+        &mut_rng,
+    ));
+    let res = acc.verify();
+    end_timer!(timer);
+    res.map_err(|_| Error::InvalidProof("Proof Verification Failed".to_string()))
+}
+
+/// Public inputs of the aggregated proofs, as given to the verifier.
+enum Inputs<'a, E: PairingEngine> {
+    /// all the public inputs of all proofs
+    All(&'a [Vec<E::Fr>]),
+    /// the evaluation at r of the polynomial of each input column, whose
+    /// coefficients are the inputs at this position in each proof
+    Evaluations(&'a [E::Fr]),
+    /// inputs with the same value in all proofs given only once
+    Shared(&'a SharedInputs<E>),
+}
+
+/// Returns the pairing checks of the aggregate proof, merged together but not
+/// verified yet, as well as the randomness used to combine the proofs. `d` is
+/// the proof with an extra commitment `proof` is part of, if any.
+#[allow(clippy::too_many_arguments)]
+fn aggregate_checks<
+    E: PairingEngine,
+    R: Rng + Send,
+    T: AggregateTranscript<E> + Send,
+    K: VerifierKey<E>,
+>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &K,
+    alphabeta: AlphaBeta,
+    inputs: Inputs<E>,
+    d: Option<&AggregateProofWithCommitments<E>>,
+    proof: &AggregateProof<E>,
+    rng: &Mutex<R>,
+    transcript: &mut T,
+) -> (PairingCheck<E>, E::Fr) {
+    let agg_d = d.map(|d| &d.agg_d);
     // Random linear combination of proofs
-    transcript.append(b"AB-commitment", &proof.com_ab);
-    transcript.append(b"C-commitment", &proof.com_c);
-    let r = transcript.challenge_scalar::<E::Fr>(b"r-random-fiatshamir");
-
-    // channels to send/recv pairing checks so we aggregate them all in a
-    // loop - 9 places where we send pairing checks
-    let (send_checks, rcv_checks) = bounded(9);
-    // channel to receive the final results so aggregate waits on all.
-    let (valid_send, valid_rcv) = bounded(1);
-    rayon::scope(move |s| {
-        // Continuous loop that aggregate pairing checks together
-        s.spawn(move |_| {
-            let mut acc = PairingCheck::new();
-            while let Ok(tuple) = rcv_checks.recv() {
-                acc.merge(&tuple);
-            }
-            valid_send.send(acc.verify()).unwrap();
-        });
+    let r = transcript.r_challenge(&proof.com_ab, &proof.com_c);
 
+    par! {
         // 1.Check TIPA proof ab
         // 2.Check TIPA proof c
-        let checkclone = send_checks.clone();
-        s.spawn(move |_| {
-            let now = Instant::now();
-            verify_tipp_mipp::<E, R, T>(
-                ip_verifier_srs,
-                proof,
-                &r, // we give the extra r as it's not part of the proof itself - it is simply used on top for the groth16 aggregation
-                &mut transcript,
-                &mut_rng,
-                checkclone,
-            );
-            dbg!("TIPP took {} ms", now.elapsed().as_millis(),);
-        });
-
+        let tipp_mipp = verify_tipp_mipp::<E, R, T>(
+            ip_verifier_srs,
+            proof,
+            d,
+            &r, // we give the extra r as it's not part of the proof itself - it is simply used on top for the groth16 aggregation
+            transcript,
+            rng,
+        ),
         // Check aggregate pairing product equation
-        // SUM of a geometric progression
-        // SUM a^i = (1 - a^n) / (1 - a) = -(1-a^n)/-(1-a)
-        // = (a^n - 1) / (a - 1)
-        dbg!("checking aggregate pairing");
-        let mut r_sum = r.pow(&[public_inputs.len() as u64]);
-        r_sum.sub_assign(&E::Fr::one());
-        let b = sub!(r, &E::Fr::one()).inverse().unwrap();
-        r_sum.mul_assign(&b);
-
-        // The following parts 3 4 5 are independently computing the parts of
-        // the Groth16 verification equation NOTE From this point on, we are
-        // only checking *one* pairing check (the Groth16 verification equation)
-        // so we don't need to randomize as all other checks are being
-        // randomized already. When merging all pairing checks together, this
-        // will be the only one non-randomized.
-        //
-        let (r_vec_sender, r_vec_receiver) = bounded(1);
-        //        s.spawn(move |_| {
-        let now = Instant::now();
-        r_vec_sender
-            .send(structured_scalar_power(public_inputs.len(), &r))
-            .unwrap();
-        let elapsed = now.elapsed().as_millis();
-        dbg!("generation of r vector: {}ms", elapsed);
-        //        });
-
-        par! {
-            // 3. Compute left part of the final pairing equation
-            let left = {
-                let alpha_g1_r_suma = pvk.vk.alpha_g1;
-                let alpha_g1_r_sum = alpha_g1_r_suma.mul(r_sum);
-
-                E::miller_loop([&(E::G1Prepared::from(alpha_g1_r_sum.into()), E::G2Prepared::from(pvk.vk.beta_g2.into()))])
-
-            },
-            // 4. Compute right part of the final pairing equation
-            let right = {
-                E::miller_loop([&(
-                    // e(c^r vector form, h^delta)
-                    E::G1Prepared::from(proof.agg_c),
-                    E::G2Prepared::from(pvk.vk.delta_g2),
-                )])
-            },
-            // 5. compute the middle part of the final pairing equation, the one
-            //    with the public inputs
-            let middle = {
-                    // We want to compute MUL(i:0 -> l) S_i ^ (SUM(j:0 -> n) ai,j * r^j)
-                    // this table keeps tracks of incremental computation of each i-th
-                    // exponent to later multiply with S_i
-                    // The index of the table is i, which is an index of the public
-                    // input element
-                    // We incrementally build the r vector and the table
-                    // NOTE: in this version it's not r^2j but simply r^j
-
-                    let l = public_inputs[0].len();
-                    let mut g_ic = pvk.vk.gamma_abc_g1[0].into_projective();
-                    g_ic.mul_assign(r_sum);
-
-                    let powers = r_vec_receiver.recv().unwrap();
-
-                    let now = Instant::now();
-                    // now we do the multi exponentiation
-                    let summed = (0..l).into_par_iter().map(|i| {
-                        // i denotes the column of the public input, and j denotes which public input
-                        let mut c = public_inputs[0][i];
-                        for j in 1..public_inputs.len() {
-                            let mut ai = public_inputs[j][i];
-                            ai.mul_assign(&powers[j]);
-                            c.add_assign(&ai);
-                        }
-                        c.into_repr()
-                    }).collect::<Vec<_>>();
-
-                    let totsi = VariableBaseMSM::multi_scalar_mul(&pvk.vk.gamma_abc_g1[1..],&summed);
-
-                    g_ic.add_assign(&totsi);
-
-                    let ml = E::miller_loop([&(E::G1Prepared::from(g_ic.into_affine()), E::G2Prepared::from(pvk.vk.gamma_g2.clone()))]);
-                    let elapsed = now.elapsed().as_millis();
-                    dbg!("table generation: {}ms", elapsed);
-
-                    ml
+        let groth16 = verify_groth16_equation(pvk, alphabeta, &inputs, agg_d, proof, &r)
+    };
+
+    // all checks are merged together so only one final exponentiation is
+    // needed
+    let mut acc = tipp_mipp;
+    acc.merge(&groth16);
+    (acc, r)
+}
+
+/// Returns the pairing check corresponding to the aggregated Groth16 equation
+/// $e(A,B)^r = e(\alpha,\beta)^{\sum r}e(\sum_j S_j^{\sum_i a_{i,j}r^i}, \gamma)e(C^r,\delta)$.
+/// For proofs with an extra commitment D, $D^r$ is added to the $\gamma$ term.
+fn verify_groth16_equation<E: PairingEngine, K: VerifierKey<E>>(
+    pvk: &K,
+    alphabeta: AlphaBeta,
+    inputs: &Inputs<E>,
+    agg_d: Option<&E::G1Affine>,
+    proof: &AggregateProof<E>,
+    r: &E::Fr,
+) -> PairingCheck<E> {
+    let key = pvk;
+    let pvk = key.pvk();
+    // SUM of a geometric progression
+    // SUM a^i = (1 - a^n) / (1 - a) = -(1-a^n)/-(1-a)
+    // = (a^n - 1) / (a - 1)
+    let mut r_sum = r.pow([proof.tmipp.gipa.nproofs as u64]);
+    r_sum.sub_assign(&E::Fr::one());
+    let b = sub!(*r, &E::Fr::one()).inverse().unwrap();
+    r_sum.mul_assign(&b);
+
+    // The following parts 3 4 5 are independently computing the parts of
+    // the Groth16 verification equation NOTE From this point on, we are
+    // only checking *one* pairing check (the Groth16 verification equation)
+    // so we don't need to randomize as all other checks are being
+    // randomized already. When merging all pairing checks together, this
+    // will be the only one non-randomized.
+    //
+    let r_sum = &r_sum;
+
+    par! {
+        // 3. Compute left part of the final pairing equation
+        //    either as a miller loop or directly in the target group, where
+        //    it is moved to the right side of the equation
+        let left = match alphabeta {
+            AlphaBeta::MillerLoop => {
+                let alpha_g1_r_sum = pvk.vk.alpha_g1.mul(*r_sum);
+                E::miller_loop([&(E::G1Prepared::from(alpha_g1_r_sum.into_affine()), key.beta_g2_prepared().into_owned())])
             }
-        };
-        // final value ip_ab is what we want to compare in the groth16
-        // aggregated equation A * B
-        let check = PairingCheck::from_products(vec![left, middle, right], proof.ip_ab.clone());
-        send_checks.send(check).unwrap();
-    });
-    let res = valid_rcv.recv().unwrap();
-    dbg!(format!("aggregate verify done: valid ? {}", res));
-    match res {
-        true => Ok(()),
-        false => Err(Error::InvalidProof("Proof Verification Failed".to_string())),
+            AlphaBeta::Precomputed => pvk.alpha_g1_beta_g2.pow((-*r_sum).into_repr()),
+        },
+        // 4. Compute right part of the final pairing equation
+        let right = {
+            // e(c^r vector form, h^delta) = e(-c^r, h^-delta), with the
+            //  prepared -delta of the verifying key
+            E::miller_loop([&(
+                E::G1Prepared::from(proof.agg_c.neg()),
+                pvk.delta_g2_neg_pc.clone(),
+            )])
+        },
+        // 5. compute the middle part of the final pairing equation, the one
+        //    with the public inputs
+        let middle = {
+                // We want to compute MUL(i:0 -> l) S_i ^ (SUM(j:0 -> n) ai,j * r^j)
+                // this table keeps tracks of incremental computation of each i-th
+                // exponent to later multiply with S_i
+                // The index of the table is i, which is an index of the public
+                // input element
+                // We incrementally build the r vector and the table
+                // NOTE: in this version it's not r^2j but simply r^j
+
+                let mut g_ic = pvk.pvk().vk.gamma_abc_g1[0].into_projective();
+                g_ic.mul_assign(*r_sum);
+
+                let table_timer = start_timer!(|| "table generation");
+                // now we do the multi exponentiation
+                let summed = match inputs {
+                    Inputs::All(public_inputs) => {
+                        let l = public_inputs[0].len();
+                        let r_vec_timer = start_timer!(|| "generation of r vector");
+                        let powers = structured_scalar_power(public_inputs.len(), r);
+                        end_timer!(r_vec_timer);
+                        cfg_into_iter!(0..l).map(|i| {
+                            // i denotes the column of the public input, and j denotes which public input
+                            let mut c = public_inputs[0][i];
+                            for j in 1..public_inputs.len() {
+                                let mut ai = public_inputs[j][i];
+                                ai.mul_assign(&powers[j]);
+                                c.add_assign(&ai);
+                            }
+                            c.into_repr()
+                        }).collect::<Vec<_>>()
+                    }
+                    // the prover has already computed the sums, which are
+                    // checked against the commitment to the inputs
+                    Inputs::Evaluations(evaluations) => {
+                        cfg_iter!(evaluations).map(|c| c.into_repr()).collect::<Vec<_>>()
+                    }
+                    // the sum of the powers of r of a shared column is the same
+                    // geometric sum as for gamma_abc_g1[0]
+                    Inputs::Shared(inputs) => {
+                        let powers = structured_scalar_power(inputs.varying.len(), r);
+                        let m = inputs.varying[0].len();
+                        let mut varying = cfg_into_iter!(0..m).map(|k| {
+                            inputs
+                                .varying
+                                .iter()
+                                .zip(powers.iter())
+                                .map(|(row, power)| row[k] * power)
+                                .sum::<E::Fr>()
+                        }).collect::<Vec<_>>().into_iter();
+                        let mut shared = inputs.shared.iter().peekable();
+                        (0..m + inputs.shared.len()).map(|i| {
+                            let c = match shared.next_if(|(pos, _)| *pos == i) {
+                                Some((_, value)) => *value * r_sum,
+                                None => varying.next().unwrap(),
+                            };
+                            c.into_repr()
+                        }).collect::<Vec<_>>()
+                    }
+                };
+
+                let totsi = VariableBaseMSM::multi_scalar_mul(&pvk.pvk().vk.gamma_abc_g1[1..],&summed);
+
+                g_ic.add_assign(&totsi);
+                if let Some(agg_d) = agg_d {
+                    g_ic.add_assign_mixed(agg_d);
+                }
+
+                let ml = E::miller_loop([&(E::G1Prepared::from(g_ic.neg().into_affine()), pvk.gamma_g2_neg_pc.clone())]);
+                end_timer!(table_timer);
+
+                ml
+        }
+    };
+    // final value ip_ab is what we want to compare in the groth16
+    // aggregated equation A * B
+    match alphabeta {
+        AlphaBeta::MillerLoop => {
+            PairingCheck::from_products(vec![left, middle, right], proof.ip_ab)
+        }
+        AlphaBeta::Precomputed => {
+            PairingCheck::from_products(vec![middle, right], proof.ip_ab * left)
+        }
     }
 }
 
 /// verify_tipp_mipp returns a pairing equation to check the tipp proof.  $r$ is
 /// the randomness used to produce a random linear combination of A and B and
-/// used in the MIPP part with C
-fn verify_tipp_mipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
+/// used in the MIPP part with C, and with D if `d` is given.
+pub(crate) fn verify_tipp_mipp<
+    E: PairingEngine,
+    R: Rng + Send,
+    T: AggregateTranscript<E> + Send,
+>(
     v_srs: &VerifierSRS<E>,
     proof: &AggregateProof<E>,
+    d: Option<&AggregateProofWithCommitments<E>>,
     r_shift: &E::Fr,
     transcript: &mut T,
     rng: &Mutex<R>,
-    checks: Sender<PairingCheck<E>>,
-) {
-    dbg!("verify with srs shift");
-    let now = Instant::now();
+) -> PairingCheck<E> {
+    let timer = start_timer!(|| "TIPP verify: gipa verify tipp");
     // (T,U), Z for TIPP and MIPP  and all challenges
     let (final_res, final_r, challenges, challenges_inv) =
-        gipa_verify_tipp_mipp(&proof, r_shift, transcript);
-    dbg!(
-        "TIPP verify: gipa verify tipp {}ms",
-        now.elapsed().as_millis()
-    );
+        gipa_verify_tipp_mipp(proof, d, r_shift, transcript);
+    end_timer!(timer);
 
     // Verify commitment keys wellformed
     let fvkey = proof.tmipp.gipa.final_vkey;
     let fwkey = proof.tmipp.gipa.final_wkey;
     // KZG challenge point
-    transcript.append(b"kzg-challenge", &challenges[0]);
-    transcript.append(b"vkey0", &proof.tmipp.gipa.final_vkey.0);
-    transcript.append(b"vkey1", &proof.tmipp.gipa.final_vkey.1);
-    transcript.append(b"wkey0", &proof.tmipp.gipa.final_wkey.0);
-    transcript.append(b"wkey1", &proof.tmipp.gipa.final_wkey.1);
-    let c = transcript.challenge_scalar::<E::Fr>(b"z-challenge");
+    let c = transcript.kzg_challenge(&challenges[0], &proof.tmipp.gipa);
     // we take reference so they are able to be copied in the par! macro
     let final_a = &proof.tmipp.gipa.final_a;
     let final_b = &proof.tmipp.gipa.final_b;
@@ -237,36 +692,29 @@ fn verify_tipp_mipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
     let final_uab = &final_res.uab;
     let final_tc = &final_res.tc;
     let final_uc = &final_res.uc;
+    let (fvkey, fwkey) = (&fvkey, &fwkey);
+    let (challenges, challenges_inv, c) = (&challenges, &challenges_inv, &c);
 
-    let now = Instant::now();
-    let vclone = checks.clone();
-    let wclone = checks.clone();
-    let zclone = checks.clone();
-    let ab0clone = checks.clone();
-    let ab1clone = checks.clone();
-    let tclone = checks.clone();
-    let uclone = checks.clone();
+    let timer = start_timer!(|| "TIPP verify: parallel checks before merge");
     par! {
         // check the opening proof for v
-        let _vtuple = verify_kzg_v(
+        let vtuple = verify_kzg_v(
             v_srs,
-            &fvkey,
+            fvkey,
             &proof.tmipp.vkey_opening,
-            &challenges_inv,
-            &c,
+            challenges_inv,
+            c,
             rng,
-            vclone,
         ),
         // check the opening proof for w - note that w has been rescaled by $r^{-1}$
-        let _wtuple = verify_kzg_w(
+        let wtuple = verify_kzg_w(
             v_srs,
-            &fwkey,
+            fwkey,
             &proof.tmipp.wkey_opening,
-            &challenges,
+            challenges,
             &r_shift.inverse().unwrap(),
-            &c,
+            c,
             rng,
-            wclone,
         ),
         //
         // We create a sequence of pairing tuple that we aggregate together at
@@ -274,56 +722,54 @@ fn verify_tipp_mipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
         //
         // TIPP
         // z = e(A,B)
-        //let _check_z = zclone.send(PairingCheck::rand(&rng,&[(final_a, final_b)], final_zab)).unwrap(),
-        let pcheckz = PairingCheck::rand(&rng,&[(final_a, final_b)], final_zab),
+        let pcheckz = PairingCheck::rand(rng, &[(final_a, final_b)], final_zab),
         //  final_aB.0 = T = e(A,v1)e(w1,B)
-        //let check_ab0 = ab0clone.send(PairingCheck::rand(&rng,&[(final_a, &fvkey.0),(&fwkey.0, final_b)], final_tab)).unwrap(),
-        let pcheck_ab = PairingCheck::rand(&rng,&[(final_a, &fvkey.0),(&fwkey.0, final_b)], final_tab),
+        let pcheck_ab = PairingCheck::rand(rng, &[(final_a, &fvkey.0),(&fwkey.0, final_b)], final_tab),
 
         //  final_aB.1 = U = e(A,v2)e(w2,B)
-        //let _check_ab1 = ab1clone.send(PairingCheck::rand(&rng,&[(final_a, &fvkey.1),(&fwkey.1, final_b)], final_uab)).unwrap(),
-        let pcheckab2 = PairingCheck::rand(&rng,&[(final_a, &fvkey.1),(&fwkey.1, final_b)], final_uab),
+        let pcheckab2 = PairingCheck::rand(rng, &[(final_a, &fvkey.1),(&fwkey.1, final_b)], final_uab),
 
         // MIPP
         // Verify base inner product commitment
         // Z ==  c ^ r
         let final_z =
-            ip::multiexponentiation::<E::G1Affine>(&[final_c.clone()], &[final_r]),
+            ip::multiexponentiation::<E::G1Affine>(&[*final_c], &[final_r]),
         // Check commiment correctness
         // T = e(C,v1)
-        //let _check_t = tclone.send(PairingCheck::rand(&rng,&[(final_c,&fvkey.0)],final_tc)).unwrap(),
-        let pcheckt = PairingCheck::rand(&rng,&[(final_c,&fvkey.0)],final_tc),
+        let pcheckt = PairingCheck::rand(rng, &[(final_c, &fvkey.0)], final_tc),
         // U = e(A,v2)
-        //let _check_u = uclone.send(PairingCheck::rand(&rng,&[(final_c,&fvkey.1)],final_uc)).unwrap()
-        let pchecku = PairingCheck::rand(&rng,&[(final_c,&fvkey.1)],final_uc)
+        let pchecku = PairingCheck::rand(rng, &[(final_c, &fvkey.1)], final_uc)
     };
+    end_timer!(timer);
 
-    tclone.send(pcheckt).unwrap();
-    uclone.send(pchecku).unwrap();
-    ab0clone.send(pcheck_ab).unwrap();
-    ab1clone.send(pcheckab2).unwrap();
-    zclone.send(pcheckz).unwrap();
-    match final_z {
-        Err(e) => {
-            dbg!("TIPP verify: INVALID with multi exp: {}", e);
-            checks.send(PairingCheck::new_invalid()).unwrap();
-        }
-        Ok(z) => {
-            dbg!(format!(
-                "TIPP verify: parallel checks before merge: {}ms",
-                now.elapsed().as_millis()
-            ));
-            // only check that doesn't require pairing so we can give a tuple
-            // that will render the equation wrong in case it's false
-            if z != final_res.zc {
-                dbg!(format!(
-                    "tipp verify: INVALID final_z check {} vs {}",
-                    z, final_res.zc
-                ));
-                checks.send(PairingCheck::new_invalid()).unwrap()
-            }
+    let mut acc = vtuple;
+    for check in [wtuple, pcheckz, pcheck_ab, pcheckab2, pcheckt, pchecku].iter() {
+        acc.merge(check);
+    }
+    // MIPP for D, with the same final keys and r
+    if let Some(d) = d {
+        let final_d = &d.gipa_d.final_d;
+        if final_d.mul(final_r) != final_res.zd {
+            return PairingCheck::new_invalid();
         }
-    };
+        // T = e(D,v1), U = e(D,v2)
+        acc.merge(&PairingCheck::rand(
+            rng,
+            &[(final_d, &fvkey.0)],
+            &final_res.td,
+        ));
+        acc.merge(&PairingCheck::rand(
+            rng,
+            &[(final_d, &fvkey.1)],
+            &final_res.ud,
+        ));
+    }
+    match final_z {
+        // only check that doesn't require pairing so we can give a tuple
+        // that will render the equation wrong in case it's false
+        Ok(z) if z == final_res.zc => acc,
+        _ => PairingCheck::new_invalid(),
+    }
 }
 
 /// gipa_verify_tipp_mipp recurse on the proof and statement and produces the final
@@ -331,16 +777,18 @@ fn verify_tipp_mipp<E: PairingEngine, R: Rng + Send, T: Transcript + Send>(
 /// * T,U: the final commitment values of A and B
 /// * Z the final product between A and B.
 /// * Challenges are returned in inverse order as well to avoid
-/// repeating the operation multiple times later on.
+///   repeating the operation multiple times later on.
 /// * There are T,U,Z vectors as well for the MIPP relationship. Both TIPP and
-/// MIPP share the same challenges however, enabling to re-use common operations
-/// between them, such as the KZG proof for commitment keys.
-fn gipa_verify_tipp_mipp<E: PairingEngine, T: Transcript + Send>(
+///   MIPP share the same challenges however, enabling to re-use common operations
+///   between them, such as the KZG proof for commitment keys.
+/// * The same values are computed for D if `d` is given.
+#[allow(clippy::type_complexity)]
+fn gipa_verify_tipp_mipp<E: PairingEngine, T: AggregateTranscript<E> + Send>(
     proof: &AggregateProof<E>,
+    d: Option<&AggregateProofWithCommitments<E>>,
     r_shift: &E::Fr,
     transcript: &mut T,
 ) -> (GipaTUZ<E>, E::Fr, Vec<E::Fr>, Vec<E::Fr>) {
-    dbg!("gipa verify TIPP");
     let gipa = &proof.tmipp.gipa;
     // COM(A,B) = PROD e(A,B) given by prover
     let comms_ab = &gipa.comms_ab;
@@ -350,66 +798,50 @@ fn gipa_verify_tipp_mipp<E: PairingEngine, T: Transcript + Send>(
     let zs_ab = &gipa.z_ab;
     let zs_c = &gipa.z_c;
 
-    let now = Instant::now();
+    let timer = start_timer!(|| "TIPP verify: gipa challenge gen");
 
     let mut challenges = Vec::new();
     let mut challenges_inv = Vec::new();
 
-    transcript.append(b"inner-product-ab", &proof.ip_ab);
-    transcript.append(b"comm-c", &proof.agg_c);
-    let mut c_inv: E::Fr = transcript.challenge_scalar::<E::Fr>(b"first-challenge");
-    let mut c = c_inv.inverse().unwrap();
+    let agg_d = d.map(|d| &d.agg_d);
+    let mut c_inv: E::Fr = transcript.first_challenge(&proof.ip_ab, &proof.agg_c, agg_d, r_shift);
 
     // We first generate all challenges as this is the only consecutive process
     // that can not be parallelized then we scale the commitments in a
     // parallelized way
-    for (i, ((comm_ab, z_ab), (comm_c, z_c))) in comms_ab
+    for (i, ((comms_ab, z_ab), (comms_c, z_c))) in comms_ab
         .iter()
         .zip(zs_ab.iter())
         .zip(comms_c.iter().zip(zs_c.iter()))
         .enumerate()
     {
-        let (tab_l, tab_r) = comm_ab;
-        let (tuc_l, tuc_r) = comm_c;
-        let (zab_l, zab_r) = z_ab;
-        let (zc_l, zc_r) = z_c;
-
         // Fiat-Shamir challenge
-        if i == 0 {
-            // already generated c_inv and c outside of the loop
-        } else {
-            transcript.append(b"c_inv", &c_inv);
-            transcript.append(b"zab_l", zab_l);
-            transcript.append(b"zab_r", zab_r);
-            transcript.append(b"zc_l", zc_l);
-            transcript.append(b"zc_r", zc_r);
-            transcript.append(b"tab_l", tab_l);
-            transcript.append(b"tab_r", tab_r);
-            transcript.append(b"tuc_l", tuc_l);
-            transcript.append(b"tuc_r", tuc_r);
-            c_inv = transcript.challenge_scalar::<E::Fr>(b"challenge_i");
-            c = c_inv.inverse().unwrap();
-        }
+        let round = GipaRound {
+            comms_ab,
+            comms_c,
+            z_ab,
+            z_c,
+            d: d.map(|d| (&d.gipa_d.comms_d[i], &d.gipa_d.z_d[i])),
+        };
+        c_inv = transcript.round_challenge(i, &c_inv, round);
+        let c = c_inv.inverse().unwrap();
         challenges.push(c);
         challenges_inv.push(c_inv);
     }
 
-    dbg!(
-        "TIPP verify: gipa challenge gen took {}ms",
-        now.elapsed().as_millis()
-    );
+    end_timer!(timer);
 
-    let now = Instant::now();
+    let timer = start_timer!(|| "TIPP verify: gipa prep and accumulate");
     // output of the pair commitment T and U in TIPP -> COM((v,w),A,B)
     //let comab2 = proof.com_ab.clone();
     //let Output(t_ab, u_ab) = (comab2.0, comab2.1);
-    let Output { 0: t_ab, 1: u_ab } = proof.com_ab.clone();
+    let Output(t_ab, u_ab) = proof.com_ab.clone();
     let z_ab = proof.ip_ab; // in the end must be equal to Z = A^r * B
 
     // COM(v,C)
     //let comc2 = proof.com_c.clone();
     //let (t_c, u_c) = (comc2.0, comc2.1);
-    let Output { 0: t_c, 1: u_c } = proof.com_c.clone();
+    let Output(t_c, u_c) = proof.com_c.clone();
     let z_c = proof.agg_c.into_projective(); // in the end must be equal to Z = C^r
 
     let mut final_res = GipaTUZ {
@@ -419,7 +851,15 @@ fn gipa_verify_tipp_mipp<E: PairingEngine, T: Transcript + Send>(
         tc: t_c,
         uc: u_c,
         zc: z_c,
+        ..GipaTUZ::default()
     };
+    if let Some(d) = d {
+        // COM(v,D) and D^r
+        let Output(t_d, u_d) = d.com_d.clone();
+        final_res.td = t_d;
+        final_res.ud = u_d;
+        final_res.zd = d.agg_d.into_projective();
+    }
 
     // we first multiply each entry of the Z U and L vectors by the respective
     // challenges independently
@@ -427,79 +867,113 @@ fn gipa_verify_tipp_mipp<E: PairingEngine, T: Transcript + Send>(
     // multiply all of them in parrallel and then merge then back at the end.
     // same for u and z.
     enum Op<'a, E: PairingEngine> {
-        TAB(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
-        UAB(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
-        ZAB(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
+        Tab(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
+        Uab(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
+        Zab(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
         TC(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
         UC(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
         ZC(&'a E::G1Affine, <E::Fr as PrimeField>::BigInt),
+        TD(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
+        UD(&'a E::Fqk, <E::Fr as PrimeField>::BigInt),
+        ZD(&'a E::G1Affine, <E::Fr as PrimeField>::BigInt),
     }
 
-    let res = comms_ab
-        .par_iter()
-        .zip(zs_ab.par_iter())
-        .zip(comms_c.par_iter().zip(zs_c.par_iter()))
-        .zip(challenges.par_iter().zip(challenges_inv.par_iter()))
-        .flat_map(|(((comm_ab, z_ab), (comm_c, z_c)), (c, c_inv))| {
+    let ops = cfg_iter!(comms_ab)
+        .zip(cfg_iter!(zs_ab))
+        .zip(cfg_iter!(comms_c).zip(cfg_iter!(zs_c)))
+        .zip(cfg_iter!(challenges).zip(cfg_iter!(challenges_inv)))
+        .enumerate()
+        .flat_map(|(i, (((comm_ab, z_ab), (comm_c, z_c)), (c, c_inv)))| {
             // T and U values for right and left for AB part
-            let (Output { 0: tab_l, 1: uab_l }, Output { 0: tab_r, 1: uab_r }) = comm_ab;
+            let (Output(tab_l, uab_l), Output(tab_r, uab_r)) = comm_ab;
             let (zab_l, zab_r) = z_ab;
             // T and U values for right and left for C part
-            let (Output { 0: tc_l, 1: uc_l }, Output { 0: tc_r, 1: uc_r }) = comm_c;
+            let (Output(tc_l, uc_l), Output(tc_r, uc_r)) = comm_c;
             let (zc_l, zc_r) = z_c;
 
             let c_repr = c.into_repr();
             let c_inv_repr = c_inv.into_repr();
 
             // we multiple left side by x and right side by x^-1
-            vec![
-                Op::TAB::<E>(tab_l, c_repr),
-                Op::TAB(tab_r, c_inv_repr),
-                Op::UAB(uab_l, c_repr),
-                Op::UAB(uab_r, c_inv_repr),
-                Op::ZAB(zab_l, c_repr),
-                Op::ZAB(zab_r, c_inv_repr),
+            let mut ops = vec![
+                Op::Tab::<E>(tab_l, c_repr),
+                Op::Tab(tab_r, c_inv_repr),
+                Op::Uab(uab_l, c_repr),
+                Op::Uab(uab_r, c_inv_repr),
+                Op::Zab(zab_l, c_repr),
+                Op::Zab(zab_r, c_inv_repr),
                 Op::TC::<E>(tc_l, c_repr),
                 Op::TC(tc_r, c_inv_repr),
                 Op::UC(uc_l, c_repr),
                 Op::UC(uc_r, c_inv_repr),
                 Op::ZC(zc_l, c_repr),
                 Op::ZC(zc_r, c_inv_repr),
-            ]
-        })
-        .fold(GipaTUZ::<E>::default, |mut res, op: Op<E>| {
-            match op {
-                Op::TAB(tx, c) => {
-                    let tx: E::Fqk = tx.pow(c);
-                    res.tab.mul_assign(&tx);
-                }
-                Op::UAB(ux, c) => {
-                    let ux: E::Fqk = ux.pow(c);
-                    res.uab.mul_assign(&ux);
-                }
-                Op::ZAB(zx, c) => {
-                    let zx: E::Fqk = zx.pow(c);
-                    res.zab.mul_assign(&zx);
-                }
-                Op::TC(tx, c) => {
-                    let tx: E::Fqk = tx.pow(c);
-                    res.tc.mul_assign(&tx);
-                }
-                Op::UC(ux, c) => {
-                    let ux: E::Fqk = ux.pow(c);
-                    res.uc.mul_assign(&ux);
-                }
-                Op::ZC(zx, c) => {
-                    let zxp: E::G1Projective = zx.mul(c);
-                    res.zc.add_assign(&zxp);
-                }
+            ];
+            // same for the D part
+            if let Some(d) = d {
+                let (Output(td_l, ud_l), Output(td_r, ud_r)) = &d.gipa_d.comms_d[i];
+                let (zd_l, zd_r) = &d.gipa_d.z_d[i];
+                ops.extend([
+                    Op::TD(td_l, c_repr),
+                    Op::TD(td_r, c_inv_repr),
+                    Op::UD(ud_l, c_repr),
+                    Op::UD(ud_r, c_inv_repr),
+                    Op::ZD(zd_l, c_repr),
+                    Op::ZD(zd_r, c_inv_repr),
+                ]);
             }
-            res
-        })
-        .reduce(GipaTUZ::default, |mut acc_res, res| {
-            acc_res.merge(&res);
-            acc_res
+            ops
         });
+    let apply = |mut res: GipaTUZ<E>, op: Op<E>| {
+        match op {
+            Op::Tab(tx, c) => {
+                let tx: E::Fqk = tx.pow(c);
+                res.tab.mul_assign(&tx);
+            }
+            Op::Uab(ux, c) => {
+                let ux: E::Fqk = ux.pow(c);
+                res.uab.mul_assign(&ux);
+            }
+            Op::Zab(zx, c) => {
+                let zx: E::Fqk = zx.pow(c);
+                res.zab.mul_assign(&zx);
+            }
+            Op::TC(tx, c) => {
+                let tx: E::Fqk = tx.pow(c);
+                res.tc.mul_assign(&tx);
+            }
+            Op::UC(ux, c) => {
+                let ux: E::Fqk = ux.pow(c);
+                res.uc.mul_assign(&ux);
+            }
+            Op::ZC(zx, c) => {
+                let zxp: E::G1Projective = zx.mul(c);
+                res.zc.add_assign(&zxp);
+            }
+            Op::TD(tx, c) => {
+                let tx: E::Fqk = tx.pow(c);
+                res.td.mul_assign(&tx);
+            }
+            Op::UD(ux, c) => {
+                let ux: E::Fqk = ux.pow(c);
+                res.ud.mul_assign(&ux);
+            }
+            Op::ZD(zx, c) => {
+                let zxp: E::G1Projective = zx.mul(c);
+                res.zd.add_assign(&zxp);
+            }
+        }
+        res
+    };
+    #[cfg(feature = "parallel")]
+    let res =
+        ops.fold(GipaTUZ::<E>::default, apply)
+            .reduce(GipaTUZ::default, |mut acc_res, res| {
+                acc_res.merge(&res);
+                acc_res
+            });
+    #[cfg(not(feature = "parallel"))]
+    let res = ops.fold(GipaTUZ::<E>::default(), apply);
     // we reverse the order because the polynomial evaluation routine expects
     // the challenges in reverse order.Doing it here allows us to compute the final_r
     // in log time. Challenges are used as well in the KZG verification checks.
@@ -516,10 +990,7 @@ fn gipa_verify_tipp_mipp<E: PairingEngine, T: Transcript + Send>(
         &E::Fr::one(),
     );
 
-    dbg!(
-        "TIPP verify: gipa prep and accumulate took {}ms",
-        now.elapsed().as_millis()
-    );
+    end_timer!(timer);
     (final_res, final_r, challenges, challenges_inv)
 }
 
@@ -533,8 +1004,7 @@ pub fn verify_kzg_v<E: PairingEngine, R: Rng + Send>(
     challenges: &[E::Fr],
     kzg_challenge: &E::Fr,
     rng: &Mutex<R>,
-    checks: Sender<PairingCheck<E>>,
-) {
+) -> PairingCheck<E> {
     // f_v(z)
     let vpoly_eval_z = polynomial_evaluation_product_form_from_transcript(
         challenges,
@@ -544,17 +1014,14 @@ pub fn verify_kzg_v<E: PairingEngine, R: Rng + Send>(
     // -g such that when we test a pairing equation we only need to check if
     // it's equal 1 at the end:
     // e(a,b) = e(c,d) <=> e(a,b)e(-c,d) = 1
-    let mut ng = v_srs.g.clone();
+    let mut ng = v_srs.g;
     // e(A,B) = e(C,D) <=> e(A,B)e(-C,D) == 1 <=> e(A,B)e(C,D)^-1 == 1
     ng = ng.neg();
     let ng = ng.into_affine();
 
-    let v1clone = checks.clone();
-    let v2clone = checks.clone();
-
     par! {
         // e(g, C_f * h^{-y}) == e(v1 * g^{-x}, \pi) = 1
-        let _check1 = kzg_check_v::<E, R>(
+        let check1 = kzg_check_v::<E, R>(
             v_srs,
             ng,
             *kzg_challenge,
@@ -562,12 +1029,11 @@ pub fn verify_kzg_v<E: PairingEngine, R: Rng + Send>(
             final_vkey.0.into_projective(),
             v_srs.g_alpha,
             vkey_opening.0,
-            &rng,
-            v1clone,
+            rng,
         ),
 
         // e(g, C_f * h^{-y}) == e(v2 * g^{-x}, \pi) = 1
-        let _check2 = kzg_check_v::<E, R>(
+        let check2 = kzg_check_v::<E, R>(
             v_srs,
             ng,
             *kzg_challenge,
@@ -575,12 +1041,15 @@ pub fn verify_kzg_v<E: PairingEngine, R: Rng + Send>(
             final_vkey.1.into_projective(),
             v_srs.g_beta,
             vkey_opening.1,
-            &rng,
-            v2clone,
+            rng,
         )
     };
+    let mut check = check1;
+    check.merge(&check2);
+    check
 }
 
+#[allow(clippy::too_many_arguments)]
 fn kzg_check_v<E: PairingEngine, R: Rng + Send>(
     v_srs: &VerifierSRS<E>,
     ng: E::G1Affine,
@@ -590,8 +1059,7 @@ fn kzg_check_v<E: PairingEngine, R: Rng + Send>(
     vk: E::G1Projective,
     pi: E::G2Affine,
     rng: &Mutex<R>,
-    checks: Sender<PairingCheck<E>>,
-) {
+) -> PairingCheck<E> {
     // KZG Check: e(g, C_f * h^{-y}) = e(vk * g^{-x}, \pi)
     // Transformed, such that
     // e(-g, C_f * h^{-y}) * e(vk * g^{-x}, \pi) = 1
@@ -601,8 +1069,7 @@ fn kzg_check_v<E: PairingEngine, R: Rng + Send>(
 
     // vk - (g * x)
     let c = sub!(vk, &mul!(v_srs.g, x)).into_affine();
-    let p = PairingCheck::rand(&rng, &[(&ng, &b), (&c, &pi)], &E::Fqk::one());
-    checks.send(p).unwrap();
+    PairingCheck::rand(rng, &[(&ng, &b), (&c, &pi)], &E::Fqk::one())
 }
 
 /// Similar to verify_kzg_opening_g2 but for g1.
@@ -614,26 +1081,22 @@ pub fn verify_kzg_w<E: PairingEngine, R: Rng + Send>(
     r_shift: &E::Fr,
     kzg_challenge: &E::Fr,
     rng: &Mutex<R>,
-    checks: Sender<PairingCheck<E>>,
-) {
+) -> PairingCheck<E> {
     // compute in parallel f(z) and z^n and then combines into f_w(z) = z^n * f(z)
     par! {
         let fz = polynomial_evaluation_product_form_from_transcript(challenges, kzg_challenge, r_shift),
-        let zn = kzg_challenge.pow(&[v_srs.n as u64])
+        let zn = kzg_challenge.pow([v_srs.n as u64])
     };
 
     let mut fwz = fz;
     fwz.mul_assign(&zn);
 
-    let mut nh = v_srs.h;
-    nh = nh.neg();
-    let nh = nh.into_affine();
+    // -h is prepared once in the SRS for both checks
+    let nh = &v_srs.h_neg_prepared;
 
-    let w1clone = checks.clone();
-    let w2clone = checks.clone();
     par! {
         // e(C_f * g^{-y}, h) = e(\pi, w1 * h^{-x})
-        let _check1 = kzg_check_w::<E, R>(
+        let check1 = kzg_check_w::<E, R>(
             v_srs,
             nh,
             *kzg_challenge,
@@ -641,12 +1104,11 @@ pub fn verify_kzg_w<E: PairingEngine, R: Rng + Send>(
             final_wkey.0.into_projective(),
             v_srs.h_alpha,
             wkey_opening.0,
-            &rng,
-            w1clone,
+            rng,
         ),
 
         // e(C_f * g^{-y}, h) = e(\pi, w2 * h^{-x})
-        let _check2 = kzg_check_w::<E, R>(
+        let check2 = kzg_check_w::<E, R>(
             v_srs,
             nh,
             *kzg_challenge,
@@ -654,23 +1116,25 @@ pub fn verify_kzg_w<E: PairingEngine, R: Rng + Send>(
             final_wkey.1.into_projective(),
             v_srs.h_beta,
             wkey_opening.1,
-            &rng,
-            w2clone,
+            rng,
         )
     };
+    let mut check = check1;
+    check.merge(&check2);
+    check
 }
 
+#[allow(clippy::too_many_arguments)]
 fn kzg_check_w<E: PairingEngine, R: Rng + Send>(
     v_srs: &VerifierSRS<E>,
-    nh: E::G2Affine,
+    nh: &E::G2Prepared,
     x: E::Fr,
     y: E::Fr,
     cf: E::G1Projective,
     wk: E::G2Projective,
     pi: E::G1Affine,
     rng: &Mutex<R>,
-    checks: Sender<PairingCheck<E>>,
-) {
+) -> PairingCheck<E> {
     // KZG Check: e(C_f * g^{-y}, h) = e(\pi, wk * h^{-x})
     // Transformed, such that
     // e(C_f * g^{-y}, -h) * e(\pi, wk * h^{-x}) = 1
@@ -679,14 +1143,13 @@ fn kzg_check_w<E: PairingEngine, R: Rng + Send>(
     let a = sub!(cf, &mul!(v_srs.g, y)).into_affine();
 
     // wk - (x * h)
-    let d = sub!(wk, &mul!(v_srs.h, x)).into_affine();
-    let p = PairingCheck::rand(&rng, &[(&a, &nh), (&pi, &d)], &E::Fqk::one());
-    checks.send(p).unwrap();
+    let d = E::G2Prepared::from(sub!(wk, &mul!(v_srs.h, x)).into_affine());
+    PairingCheck::rand_prepared(rng, &[(&a, nh), (&pi, &d)], &E::Fqk::one())
 }
 
 /// Keeps track of the variables that have been sent by the prover and must
 /// be multiplied together by the verifier. Both MIPP and TIPP are merged
-/// together.
+/// together, as well as the MIPP of D for proofs with an extra commitment.
 struct GipaTUZ<E: PairingEngine> {
     pub tab: E::Fqk,
     pub uab: E::Fqk,
@@ -694,6 +1157,9 @@ struct GipaTUZ<E: PairingEngine> {
     pub tc: E::Fqk,
     pub uc: E::Fqk,
     pub zc: E::G1Projective,
+    pub td: E::Fqk,
+    pub ud: E::Fqk,
+    pub zd: E::G1Projective,
 }
 
 impl<E> Default for GipaTUZ<E>
@@ -708,6 +1174,9 @@ where
             tc: E::Fqk::one(),
             uc: E::Fqk::one(),
             zc: E::G1Projective::zero(),
+            td: E::Fqk::one(),
+            ud: E::Fqk::one(),
+            zd: E::G1Projective::zero(),
         }
     }
 }
@@ -723,5 +1192,8 @@ where
         self.tc.mul_assign(&other.tc);
         self.uc.mul_assign(&other.uc);
         self.zc.add_assign(&other.zc);
+        self.td.mul_assign(&other.td);
+        self.ud.mul_assign(&other.ud);
+        self.zd.add_assign(&other.zd);
     }
 }
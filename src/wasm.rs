@@ -0,0 +1,225 @@
+//! WebAssembly bindings to verify aggregated snarkjs Groth16 proofs on BN254,
+//! for example from a browser. Build the module with `cargo rustc --lib
+//! --crate-type cdylib --target wasm32-unknown-unknown --no-default-features
+//! --features wasm`: the verification runs single-threaded and the randomness
+//! needed to batch the pairing checks is derived deterministically from the
+//! inputs.
+//!
+//! The bindings verify against the transcript returned by `snarkjs_transcript`,
+//! so the aggregator must use the same transcript when calling
+//! `aggregate_proofs`.
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{prepare_verifying_key, VerifyingKey};
+use ark_serialize::CanonicalSerialize;
+use ark_std::str::FromStr;
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    snarkjs_transcript, srs::VerifierSRS, verify_aggregate_proof, AggregateProof, Error, SnarkJSVK,
+};
+
+/// Encodes the aggregate proof in the JSON form accepted by
+/// `verifyAggregateProofJson`, i.e. a JSON string containing the hex encoding
+/// of the proof.
+pub fn proof_to_json(proof: &AggregateProof<Bn254>) -> Result<String, Error> {
+    let mut buff = Vec::new();
+    proof.write(&mut buff)?;
+    serde_json::to_string(&hex::encode(buff))
+        .map_err(|e| Error::InvalidProof(format!("json encoding: {}", e)))
+}
+
+/// Verifies the aggregate proof encoded with `AggregateProof::write` against
+/// the snarkjs verifying key, the serialized verifier SRS and the public inputs
+/// of each aggregated proof. The key is checked with
+/// `SnarkJSVK::to_verifying_key`.
+pub fn verify(
+    vk: SnarkJSVK,
+    srs: &[u8],
+    public_inputs: &[Vec<Fr>],
+    proof: &[u8],
+) -> Result<(), Error> {
+    let vk = vk.to_verifying_key()?;
+    let rng = deterministic_rng(&vk, srs, public_inputs, proof)?;
+    let pvk = prepare_verifying_key(&vk);
+    let srs = VerifierSRS::<Bn254>::read(srs)?;
+    let agg = AggregateProof::<Bn254>::read(proof)?;
+    let mut transcript = snarkjs_transcript(public_inputs);
+    verify_aggregate_proof(&srs, &pvk, public_inputs, &agg, rng, &mut transcript)
+}
+
+/// Verifies an aggregate proof given in its binary encoding.
+/// - `vk_json` is the snarkjs `verification_key.json`
+/// - `srs` is the verifier SRS written with `VerifierSRS::write`
+/// - `public_inputs_json` is a JSON array containing the snarkjs `public.json`
+///   of each aggregated proof, in the order they were aggregated
+///
+/// It throws if the proof is invalid.
+#[wasm_bindgen(js_name = verifyAggregateProof)]
+pub fn verify_aggregate_proof_bytes(
+    vk_json: &str,
+    srs: &[u8],
+    public_inputs_json: &str,
+    proof: &[u8],
+) -> Result<(), JsValue> {
+    let vk = parse_vk(vk_json).map_err(to_js)?;
+    let public_inputs = parse_public_inputs(public_inputs_json).map_err(to_js)?;
+    verify(vk, srs, &public_inputs, proof).map_err(to_js)
+}
+
+/// Same as `verifyAggregateProof` but the proof is given in the JSON form
+/// returned by `proof_to_json`.
+#[wasm_bindgen(js_name = verifyAggregateProofJson)]
+pub fn verify_aggregate_proof_json(
+    vk_json: &str,
+    srs: &[u8],
+    public_inputs_json: &str,
+    proof_json: &str,
+) -> Result<(), JsValue> {
+    let proof = parse_proof(proof_json).map_err(to_js)?;
+    verify_aggregate_proof_bytes(vk_json, srs, public_inputs_json, &proof)
+}
+
+/// The rng is seeded from the whole statement, i.e. the verifying key, the SRS,
+/// the public inputs and the proof, so verification is reproducible without
+/// access to an entropy source.
+fn deterministic_rng(
+    vk: &VerifyingKey<Bn254>,
+    srs: &[u8],
+    public_inputs: &[Vec<Fr>],
+    proof: &[u8],
+) -> Result<ChaChaRng, Error> {
+    let mut vk_bytes = Vec::new();
+    vk.serialize(&mut vk_bytes)?;
+    let mut inputs = Vec::new();
+    public_inputs.to_vec().serialize(&mut inputs)?;
+    let mut hasher = Sha256::new();
+    hasher.update(&vk_bytes);
+    hasher.update(srs);
+    hasher.update(&inputs);
+    hasher.update(proof);
+    Ok(ChaChaRng::from_seed(hasher.finalize().into()))
+}
+
+fn parse_vk(vk_json: &str) -> Result<SnarkJSVK, Error> {
+    serde_json::from_str(vk_json).map_err(|_| Error::MalformedVerifyingKey)
+}
+
+fn parse_public_inputs(public_inputs_json: &str) -> Result<Vec<Vec<Fr>>, Error> {
+    let inputs: Vec<Vec<String>> = serde_json::from_str(public_inputs_json)
+        .map_err(|e| Error::InvalidProof(format!("public inputs: {}", e)))?;
+    inputs
+        .iter()
+        .map(|input| {
+            input
+                .iter()
+                .map(|s| {
+                    Fr::from_str(s)
+                        .map_err(|_| Error::InvalidProof(format!("invalid public input {}", s)))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_proof(proof_json: &str) -> Result<Vec<u8>, Error> {
+    let proof: String = serde_json::from_str(proof_json)
+        .map_err(|e| Error::InvalidProof(format!("json decoding: {}", e)))?;
+    hex::decode(proof.trim_start_matches("0x"))
+        .map_err(|e| Error::InvalidProof(format!("hex decoding: {}", e)))
+}
+
+fn to_js(e: Error) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+// The fixtures are included rather than read from the file system so the tests
+// also run on wasm32: with wasmtime on `wasm32-wasip1` (see
+// `.cargo/config.toml`), which needs no JavaScript engine,
+// cargo test --target wasm32-wasip1 --no-default-features --features wasm --lib
+// or with the `wasm-bindgen-test-runner` of `wasm-bindgen-cli` on
+// `wasm32-unknown-unknown`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aggregate_proofs, srs::setup_fake_srs, SnarkJSProof};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    const PROOFS: [&str; 2] = [
+        include_str!("../tests/secret/000/proof.json"),
+        include_str!("../tests/secret/001/proof.json"),
+    ];
+    const PUBLIC_INPUTS: [&str; 2] = [
+        include_str!("../tests/secret/000/public.json"),
+        include_str!("../tests/secret/001/public.json"),
+    ];
+    const VK: &str = include_str!("../tests/secret/000/verification_key.json");
+
+    #[test]
+    fn test_verify_snarkjs_aggregate() {
+        let nproofs = 2;
+        let mut rng = ChaChaRng::seed_from_u64(0u64);
+        let srs = setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+        let (prover_srs, ver_srs) = srs.specialize(nproofs);
+
+        let mut proofs = Vec::new();
+        let mut public_jsons = Vec::new();
+        for (proof, public_json) in PROOFS.iter().zip(PUBLIC_INPUTS.iter()) {
+            let proof: SnarkJSProof = serde_json::from_str(proof).unwrap();
+            proofs.push(proof.into());
+            public_jsons.push(*public_json);
+        }
+
+        let public_inputs_json = format!("[{}]", public_jsons.join(","));
+        let public_inputs = parse_public_inputs(&public_inputs_json).unwrap();
+
+        let mut transcript = snarkjs_transcript(&public_inputs);
+        let agg = aggregate_proofs(&prover_srs, &mut transcript, &proofs).unwrap();
+        let mut proof = Vec::new();
+        agg.write(&mut proof).unwrap();
+        let mut srs_bytes = Vec::new();
+        ver_srs.write(&mut srs_bytes).unwrap();
+
+        verify_aggregate_proof_bytes(VK, &srs_bytes, &public_inputs_json, &proof).unwrap();
+        let proof_json = proof_to_json(&agg).unwrap();
+        verify_aggregate_proof_json(VK, &srs_bytes, &public_inputs_json, &proof_json).unwrap();
+
+        let vk = parse_vk(VK).unwrap();
+        let mut wrong_inputs = public_inputs.clone();
+        wrong_inputs.swap(0, 1);
+        verify(vk, &srs_bytes, &wrong_inputs, &proof).expect_err("this should have failed");
+    }
+
+    #[test]
+    fn test_malformed_vk() {
+        // the key is checked before the SRS and the proof are read
+        let is_invalid = |vk: SnarkJSVK| match verify(vk, &[], &[], &[]) {
+            Err(Error::InvalidVerifyingKey(_)) => (),
+            r => panic!("unexpected result {:?}", r),
+        };
+        let vk = parse_vk(VK).unwrap();
+
+        let mut short = vk.clone();
+        short.vk_alpha_1.truncate(2);
+        is_invalid(short);
+
+        let mut off_curve = vk.clone();
+        off_curve.vk_beta_2[1][0] = "1".to_string();
+        is_invalid(off_curve);
+
+        let mut not_canonical = vk.clone();
+        not_canonical.ic[0][0].insert(0, '1');
+        is_invalid(not_canonical);
+
+        let mut missing_ic = vk.clone();
+        missing_ic.ic.pop();
+        is_invalid(missing_ic);
+
+        let mut curve = vk;
+        curve.curve = "bls12381".to_string();
+        is_invalid(curve);
+    }
+}
@@ -1,27 +1,20 @@
-use ark_bn254::Fq6;
-use ark_bn254::G1Projective;
-use ark_bn254::G2Projective;
-use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
-#[allow(dead_code)]
+use ark_bn254::{Bn254, Fr};
+#[cfg(feature = "std")]
+use ark_bn254::{Fq2, G1Affine, G1Projective, G2Affine, G2Projective};
 use ark_ff::One;
 use ark_groth16::{
-    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof, Proof,
+    create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
 };
 
+#[cfg(feature = "std")]
 use serde_json::Value;
-use snarkpack;
-use snarkpack::transcript::Transcript;
-use snarkpack::{fq_from_str, fr_from_str, read_zkey};
+use snarkpack::transcript::{InputsBinding, Transcript};
+#[cfg(feature = "std")]
+use snarkpack::{fq_from_str, read_zkey};
 mod constraints;
 use crate::constraints::Benchmark;
 use rand_core::SeedableRng;
 
-#[macro_use]
-extern crate serde_derive;
-
-use serde::Deserialize;
-use std::str::FromStr;
-
 #[test]
 fn groth16_aggregation() {
     let num_constraints = 1000;
@@ -67,11 +60,587 @@ fn groth16_aggregation() {
         &mut ver_transcript,
     )
     .expect("error in verification");
+
+    // beta can be prepared once for many verifications, and -h is prepared
+    // again when the SRS is read
+    let mut srs_bytes = Vec::new();
+    ver_srs.write(&mut srs_bytes).unwrap();
+    let ver_srs = snarkpack::srs::VerifierSRS::<Bn254>::read(&srs_bytes[..]).unwrap();
+    let key = snarkpack::PreparedBetaKey::from(pvk);
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test aggregation");
+    ver_transcript.append(b"public-inputs", &all_inputs);
+    snarkpack::verify_aggregate_proof(
+        &ver_srs,
+        &key,
+        &all_inputs,
+        &aggregate_proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification with prepared beta");
+}
+
+#[test]
+fn groth16_aggregation_bound() {
+    let num_constraints = 1000;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(2u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs)
+        .map(|_| [Fr::one(); 2].to_vec())
+        .collect::<Vec<_>>();
+
+    for binding in [InputsBinding::Absorb, InputsBinding::Fixed].iter() {
+        let mut prover_transcript = snarkpack::transcript::new_merlin_transcript(b"test bound");
+        let aggregate_proof = snarkpack::aggregate_and_bind(
+            &prover_srs,
+            &params.vk,
+            &all_inputs,
+            &proofs,
+            *binding,
+            &mut prover_transcript,
+        )
+        .expect("error in aggregation");
+
+        let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test bound");
+        snarkpack::verify_bound(
+            &ver_srs,
+            &pvk,
+            &all_inputs,
+            &aggregate_proof,
+            *binding,
+            &mut rng,
+            &mut ver_transcript,
+        )
+        .expect("error in verification");
+
+        // the proof is bound to the way the statement was absorbed
+        let other = match binding {
+            InputsBinding::Absorb => InputsBinding::Fixed,
+            InputsBinding::Fixed => InputsBinding::Absorb,
+        };
+        let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test bound");
+        snarkpack::verify_bound(
+            &ver_srs,
+            &pvk,
+            &all_inputs,
+            &aggregate_proof,
+            other,
+            &mut rng,
+            &mut ver_transcript,
+        )
+        .expect_err("verification should have failed");
+    }
+}
+
+#[test]
+fn groth16_incremental_aggregation() {
+    use ark_serialize::CanonicalSerialize;
+
+    let num_constraints = 1000;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(3u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs)
+        .map(|_| [Fr::one(); 2].to_vec())
+        .collect::<Vec<_>>();
+
+    let mut aggregator = snarkpack::Aggregator::new(&prover_srs, &params.vk);
+    aggregator
+        .push(&proofs[0], &all_inputs[0][..1])
+        .expect_err("wrong number of public inputs");
+    for (proof, inputs) in proofs.iter().zip(all_inputs.iter()) {
+        aggregator.push(proof, inputs).unwrap();
+    }
+    assert_eq!(aggregator.len(), nproofs);
+    aggregator
+        .push(&proofs[0], &all_inputs[0])
+        .expect_err("more proofs than the srs supports");
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test incremental");
+    let aggregate_proof = aggregator.finalize(&mut transcript).unwrap();
+
+    // same proof as when aggregating all proofs at once
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test incremental");
+    let expected = snarkpack::aggregate_and_bind(
+        &prover_srs,
+        &params.vk,
+        &all_inputs,
+        &proofs,
+        InputsBinding::Absorb,
+        &mut transcript,
+    )
+    .unwrap();
+    let (mut got, mut want) = (Vec::new(), Vec::new());
+    aggregate_proof.serialize(&mut got).unwrap();
+    expected.serialize(&mut want).unwrap();
+    assert_eq!(got, want);
+
+    // same proof when the points are written to a file until finalize, through
+    // a buffer smaller than a single proof
+    #[cfg(feature = "std")]
+    {
+        let path = std::env::temp_dir().join("snarkpack-test-incremental");
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let store = snarkpack::FileStore::new(file, 64);
+        let mut aggregator = snarkpack::Aggregator::with_store(&prover_srs, &params.vk, store);
+        for (proof, inputs) in proofs.iter().zip(all_inputs.iter()) {
+            aggregator.push(proof, inputs).unwrap();
+        }
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test incremental");
+        let proof = aggregator.finalize(&mut transcript).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut got = Vec::new();
+        proof.serialize(&mut got).unwrap();
+        assert_eq!(got, want);
+    }
+
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test incremental");
+    snarkpack::verify_bound(
+        &ver_srs,
+        &pvk,
+        &all_inputs,
+        &aggregate_proof,
+        InputsBinding::Absorb,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+
+    let mut aggregator = snarkpack::Aggregator::new(&prover_srs, &params.vk);
+    aggregator.push(&proofs[0], &all_inputs[0]).unwrap();
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test incremental");
+    aggregator
+        .finalize(&mut transcript)
+        .expect_err("not enough proofs");
+}
+
+#[test]
+fn groth16_sharded_aggregation() {
+    use ark_serialize::CanonicalSerialize;
+
+    let num_constraints = 1000;
+    let nproofs = 8;
+    let shard_size = 2;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(4u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs)
+        .map(|_| [Fr::one(); 2].to_vec())
+        .collect::<Vec<_>>();
+
+    // each worker accumulates its shard, which is sent serialized to the
+    // coordinator
+    let mut shards = proofs
+        .chunks(shard_size)
+        .zip(all_inputs.chunks(shard_size))
+        .enumerate()
+        .map(|(i, (proofs, inputs))| {
+            let mut aggregator =
+                snarkpack::Aggregator::shard(&prover_srs, &params.vk, i * shard_size);
+            for (proof, inputs) in proofs.iter().zip(inputs.iter()) {
+                aggregator.push(proof, inputs).unwrap();
+            }
+            let mut buff = Vec::new();
+            aggregator.into_shard().write(&mut buff).unwrap();
+            snarkpack::Shard::<Bn254>::read(&buff[..]).unwrap()
+        })
+        .collect::<Vec<_>>();
+    shards.reverse();
+
+    let mut missing = shards.clone();
+    missing.remove(1);
+    snarkpack::Aggregator::merge(&prover_srs, &params.vk, missing).expect_err("a shard is missing");
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test sharded");
+    snarkpack::Aggregator::merge(&prover_srs, &params.vk, shards[1..].to_vec())
+        .unwrap()
+        .finalize(&mut transcript)
+        .expect_err("the last shard is missing");
+    let mut duplicated = shards.clone();
+    duplicated.push(shards[0].clone());
+    snarkpack::Aggregator::merge(&prover_srs, &params.vk, duplicated)
+        .expect_err("a shard is duplicated");
+    // a shard with a single inputs hash for its two proofs: the hashes are
+    // serialized just before the four Fq12 of the miller loop outputs
+    let mut buff = Vec::new();
+    shards[0].write(&mut buff).unwrap();
+    let tail = buff.len() - 4 * ark_bn254::Fq12::one().serialized_size();
+    let leaves = tail - 8 - shard_size * (8 + 32);
+    let mut truncated = buff[..leaves].to_vec();
+    truncated.extend_from_slice(&1u64.to_le_bytes());
+    truncated.extend_from_slice(&buff[leaves + 8..leaves + 8 + 8 + 32]);
+    truncated.extend_from_slice(&buff[tail..]);
+    let mut truncated_shards = shards.clone();
+    truncated_shards[0] = snarkpack::Shard::<Bn254>::read(&truncated[..]).unwrap();
+    snarkpack::Aggregator::merge(&prover_srs, &params.vk, truncated_shards)
+        .expect_err("a shard misses an inputs hash");
+
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test sharded");
+    let aggregate_proof = snarkpack::Aggregator::merge(&prover_srs, &params.vk, shards)
+        .unwrap()
+        .finalize(&mut transcript)
+        .unwrap();
+
+    // same proof as when aggregating all proofs at once
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test sharded");
+    let expected = snarkpack::aggregate_and_bind(
+        &prover_srs,
+        &params.vk,
+        &all_inputs,
+        &proofs,
+        InputsBinding::Absorb,
+        &mut transcript,
+    )
+    .unwrap();
+    let (mut got, mut want) = (Vec::new(), Vec::new());
+    aggregate_proof.serialize(&mut got).unwrap();
+    expected.serialize(&mut want).unwrap();
+    assert_eq!(got, want);
+
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test sharded");
+    snarkpack::verify_bound(
+        &ver_srs,
+        &pvk,
+        &all_inputs,
+        &aggregate_proof,
+        InputsBinding::Absorb,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
 }
 
+#[cfg(feature = "std")]
+#[test]
+fn groth16_distributed_aggregation() {
+    use ark_serialize::CanonicalSerialize;
+    use snarkpack::distributed::{aggregate_distributed, serve, Connection, Local};
+    use std::net::{TcpListener, TcpStream};
+
+    let num_constraints = 1000;
+    let nproofs = 8;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(5u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let inputs: Vec<_> = [Fr::one(); 2].to_vec();
+    let all_inputs = (0..nproofs).map(|_| inputs.clone()).collect::<Vec<_>>();
+
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test distributed");
+    let expected = snarkpack::aggregate_proofs(&prover_srs, &mut transcript, &proofs).unwrap();
+    let mut want = Vec::new();
+    expected.serialize(&mut want).unwrap();
+
+    for nworkers in [1, 2, 4, 8].iter() {
+        let mut workers = (0..*nworkers)
+            .map(|_| Local::<Bn254>::new())
+            .collect::<Vec<_>>();
+        let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test distributed");
+        let proof =
+            aggregate_distributed(&prover_srs, &mut transcript, &proofs, &mut workers).unwrap();
+        let mut got = Vec::new();
+        proof.serialize(&mut got).unwrap();
+        assert_eq!(got, want);
+    }
+    let mut workers = (0..3).map(|_| Local::<Bn254>::new()).collect::<Vec<_>>();
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test distributed");
+    aggregate_distributed(&prover_srs, &mut transcript, &proofs, &mut workers)
+        .expect_err("number of workers not a power of two");
+
+    // workers reached through TCP
+    let mut workers = (0..2)
+        .map(|_| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            std::thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                serve::<Bn254, _>(stream).unwrap();
+            });
+            Connection::new(TcpStream::connect(addr).unwrap())
+        })
+        .collect::<Vec<_>>();
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test distributed");
+    let proof = aggregate_distributed(&prover_srs, &mut transcript, &proofs, &mut workers).unwrap();
+    let mut got = Vec::new();
+    proof.serialize(&mut got).unwrap();
+    assert_eq!(got, want);
+
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test distributed");
+    snarkpack::verify_aggregate_proof(
+        &ver_srs,
+        &pvk,
+        &all_inputs,
+        &proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+}
+
+#[test]
+fn groth16_committed_inputs_aggregation() {
+    let num_constraints = 1000;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(5u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs)
+        .map(|_| [Fr::one(); 2].to_vec())
+        .collect::<Vec<_>>();
+
+    // the verifier only gets the commitment to the public inputs
+    let commitment = snarkpack::commit_inputs(&prover_srs, &all_inputs).unwrap();
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test committed");
+    let proof = snarkpack::aggregate_with_committed_inputs(
+        &prover_srs,
+        &mut transcript,
+        &commitment,
+        &all_inputs,
+        &proofs,
+    )
+    .unwrap();
+    let mut buf = Vec::new();
+    proof.write(&mut buf).unwrap();
+    let proof = snarkpack::CommittedInputsProof::<Bn254>::read(&buf[..]).unwrap();
+    let verify = |commitment: &snarkpack::InputsCommitment<Bn254>,
+                  proof: &snarkpack::CommittedInputsProof<Bn254>| {
+        let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test committed");
+        snarkpack::verify_with_committed_inputs(
+            &ver_srs,
+            &pvk,
+            commitment,
+            proof,
+            rand_chacha::ChaChaRng::seed_from_u64(6u64),
+            &mut transcript,
+        )
+    };
+    verify(&commitment, &proof).expect("error in verification");
+
+    // wrong evaluations
+    let mut wrong = proof.clone();
+    wrong.evaluations[1] += Fr::one();
+    verify(&commitment, &wrong).expect_err("wrong evaluations must not verify");
+    // commitment to different public inputs
+    let mut other_inputs = all_inputs.clone();
+    other_inputs[2][0] += Fr::one();
+    let other = snarkpack::commit_inputs(&prover_srs, &other_inputs).unwrap();
+    verify(&other, &proof).expect_err("wrong commitment must not verify");
+    // consistent proof for public inputs the proofs were not created with
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test committed");
+    let forged = snarkpack::aggregate_with_committed_inputs(
+        &prover_srs,
+        &mut transcript,
+        &other,
+        &other_inputs,
+        &proofs,
+    )
+    .unwrap();
+    verify(&other, &forged).expect_err("wrong public inputs must not verify");
+}
+
+#[test]
+fn groth16_shared_inputs_aggregation() {
+    let num_constraints = 1000;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(7u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    let pvk = prepare_verifying_key(&params.vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs)
+        .map(|_| [Fr::one(); 2].to_vec())
+        .collect::<Vec<_>>();
+
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test shared");
+    let aggregate_proof =
+        snarkpack::aggregate_proofs(&prover_srs, &mut transcript, &proofs).unwrap();
+    let verify = |inputs: &snarkpack::SharedInputs<Bn254>| {
+        let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test shared");
+        snarkpack::verify_with_shared_inputs(
+            &ver_srs,
+            &pvk,
+            inputs,
+            &aggregate_proof,
+            rand_chacha::ChaChaRng::seed_from_u64(8u64),
+            &mut transcript,
+        )
+    };
+    for positions in [vec![], vec![0], vec![1], vec![0, 1]].iter() {
+        let inputs = snarkpack::SharedInputs::from_inputs(&all_inputs, positions).unwrap();
+        assert_eq!(inputs.shared.len(), positions.len());
+        verify(&inputs).expect("error in verification");
+    }
+
+    let mut inputs = snarkpack::SharedInputs::from_inputs(&all_inputs, &[1]).unwrap();
+    inputs.shared[0].1 += Fr::one();
+    verify(&inputs).expect_err("wrong shared input must not verify");
+    inputs.shared[0].1 = Fr::one();
+    inputs.varying.pop();
+    verify(&inputs).expect_err("missing proof inputs must not verify");
+    inputs.shared.push((1, Fr::one()));
+    verify(&inputs).expect_err("duplicate shared input must not verify");
+
+    let mut other_inputs = all_inputs.clone();
+    other_inputs[3][0] += Fr::one();
+    snarkpack::SharedInputs::<Bn254>::from_inputs(&other_inputs, &[0])
+        .expect_err("input is not shared by all proofs");
+    snarkpack::SharedInputs::<Bn254>::from_inputs(&all_inputs, &[1, 0])
+        .expect_err("positions are not sorted");
+}
+
+#[test]
+fn groth16_aggregation_with_commitments() {
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+
+    let num_constraints = 1000;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(9u64);
+    let params = {
+        let c = Benchmark::<Fr>::new(num_constraints);
+        generate_random_parameters::<Bn254, _, _>(c, &mut rng).unwrap()
+    };
+    // the last public input plays the role of the committed witness: its term
+    // of the verification equation is the D point of each proof
+    let mut vk = params.vk.clone();
+    let committed_base = vk.gamma_abc_g1.pop().unwrap();
+    let pvk = prepare_verifying_key(&vk);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let proofs = (0..nproofs)
+        .map(|_| {
+            let c = Benchmark::new(num_constraints);
+            create_random_proof(c, &params, &mut rng).expect("proof creation failed")
+        })
+        .collect::<Vec<_>>();
+    let all_inputs = (0..nproofs).map(|_| vec![Fr::one()]).collect::<Vec<_>>();
+    // the committed input is one in all proofs
+    let commitments = vec![committed_base; nproofs];
+
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test commitments");
+    let proof = snarkpack::aggregate_proofs_with_commitments(
+        &prover_srs,
+        &mut transcript,
+        &proofs,
+        &commitments,
+    )
+    .unwrap();
+    let mut buf = Vec::new();
+    proof.write(&mut buf).unwrap();
+    let proof = snarkpack::AggregateProofWithCommitments::<Bn254>::read(&buf[..]).unwrap();
+    let verify = |proof: &snarkpack::AggregateProofWithCommitments<Bn254>| {
+        let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test commitments");
+        snarkpack::verify_aggregate_proof_with_commitments(
+            &ver_srs,
+            &pvk,
+            &all_inputs,
+            proof,
+            rand_chacha::ChaChaRng::seed_from_u64(10u64),
+            &mut transcript,
+        )
+    };
+    verify(&proof).expect("error in verification");
+
+    // a commitment which does not satisfy the equation
+    let mut wrong = commitments.clone();
+    wrong[2] = committed_base.mul(Fr::from(2u64)).into_affine();
+    let mut transcript = snarkpack::transcript::new_merlin_transcript(b"test commitments");
+    let wrong_proof =
+        snarkpack::aggregate_proofs_with_commitments(&prover_srs, &mut transcript, &proofs, &wrong)
+            .unwrap();
+    verify(&wrong_proof).expect_err("wrong commitment must not verify");
+    // the aggregated D does not match its commitment
+    let mut tampered = proof.clone();
+    tampered.agg_d = wrong_proof.agg_d;
+    verify(&tampered).expect_err("wrong aggregated commitment must not verify");
+    // the values of D in GIPA do not match the ones of the proof
+    let mut tampered = proof.clone();
+    tampered.gipa_d.final_d = wrong_proof.gipa_d.final_d;
+    verify(&tampered).expect_err("wrong final commitment must not verify");
+    let mut tampered = proof.clone();
+    tampered.gipa_d.z_d[0] = wrong_proof.gipa_d.z_d[0];
+    verify(&tampered).expect_err("wrong round values must not verify");
+    let mut tampered = proof;
+    tampered.gipa_d.z_d.pop();
+    verify(&tampered).expect_err("missing round values must not verify");
+}
+
+#[cfg(feature = "std")]
 #[test]
 fn snarkjs_groth16_aggreagtion() {
-    use snarkpack::{fr_from_str, get_prepared_verifying_key, SnarkJSProof, SnarkJSVK};
+    use ark_ff::Field;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use snarkpack::{fr_from_str, get_prepared_verifying_key, AlphaBeta, SnarkJSProof, SnarkJSVK};
     use std::fs::File;
 
     let nproofs = 2;
@@ -93,12 +662,12 @@ fn snarkjs_groth16_aggreagtion() {
         vk_json = serde_json::from_reader(file).unwrap();
         let file = File::open(format!("{}/public.json", base_path)).unwrap();
         let public_json: Vec<String> = serde_json::from_reader(file).unwrap();
-        let pvk = get_prepared_verifying_key(vk_json.clone());
+        let pvk = get_prepared_verifying_key(vk_json.clone()).unwrap();
         let ark_pub_inputs: Vec<ark_bn254::Fr> = public_json.into_iter().map(fr_from_str).collect();
         let res =
             ark_groth16::verify_proof(&pvk.into(), &proof_json.clone().into(), &ark_pub_inputs[..])
                 .unwrap();
-        assert_eq!(res, true);
+        assert!(res);
         proofs.push(proof_json.into());
         inputs.push(ark_pub_inputs);
     }
@@ -112,7 +681,7 @@ fn snarkjs_groth16_aggreagtion() {
     let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test aggregation");
     ver_transcript.append(b"public-inputs", &inputs);
 
-    let parse_vkey: ark_groth16::VerifyingKey<ark_bn254::Bn254> = vk_json.into();
+    let parse_vkey: ark_groth16::VerifyingKey<ark_bn254::Bn254> = vk_json.clone().into();
     let pvk = ark_groth16::prepare_verifying_key(&parse_vkey);
 
     snarkpack::verify_aggregate_proof(
@@ -124,8 +693,143 @@ fn snarkjs_groth16_aggreagtion() {
         &mut ver_transcript,
     )
     .expect("error in verification");
+
+    // the alphabeta of snarkjs is the pairing computed by Arkworks and can be
+    // used instead of the miller loop
+    let vk_alphabeta_12 = vk_json.vk_alphabeta_12.clone();
+    let snarkjs_pvk: ark_groth16::PreparedVerifyingKey<Bn254> =
+        get_prepared_verifying_key(vk_json.clone()).unwrap().into();
+    assert_eq!(snarkjs_pvk.alpha_g1_beta_g2, pvk.alpha_g1_beta_g2);
+    let verify = |pvk: &ark_groth16::PreparedVerifyingKey<Bn254>, alphabeta, rng: &mut _| {
+        let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test aggregation");
+        ver_transcript.append(b"public-inputs", &inputs);
+        snarkpack::verify_aggregate_proof_with_alphabeta(
+            &ver_srs,
+            pvk,
+            &inputs,
+            &aggregate_proof,
+            alphabeta,
+            rng,
+            &mut ver_transcript,
+        )
+    };
+    for alphabeta in [AlphaBeta::MillerLoop, AlphaBeta::Precomputed] {
+        verify(&snarkjs_pvk, alphabeta, &mut rng).expect("error in verification");
+    }
+    let mut wrong = snarkjs_pvk.clone();
+    wrong.alpha_g1_beta_g2.square_in_place();
+    verify(&wrong, AlphaBeta::Precomputed, &mut rng).expect_err("wrong alphabeta must not verify");
+    verify(&wrong, AlphaBeta::MillerLoop, &mut rng).expect("alphabeta is not used");
+
+    // a malformed alphabeta is an error, not a panic
+    let mut malformed = vk_json;
+    malformed.vk_alphabeta_12[1].pop();
+    get_prepared_verifying_key(malformed).expect_err("alphabeta must have 2 coefficients");
+
+    // the prepared key can be cached in binary or in JSON
+    let cached: snarkpack::PreparedVerifyingKey = snarkjs_pvk.into();
+    let mut bytes = Vec::new();
+    cached.serialize(&mut bytes).unwrap();
+    let from_bytes = snarkpack::PreparedVerifyingKey::deserialize(&bytes[..]).unwrap();
+    assert_eq!(from_bytes, cached);
+    let json = serde_json::to_string(&cached).unwrap();
+    let from_json: snarkpack::PreparedVerifyingKey = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_json, cached);
+    let vk: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(vk["alpha_g1_beta_g2"], serde_json::json!(vk_alphabeta_12));
+    let tampered = json.replacen(vk["vk"]["alpha_g1"][0].as_str().unwrap(), "1", 1);
+    serde_json::from_str::<snarkpack::PreparedVerifyingKey>(&tampered)
+        .expect_err("alpha is not on the curve");
+
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test aggregation");
+    ver_transcript.append(b"public-inputs", &inputs);
+    snarkpack::verify_aggregate_proof_with_cached_key(
+        &ver_srs,
+        &from_json,
+        &inputs,
+        &aggregate_proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn gnark_groth16_aggregation() {
+    use snarkpack::{fr_from_str, read_gnark_proof, read_gnark_vk, SnarkJSProof, SnarkJSVK};
+    use std::{convert::TryFrom, fs::File};
+
+    // the fixtures are the snarkjs proofs of tests/secret in gnark's encoding,
+    // see tests/gnark/README.md: 000 is compressed as written by gnark before
+    // v0.9, 001 is uncompressed with the empty commitments written since v0.9
+    let nproofs = 2;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+
+    let vk =
+        read_gnark_vk::<Bn254, _>(File::open("tests/gnark/verification_key.bin").unwrap()).unwrap();
+    let file = File::open("tests/secret/000/verification_key.json").unwrap();
+    let vk_json: SnarkJSVK = serde_json::from_reader(file).unwrap();
+    assert_eq!(vk, vk_json.into());
+    let pvk = prepare_verifying_key(&vk);
+
+    let mut proofs = Vec::new();
+    let mut inputs = Vec::new();
+    for i in 0..nproofs {
+        let file = File::open(format!("tests/gnark/{:03}/proof.bin", i)).unwrap();
+        let proof = read_gnark_proof::<Bn254, _>(file).unwrap();
+        assert!(proof.commitments.is_empty());
+        assert_eq!(proof.commitment_pok.is_some(), i == 1);
+        let file = File::open(format!("tests/secret/{:03}/proof.json", i)).unwrap();
+        let proof_json: SnarkJSProof = serde_json::from_reader(file).unwrap();
+        let proof = ark_groth16::Proof::try_from(proof).unwrap();
+        assert_eq!(proof, proof_json.into());
+
+        let file = File::open(format!("tests/gnark/{:03}/public.json", i)).unwrap();
+        let public_json: Vec<String> = serde_json::from_reader(file).unwrap();
+        let public_inputs: Vec<Fr> = public_json.into_iter().map(fr_from_str).collect();
+        assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+        proofs.push(proof);
+        inputs.push(public_inputs);
+    }
+
+    let mut prover_transcript = snarkpack::transcript::new_merlin_transcript(b"test gnark");
+    prover_transcript.append(b"public-inputs", &inputs);
+    let aggregate_proof =
+        snarkpack::aggregate_proofs(&prover_srs, &mut prover_transcript, &proofs).unwrap();
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test gnark");
+    ver_transcript.append(b"public-inputs", &inputs);
+    snarkpack::verify_aggregate_proof(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &aggregate_proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+
+    // truncated proof
+    let bytes = std::fs::read("tests/gnark/001/proof.bin").unwrap();
+    read_gnark_proof::<Bn254, _>(&bytes[..bytes.len() - 1]).expect_err("truncated proof");
+    // a proof with one commitment, set to its A point, is not a Groth16 proof
+    let mut with_commitment = bytes[..256].to_vec();
+    with_commitment.extend_from_slice(&1u32.to_be_bytes());
+    with_commitment.extend_from_slice(&bytes[..64]);
+    with_commitment.extend_from_slice(&bytes[260..]);
+    let proof = read_gnark_proof::<Bn254, _>(&with_commitment[..]).unwrap();
+    assert_eq!(proof.commitments, vec![proof.proof.a]);
+    ark_groth16::Proof::try_from(proof).expect_err("proof with commitments");
+    // a key with a commitment key
+    let mut bytes = std::fs::read("tests/gnark/verification_key.bin").unwrap();
+    let len = bytes.len();
+    bytes[len - 1] = 1;
+    read_gnark_vk::<Bn254, _>(&bytes[..]).expect_err("key with commitments");
 }
 
+#[cfg(feature = "std")]
 fn json_to_g1(json: &Value, key: &str) -> G1Affine {
     let els: Vec<String> = json
         .get(key)
@@ -142,6 +846,7 @@ fn json_to_g1(json: &Value, key: &str) -> G1Affine {
     ))
 }
 
+#[cfg(feature = "std")]
 fn json_to_g1_vec(json: &Value, key: &str) -> Vec<G1Affine> {
     let els: Vec<Vec<String>> = json
         .get(key)
@@ -169,6 +874,7 @@ fn json_to_g1_vec(json: &Value, key: &str) -> Vec<G1Affine> {
         .collect()
 }
 
+#[cfg(feature = "std")]
 fn json_to_g2(json: &Value, key: &str) -> G2Affine {
     let els: Vec<Vec<String>> = json
         .get(key)
@@ -191,21 +897,12 @@ fn json_to_g2(json: &Value, key: &str) -> G2Affine {
     G2Affine::from(G2Projective::new(x, y, z))
 }
 
+#[cfg(feature = "std")]
 #[test]
+#[ignore = "needs tests/circuit_final.zkey, which is too large to check in"]
 fn verify_proof_with_zkey_with_r1cs() {
-    use ark_bn254::{G1Projective, G2Projective};
-    use ark_crypto_primitives::snark::SNARK;
-    use num_bigint::BigUint;
-    use serde_json::Value;
     use std::fs::File;
 
-    use ark_groth16::Groth16;
-    use ark_std::rand::thread_rng;
-    use num_traits::{One, Zero};
-    use std::str::FromStr;
-
-    use std::convert::TryFrom;
-
     let path = "./tests/circuit_final.zkey";
     let mut file = File::open(path).unwrap();
     let (params, _matrices) = read_zkey(&mut file).unwrap(); // binfile.proving_key().unwrap();
@@ -218,3 +915,253 @@ fn verify_proof_with_zkey_with_r1cs() {
     assert_eq!(json_to_g2(&json, "vk_delta_2"), params.vk.delta_g2);
     assert_eq!(json_to_g1_vec(&json, "IC"), params.vk.gamma_abc_g1);
 }
+
+#[test]
+#[cfg(feature = "std")]
+fn zkey_witness_groth16_aggregation() {
+    use ark_ff::{FpParameters, PrimeField};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_serialize::CanonicalSerialize;
+    use snarkpack::{create_random_proof_from_witness, read_witness, write_zkey};
+    use std::io::Cursor;
+
+    let num_constraints = 100;
+    let nproofs = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+    let params =
+        generate_random_parameters::<Bn254, _, _>(Benchmark::<Fr>::new(num_constraints), &mut rng)
+            .unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+
+    // the zkey and the witness file a circom circuit would come with
+    let cs = ConstraintSystem::new_ref();
+    Benchmark::<Fr>::new(num_constraints)
+        .generate_constraints(cs.clone())
+        .unwrap();
+    cs.finalize();
+    let matrices = cs.to_matrices().unwrap();
+    let mut zkey = Vec::new();
+    write_zkey(&mut zkey, &params, &matrices).unwrap();
+    let witness = {
+        let cs = cs.borrow().unwrap();
+        [&cs.instance_assignment[..], &cs.witness_assignment[..]].concat()
+    };
+    let mut header = 32u32.to_le_bytes().to_vec();
+    ark_bn254::FrParameters::MODULUS
+        .serialize(&mut header)
+        .unwrap();
+    header.extend_from_slice(&(witness.len() as u32).to_le_bytes());
+    let mut values = Vec::new();
+    for w in &witness {
+        w.into_repr().serialize(&mut values).unwrap();
+    }
+    let mut wtns = b"wtns".to_vec();
+    for n in &[2u32, 2, 1] {
+        wtns.extend_from_slice(&n.to_le_bytes());
+    }
+    wtns.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    wtns.extend_from_slice(&header);
+    wtns.extend_from_slice(&2u32.to_le_bytes());
+    wtns.extend_from_slice(&(values.len() as u64).to_le_bytes());
+    wtns.extend_from_slice(&values);
+
+    let (pk, matrices) = read_zkey(&mut Cursor::new(&zkey)).unwrap();
+    let witness = read_witness(&mut Cursor::new(&wtns)).unwrap();
+    let inputs = witness[1..matrices.num_instance_variables].to_vec();
+    let proofs = (0..nproofs)
+        .map(|_| create_random_proof_from_witness(&pk, &matrices, &witness, &mut rng).unwrap())
+        .collect::<Vec<_>>();
+    for proof in &proofs {
+        assert!(verify_proof(&pvk, proof, &inputs).unwrap());
+    }
+    // the proving key of Arkworks can be used as well
+    let proof = create_random_proof_from_witness(&params, &matrices, &witness, &mut rng).unwrap();
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+
+    let mut wrong = witness.clone();
+    wrong[3] += Fr::one();
+    let proof = create_random_proof_from_witness(&pk, &matrices, &wrong, &mut rng).unwrap();
+    assert!(!verify_proof(&pvk, &proof, &inputs).unwrap());
+    create_random_proof_from_witness(&pk, &matrices, &witness[1..], &mut rng)
+        .expect_err("witness too short");
+
+    let srs = snarkpack::srs::setup_fake_srs::<Bn254, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let all_inputs = vec![inputs; nproofs];
+    let mut prover_transcript = snarkpack::transcript::new_merlin_transcript(b"test zkey");
+    prover_transcript.append(b"public-inputs", &all_inputs);
+    let aggregate_proof =
+        snarkpack::aggregate_proofs(&prover_srs, &mut prover_transcript, &proofs).unwrap();
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test zkey");
+    ver_transcript.append(b"public-inputs", &all_inputs);
+    snarkpack::verify_aggregate_proof(
+        &ver_srs,
+        &pvk,
+        &all_inputs,
+        &aggregate_proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn bellperson_groth16_aggregation() {
+    use ark_bls12_381::{Bls12_381, Fr};
+    use snarkpack::{read_bellperson_proofs, read_bellperson_vk};
+    use std::{fs::File, str::FromStr};
+
+    // the fixtures are written by bellperson 0.18 for a circuit proving the
+    // knowledge of a and b with a * b = c and a + b = d, c and d public
+    let nproofs = 8;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+    let srs = snarkpack::srs::setup_fake_srs::<Bls12_381, _>(&mut rng, nproofs);
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+
+    let vk =
+        read_bellperson_vk(File::open("tests/bellperson/verification_key.bin").unwrap()).unwrap();
+    assert_eq!(vk.gamma_abc_g1.len(), 3);
+    let pvk = prepare_verifying_key(&vk);
+
+    let bytes = std::fs::read("tests/bellperson/proofs.bin").unwrap();
+    let proofs = read_bellperson_proofs(&bytes).unwrap();
+    assert_eq!(proofs.len(), nproofs);
+    let file = File::open("tests/bellperson/public.json").unwrap();
+    let public_json: Vec<Vec<String>> = serde_json::from_reader(file).unwrap();
+    let inputs: Vec<Vec<Fr>> = public_json
+        .iter()
+        .map(|row| row.iter().map(|s| Fr::from_str(s).unwrap()).collect())
+        .collect();
+    for (proof, public_inputs) in proofs.iter().zip(inputs.iter()) {
+        assert!(verify_proof(&pvk, proof, public_inputs).unwrap());
+    }
+
+    let mut prover_transcript = snarkpack::transcript::new_merlin_transcript(b"test bellperson");
+    prover_transcript.append(b"public-inputs", &inputs);
+    let aggregate_proof =
+        snarkpack::aggregate_proofs(&prover_srs, &mut prover_transcript, &proofs).unwrap();
+    let mut ver_transcript = snarkpack::transcript::new_merlin_transcript(b"test bellperson");
+    ver_transcript.append(b"public-inputs", &inputs);
+    snarkpack::verify_aggregate_proof(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &aggregate_proof,
+        &mut rng,
+        &mut ver_transcript,
+    )
+    .expect("error in verification");
+
+    // truncated proofs and the verifying key in place of the proofs
+    read_bellperson_proofs(&bytes[..bytes.len() - 1]).expect_err("truncated proofs");
+    let vk_bytes = std::fs::read("tests/bellperson/verification_key.bin").unwrap();
+    read_bellperson_proofs(&vk_bytes[..4 * 192]).expect_err("uncompressed points");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn bellperson_aggregate_compatibility() {
+    use ark_bls12_381::Fr;
+    use snarkpack::{
+        aggregate_proofs_bellperson, read_bellperson_aggregate, read_bellperson_proofs,
+        read_bellperson_srs, read_bellperson_vk, verify_aggregate_proof_bellperson,
+        write_bellperson_aggregate, AggregateVersion,
+    };
+    use std::{fs::File, str::FromStr};
+
+    // the SRS and the aggregates of the proofs of bellperson_groth16_aggregation
+    // are written by bellperson, with the versions 1 and 2 of its aggregation
+    let nproofs = 8;
+    let include = b"snarkpack test vectors";
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+    let srs = read_bellperson_srs(File::open("tests/bellperson/srs.bin").unwrap()).unwrap();
+    let (prover_srs, ver_srs) = srs.specialize(nproofs);
+    let vk =
+        read_bellperson_vk(File::open("tests/bellperson/verification_key.bin").unwrap()).unwrap();
+    let pvk = prepare_verifying_key(&vk);
+    let proofs =
+        read_bellperson_proofs(&std::fs::read("tests/bellperson/proofs.bin").unwrap()).unwrap();
+    let file = File::open("tests/bellperson/public.json").unwrap();
+    let public_json: Vec<Vec<String>> = serde_json::from_reader(file).unwrap();
+    let inputs: Vec<Vec<Fr>> = public_json
+        .iter()
+        .map(|row| row.iter().map(|s| Fr::from_str(s).unwrap()).collect())
+        .collect();
+
+    let expected = std::fs::read("tests/bellperson/aggregate_v1.bin").unwrap();
+    let bellperson_proof = read_bellperson_aggregate(&expected[..]).unwrap();
+    verify_aggregate_proof_bellperson(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &bellperson_proof,
+        &mut rng,
+        include,
+        AggregateVersion::V1,
+    )
+    .expect("bellperson proof should verify");
+
+    // the aggregation is deterministic so the same proof is produced here
+    let aggregate_proof =
+        aggregate_proofs_bellperson(&prover_srs, include, &proofs, AggregateVersion::V1).unwrap();
+    assert_eq!(aggregate_proof, bellperson_proof);
+    let mut bytes = Vec::new();
+    write_bellperson_aggregate(&aggregate_proof, &mut bytes).unwrap();
+    assert_eq!(bytes, expected);
+
+    verify_aggregate_proof_bellperson(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &bellperson_proof,
+        &mut rng,
+        b"other include",
+        AggregateVersion::V1,
+    )
+    .expect_err("different transcript");
+    verify_aggregate_proof_bellperson(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &bellperson_proof,
+        &mut rng,
+        include,
+        AggregateVersion::V2,
+    )
+    .expect_err("different version");
+
+    // same with the version 2, written by bellperson 0.21
+    let expected_v2 = std::fs::read("tests/bellperson/aggregate_v2.bin").unwrap();
+    let bellperson_proof_v2 = read_bellperson_aggregate(&expected_v2[..]).unwrap();
+    verify_aggregate_proof_bellperson(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &bellperson_proof_v2,
+        &mut rng,
+        include,
+        AggregateVersion::V2,
+    )
+    .expect("bellperson V2 proof should verify");
+    verify_aggregate_proof_bellperson(
+        &ver_srs,
+        &pvk,
+        &inputs,
+        &bellperson_proof_v2,
+        &mut rng,
+        include,
+        AggregateVersion::V1,
+    )
+    .expect_err("different version");
+
+    let proof_v2 =
+        aggregate_proofs_bellperson(&prover_srs, include, &proofs, AggregateVersion::V2).unwrap();
+    assert!(proof_v2 != aggregate_proof);
+    assert_eq!(proof_v2, bellperson_proof_v2);
+    let mut bytes = Vec::new();
+    write_bellperson_aggregate(&proof_v2, &mut bytes).unwrap();
+    assert_eq!(bytes, expected_v2);
+
+    read_bellperson_aggregate(&expected[..expected.len() - 1]).expect_err("truncated proof");
+}
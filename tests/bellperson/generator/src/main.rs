@@ -0,0 +1,148 @@
+//! Writes the test vectors of tests/bellperson with bellperson 0.21.0: a
+//! verifying key, 8 proofs of a small circuit with their public inputs, a
+//! fake SRS and the aggregate proofs of bellperson for the 8 proofs, with the
+//! versions 1 and 2 of its transcript. Run with the output directory as
+//! argument.
+use bellperson::groth16::*;
+use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+use blstrs::{Bls12, Scalar};
+use ff::{Field, PrimeField};
+use rand::SeedableRng;
+use std::fs;
+use std::ops::{AddAssign, MulAssign};
+
+#[derive(Clone)]
+struct Mul {
+    a: Option<Scalar>,
+    b: Option<Scalar>,
+}
+
+impl Circuit<Scalar> for Mul {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+        let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+        let c = cs.alloc_input(
+            || "c",
+            || {
+                let mut a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                a.mul_assign(&self.b.ok_or(SynthesisError::AssignmentMissing)?);
+                Ok(a)
+            },
+        )?;
+        let d = cs.alloc_input(
+            || "d",
+            || {
+                let mut a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                a.add_assign(&self.b.ok_or(SynthesisError::AssignmentMissing)?);
+                Ok(a)
+            },
+        )?;
+        cs.enforce(|| "mul", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+        cs.enforce(|| "add", |lc| lc + a + b, |lc| lc + CS::one(), |lc| lc + d);
+        Ok(())
+    }
+}
+
+fn dec(s: &Scalar) -> String {
+    // little-endian repr to decimal
+    let bytes = s.to_repr();
+    let mut digits: Vec<u32> = vec![0];
+    for byte in bytes.as_ref().iter().rev() {
+        let mut carry = *byte as u32;
+        for d in digits.iter_mut() {
+            let v = *d * 256 + carry;
+            *d = v % 10;
+            carry = v / 10;
+        }
+        while carry > 0 {
+            digits.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    while digits.len() > 1 && *digits.last().unwrap() == 0 {
+        digits.pop();
+    }
+    digits.iter().rev().map(|d| d.to_string()).collect()
+}
+
+fn main() {
+    let out = std::env::args().nth(1).unwrap();
+    let n = 8;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+    let params =
+        generate_random_parameters::<Bls12, _, _>(Mul { a: None, b: None }, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let mut vk = Vec::new();
+    params.vk.write(&mut vk).unwrap();
+    fs::write(format!("{}/verification_key.bin", out), vk).unwrap();
+
+    let mut proofs_bytes = Vec::new();
+    let mut inputs = Vec::new();
+    let mut statements = Vec::new();
+    for i in 0..n {
+        let a = Scalar::from(i as u64 + 3);
+        let b = Scalar::random(&mut rng);
+        let proof = create_random_proof(
+            Mul {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        let c = a * b;
+        let d = a + b;
+        assert!(verify_proof(&pvk, &proof, &[c, d]).unwrap());
+        proof.write(&mut proofs_bytes).unwrap();
+        statements.push(vec![c, d]);
+        inputs.push(format!("[\"{}\", \"{}\"]", dec(&c), dec(&d)));
+    }
+    fs::write(format!("{}/proofs.bin", out), &proofs_bytes).unwrap();
+
+    // aggregation test vectors
+    let srs = aggregate::setup_fake_srs::<Bls12, _>(&mut rng, n);
+    let mut srs_bytes = Vec::new();
+    srs.write(&mut srs_bytes).unwrap();
+    fs::write(format!("{}/srs.bin", out), srs_bytes).unwrap();
+    let (pk, vk) = srs.specialize(n);
+    let proofs = Proof::<Bls12>::read_many(&proofs_bytes, n).unwrap();
+    let include = b"snarkpack test vectors";
+    let agg = aggregate::aggregate_proofs(&pk, include, &proofs, aggregate::AggregateVersion::V1)
+        .unwrap();
+    let mut agg_bytes = Vec::new();
+    agg.write(&mut agg_bytes).unwrap();
+    fs::write(format!("{}/aggregate_v1.bin", out), agg_bytes).unwrap();
+    assert!(aggregate::verify_aggregate_proof(
+        &vk,
+        &pvk,
+        &mut rng,
+        &statements,
+        &agg,
+        include,
+        aggregate::AggregateVersion::V1
+    )
+    .unwrap());
+    fs::write(
+        format!("{}/public.json", out),
+        format!("[\n  {}\n]\n", inputs.join(",\n  ")),
+    )
+    .unwrap();
+
+    // written last so the files above do not depend on it
+    let version = aggregate::AggregateVersion::V2;
+    let agg = aggregate::aggregate_proofs(&pk, include, &proofs, version).unwrap();
+    let mut agg_bytes = Vec::new();
+    agg.write(&mut agg_bytes).unwrap();
+    fs::write(format!("{}/aggregate_v2.bin", out), agg_bytes).unwrap();
+    assert!(aggregate::verify_aggregate_proof(
+        &vk,
+        &pvk,
+        &mut rng,
+        &statements,
+        &agg,
+        include,
+        version
+    )
+    .unwrap());
+}
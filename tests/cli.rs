@@ -0,0 +1,320 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn snarkpack(args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_snarkpack"))
+        .args(args)
+        .status()
+        .expect("failed to run snarkpack")
+        .success()
+}
+
+fn path(p: &Path) -> &str {
+    p.to_str().unwrap()
+}
+
+#[test]
+fn cli_aggregate_and_verify() {
+    let tmp: PathBuf = std::env::temp_dir().join(format!("snarkpack-cli-{}", std::process::id()));
+    fs::create_dir_all(&tmp).unwrap();
+    let srs = tmp.join("srs.bin");
+    let prover_srs = tmp.join("prover.bin");
+    let verifier_srs = tmp.join("verifier.bin");
+    let proof = tmp.join("proof.bin");
+    let vk = "tests/secret/000/verification_key.json";
+
+    assert!(snarkpack(&[
+        "setup-fake",
+        "--size",
+        "4",
+        "--seed",
+        "1",
+        "--out",
+        path(&srs)
+    ]));
+    let imported = tmp.join("imported.bin");
+    assert!(snarkpack(&[
+        "import-srs",
+        "--input",
+        path(&srs),
+        "--max-proofs",
+        "2",
+        "--out",
+        path(&imported)
+    ]));
+    assert!(snarkpack(&["inspect", "--kind", "srs", path(&imported)]));
+    assert!(snarkpack(&[
+        "specialize",
+        "--srs",
+        path(&imported),
+        "--nproofs",
+        "2",
+        "--prover-out",
+        path(&prover_srs),
+        "--verifier-out",
+        path(&verifier_srs)
+    ]));
+    assert!(snarkpack(&[
+        "aggregate",
+        "--dir",
+        "tests/secret",
+        "--vk",
+        vk,
+        "--srs",
+        path(&prover_srs),
+        "--out",
+        path(&proof)
+    ]));
+    assert!(snarkpack(&["inspect", path(&proof)]));
+    assert!(snarkpack(&[
+        "verify",
+        "--dir",
+        "tests/secret",
+        "--vk",
+        vk,
+        "--srs",
+        path(&verifier_srs),
+        "--proof",
+        path(&proof)
+    ]));
+
+    // the same proofs listed in a different order must not verify
+    let swapped = tmp.join("swapped");
+    for (from, to) in [("000", "b"), ("001", "a")].iter() {
+        let dir = swapped.join(to);
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["proof.json", "public.json"].iter() {
+            fs::copy(
+                Path::new("tests/secret").join(from).join(file),
+                dir.join(file),
+            )
+            .unwrap();
+        }
+    }
+    assert!(!snarkpack(&[
+        "verify",
+        "--dir",
+        path(&swapped),
+        "--vk",
+        vk,
+        "--srs",
+        path(&verifier_srs),
+        "--proof",
+        path(&proof)
+    ]));
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn cli_merge_shards() {
+    let tmp: PathBuf =
+        std::env::temp_dir().join(format!("snarkpack-shards-{}", std::process::id()));
+    fs::create_dir_all(&tmp).unwrap();
+    let srs = tmp.join("srs.bin");
+    let prover_srs = tmp.join("prover.bin");
+    let verifier_srs = tmp.join("verifier.bin");
+    let proof = tmp.join("proof.bin");
+    let vk = "tests/secret/000/verification_key.json";
+
+    assert!(snarkpack(&[
+        "setup-fake",
+        "--size",
+        "2",
+        "--seed",
+        "2",
+        "--out",
+        path(&srs)
+    ]));
+    assert!(snarkpack(&[
+        "specialize",
+        "--srs",
+        path(&srs),
+        "--nproofs",
+        "2",
+        "--prover-out",
+        path(&prover_srs),
+        "--verifier-out",
+        path(&verifier_srs)
+    ]));
+
+    // one worker process per proof, running concurrently
+    let mut workers = Vec::new();
+    let mut shards = Vec::new();
+    for (offset, name) in ["000", "001"].iter().enumerate() {
+        let dir = tmp.join("proofs").join(name);
+        fs::create_dir_all(dir.join(name)).unwrap();
+        for file in ["proof.json", "public.json"].iter() {
+            fs::copy(
+                Path::new("tests/secret").join(name).join(file),
+                dir.join(name).join(file),
+            )
+            .unwrap();
+        }
+        let shard = tmp.join(format!("shard-{}.bin", name));
+        let worker = Command::new(env!("CARGO_BIN_EXE_snarkpack"))
+            .args([
+                "aggregate-shard",
+                "--dir",
+                path(&dir),
+                "--vk",
+                vk,
+                "--srs",
+                path(&prover_srs),
+                "--offset",
+                &offset.to_string(),
+                "--out",
+                path(&shard),
+            ])
+            .spawn()
+            .expect("failed to run snarkpack");
+        workers.push(worker);
+        shards.push(shard);
+    }
+    for mut worker in workers {
+        assert!(worker.wait().unwrap().success());
+    }
+    assert!(snarkpack(&["inspect", "--kind", "shard", path(&shards[1])]));
+
+    // shards are ordered by their offset, not by the command line
+    assert!(snarkpack(&[
+        "merge",
+        "--vk",
+        vk,
+        "--srs",
+        path(&prover_srs),
+        "--out",
+        path(&proof),
+        path(&shards[1]),
+        path(&shards[0])
+    ]));
+    assert!(snarkpack(&[
+        "verify",
+        "--dir",
+        "tests/secret",
+        "--vk",
+        vk,
+        "--srs",
+        path(&verifier_srs),
+        "--proof",
+        path(&proof),
+        "--bound"
+    ]));
+    assert!(!snarkpack(&[
+        "verify",
+        "--dir",
+        "tests/secret",
+        "--vk",
+        vk,
+        "--srs",
+        path(&verifier_srs),
+        "--proof",
+        path(&proof)
+    ]));
+    // a shard is missing
+    assert!(!snarkpack(&[
+        "merge",
+        "--vk",
+        vk,
+        "--srs",
+        path(&prover_srs),
+        "--out",
+        path(&proof),
+        path(&shards[1])
+    ]));
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn cli_aggregate_with_workers() {
+    let tmp: PathBuf =
+        std::env::temp_dir().join(format!("snarkpack-workers-{}", std::process::id()));
+    fs::create_dir_all(&tmp).unwrap();
+    let srs = tmp.join("srs.bin");
+    let prover_srs = tmp.join("prover.bin");
+    let verifier_srs = tmp.join("verifier.bin");
+    let proof = tmp.join("proof.bin");
+    let vk = "tests/secret/000/verification_key.json";
+
+    // the two proofs of the fixtures aggregated twice, so the workers fold
+    // their share before it is collected
+    let proofs = tmp.join("proofs");
+    for (i, name) in ["000", "001", "000", "001"].iter().enumerate() {
+        let dir = proofs.join(i.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["proof.json", "public.json"].iter() {
+            fs::copy(
+                Path::new("tests/secret").join(name).join(file),
+                dir.join(file),
+            )
+            .unwrap();
+        }
+    }
+    assert!(snarkpack(&[
+        "setup-fake",
+        "--size",
+        "4",
+        "--seed",
+        "3",
+        "--out",
+        path(&srs)
+    ]));
+    assert!(snarkpack(&[
+        "specialize",
+        "--srs",
+        path(&srs),
+        "--nproofs",
+        "4",
+        "--prover-out",
+        path(&prover_srs),
+        "--verifier-out",
+        path(&verifier_srs)
+    ]));
+
+    let mut workers = Vec::new();
+    let mut addrs = Vec::new();
+    for _ in 0..2 {
+        let mut worker = Command::new(env!("CARGO_BIN_EXE_snarkpack"))
+            .arg("worker")
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run snarkpack");
+        let mut line = String::new();
+        BufReader::new(worker.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        addrs.push(line.trim().trim_start_matches("listening on ").to_string());
+        workers.push(worker);
+    }
+    let aggregated = snarkpack(&[
+        "aggregate",
+        "--dir",
+        path(&proofs),
+        "--vk",
+        vk,
+        "--srs",
+        path(&prover_srs),
+        "--out",
+        path(&proof),
+        "--workers",
+        &addrs.join(","),
+    ]);
+    for mut worker in workers {
+        worker.kill().unwrap();
+        worker.wait().unwrap();
+    }
+    assert!(aggregated);
+    assert!(snarkpack(&[
+        "verify",
+        "--dir",
+        path(&proofs),
+        "--vk",
+        vk,
+        "--srs",
+        path(&verifier_srs),
+        "--proof",
+        path(&proof)
+    ]));
+    fs::remove_dir_all(&tmp).unwrap();
+}
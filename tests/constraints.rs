@@ -34,7 +34,7 @@ impl<F: Field> ConstraintSynthesizer<F> for Benchmark<F> {
 
         for i in 0..self.num_constraints - 1 {
             if i % 2 != 0 {
-                let c_val = a_val * &b_val;
+                let c_val = a_val * b_val;
                 let c_var = cs.new_witness_variable(|| Ok(c_val))?;
 
                 cs.enforce_constraint(lc!() + a_var, lc!() + b_var, lc!() + c_var)?;
@@ -45,7 +45,7 @@ impl<F: Field> ConstraintSynthesizer<F> for Benchmark<F> {
                 b_val = c_val;
                 b_var = c_var;
             } else {
-                let c_val = a_val + &b_val;
+                let c_val = a_val + b_val;
                 let c_var = cs.new_witness_variable(|| Ok(c_val))?;
 
                 cs.enforce_constraint(lc!() + a_var + b_var, lc!() + Variable::One, lc!() + c_var)?;
@@ -65,7 +65,7 @@ impl<F: Field> ConstraintSynthesizer<F> for Benchmark<F> {
         for (val, var) in assignments {
             a_lc = a_lc + var;
             b_lc = b_lc + var;
-            c_val = c_val + &val;
+            c_val += val;
         }
         c_val = c_val.square();
 
@@ -0,0 +1,56 @@
+# Writes the gnark fixtures from the snarkjs proofs of tests/secret, see
+# README.md. Run from the root of the repository.
+import json
+
+P = 21888242871839275222246405745257275088696311157297823662689037894645226208583
+
+
+def be(x):
+    return x.to_bytes(32, "big")
+
+
+def largest(y):
+    return y > P - y
+
+
+def g1(pt, raw=False):
+    x, y, z = [int(v) for v in pt]
+    assert z == 1
+    if raw:
+        return be(x) + be(y)
+    b = bytearray(be(x))
+    b[0] |= 0xC0 if largest(y) else 0x80
+    return bytes(b)
+
+
+def g2(pt, raw=False):
+    (x0, x1), (y0, y1), (z0, z1) = [[int(v) for v in c] for c in pt]
+    assert (z0, z1) == (1, 0)
+    if raw:
+        return be(x1) + be(x0) + be(y1) + be(y0)
+    lg = largest(y1) if y1 != 0 else largest(y0)
+    b = bytearray(be(x1) + be(x0))
+    b[0] |= 0xC0 if lg else 0x80
+    return bytes(b)
+
+
+# [beta]_1 and [delta]_1 are not in the snarkjs key: the generator is used
+G1GEN = ["1", "2", "1"]
+vk = json.load(open("tests/secret/000/verification_key.json"))
+out = g1(vk["vk_alpha_1"]) + g1(G1GEN) + g2(vk["vk_beta_2"]) + g2(vk["vk_gamma_2"])
+out += g1(G1GEN) + g2(vk["vk_delta_2"])
+out += len(vk["IC"]).to_bytes(4, "big") + b"".join(g1(p) for p in vk["IC"])
+# gnark v0.9+: no wires committed, no commitment key
+out += (0).to_bytes(4, "big") + (0).to_bytes(4, "big")
+open("tests/gnark/verification_key.bin", "wb").write(out)
+
+for d, raw in (("000", False), ("001", True)):
+    pr = json.load(open("tests/secret/%s/proof.json" % d))
+    out = g1(pr["pi_a"], raw) + g2(pr["pi_b"], raw) + g1(pr["pi_c"], raw)
+    if raw:
+        # gnark v0.9+: no commitments, proof of knowledge at infinity
+        out += (0).to_bytes(4, "big") + bytes(64)
+    open("tests/gnark/%s/proof.bin" % d, "wb").write(out)
+    open("tests/gnark/%s/public.json" % d, "w").write(
+        open("tests/secret/%s/public.json" % d).read()
+    )
@@ -0,0 +1,7 @@
+// Writes the gnark fixtures, see ../README.md. gnark-crypto is required at the
+// version gnark v0.10.0 depends on, which `go mod tidy` adds with go.sum.
+module github.com/nikkolasg/snarkpack/tests/gnark/generator
+
+go 1.21
+
+require github.com/consensys/gnark v0.10.0
@@ -0,0 +1,145 @@
+// Command generator writes the gnark fixtures of tests/gnark with gnark's own
+// serialization: the snarkjs verifying key and proofs of tests/secret are
+// converted to gnark's BN254 Groth16 types and written with WriteTo and
+// WriteRawTo. Run from this directory with the output directory as argument.
+package main
+
+import (
+	"encoding/json"
+	"fmt"
+	"io"
+	"os"
+	"path/filepath"
+
+	curve "github.com/consensys/gnark-crypto/ecc/bn254"
+	"github.com/consensys/gnark-crypto/ecc/bn254/fp"
+	groth16 "github.com/consensys/gnark/backend/groth16/bn254"
+)
+
+type snarkjsVK struct {
+	Alpha1 []string   `json:"vk_alpha_1"`
+	Beta2  [][]string `json:"vk_beta_2"`
+	Gamma2 [][]string `json:"vk_gamma_2"`
+	Delta2 [][]string `json:"vk_delta_2"`
+	IC     [][]string `json:"IC"`
+}
+
+type snarkjsProof struct {
+	A []string   `json:"pi_a"`
+	B [][]string `json:"pi_b"`
+	C []string   `json:"pi_c"`
+}
+
+func readJSON(path string, v interface{}) {
+	b, err := os.ReadFile(path)
+	check(err)
+	check(json.Unmarshal(b, v))
+}
+
+// g1 converts a snarkjs point in projective coordinates with z = 1.
+func g1(p []string) curve.G1Affine {
+	if len(p) != 3 || p[2] != "1" {
+		panic(fmt.Sprintf("unexpected G1 point %v", p))
+	}
+	var res curve.G1Affine
+	setString(&res.X, p[0])
+	setString(&res.Y, p[1])
+	if !res.IsOnCurve() || !res.IsInSubGroup() {
+		panic(fmt.Sprintf("invalid G1 point %v", p))
+	}
+	return res
+}
+
+// g2 converts a snarkjs point, whose coordinates are [c0, c1] for c0 + c1*u.
+func g2(p [][]string) curve.G2Affine {
+	if len(p) != 3 || p[2][0] != "1" || p[2][1] != "0" {
+		panic(fmt.Sprintf("unexpected G2 point %v", p))
+	}
+	var res curve.G2Affine
+	setString(&res.X.A0, p[0][0])
+	setString(&res.X.A1, p[0][1])
+	setString(&res.Y.A0, p[1][0])
+	setString(&res.Y.A1, p[1][1])
+	if !res.IsOnCurve() || !res.IsInSubGroup() {
+		panic(fmt.Sprintf("invalid G2 point %v", p))
+	}
+	return res
+}
+
+func setString(e *fp.Element, s string) {
+	_, err := e.SetString(s)
+	check(err)
+}
+
+func write(path string, w io.WriterTo) {
+	check(os.MkdirAll(filepath.Dir(path), 0o755))
+	f, err := os.Create(path)
+	check(err)
+	defer f.Close()
+	_, err = w.WriteTo(f)
+	check(err)
+}
+
+type rawWriter interface {
+	WriteRawTo(w io.Writer) (int64, error)
+}
+
+// raw writes with WriteRawTo, i.e. with uncompressed points.
+type raw struct{ rawWriter }
+
+func (r raw) WriteTo(w io.Writer) (int64, error) {
+	return r.WriteRawTo(w)
+}
+
+func copyFile(dst, src string) {
+	b, err := os.ReadFile(src)
+	check(err)
+	check(os.WriteFile(dst, b, 0o644))
+}
+
+func check(err error) {
+	if err != nil {
+		panic(err)
+	}
+}
+
+func main() {
+	if len(os.Args) != 2 {
+		panic("usage: generator <output directory>")
+	}
+	out := os.Args[1]
+	secret := filepath.Join("..", "..", "secret")
+
+	var vkJSON snarkjsVK
+	readJSON(filepath.Join(secret, "000", "verification_key.json"), &vkJSON)
+	// snarkjs keys have no [β]₁ and [δ]₁, the generator of G1 is used instead
+	_, _, gen, _ := curve.Generators()
+	var vk groth16.VerifyingKey
+	vk.G1.Alpha = g1(vkJSON.Alpha1)
+	vk.G1.Beta = gen
+	vk.G1.Delta = gen
+	for _, p := range vkJSON.IC {
+		vk.G1.K = append(vk.G1.K, g1(p))
+	}
+	vk.G2.Beta = g2(vkJSON.Beta2)
+	vk.G2.Gamma = g2(vkJSON.Gamma2)
+	vk.G2.Delta = g2(vkJSON.Delta2)
+	write(filepath.Join(out, "verification_key.bin"), &vk)
+
+	for i, name := range []string{"000", "001"} {
+		var proofJSON snarkjsProof
+		readJSON(filepath.Join(secret, name, "proof.json"), &proofJSON)
+		proof := groth16.Proof{
+			Ar:  g1(proofJSON.A),
+			Bs:  g2(proofJSON.B),
+			Krs: g1(proofJSON.C),
+		}
+		path := filepath.Join(out, name, "proof.bin")
+		if i == 0 {
+			write(path, &proof)
+		} else {
+			write(path, raw{&proof})
+		}
+		copyFile(filepath.Join(out, name, "public.json"), filepath.Join(secret, name, "public.json"))
+	}
+}